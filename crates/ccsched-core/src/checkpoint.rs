@@ -0,0 +1,193 @@
+use crate::error::{CcschedError, Result};
+use crate::models::{Checkpoint, CheckpointKind};
+use crate::snapshot;
+use crate::worktree::git;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+use tokio::process::Command;
+
+/// Size and modification time of every file under a directory, by relative path.
+pub type Manifest = BTreeMap<String, (u64, u128)>;
+
+/// Directory tarball checkpoints are written to.
+const TARBALL_DIR: &str = "./checkpoints";
+
+/// Path of the tarball checkpoint of a task.
+pub fn tarball_path(task_id: i64) -> String {
+    format!("{TARBALL_DIR}/task_{task_id}.tar.gz")
+}
+
+/// Tarball checkpoints found in the checkpoint directory, as task ID and path.
+pub fn tarballs() -> Result<Vec<(i64, String)>> {
+    let entries = match std::fs::read_dir(TARBALL_DIR) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut tarballs = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        if let Some(id) = entry
+            .file_name()
+            .to_str()
+            .and_then(|name| name.strip_prefix("task_")?.strip_suffix(".tar.gz"))
+            && let Ok(id) = id.parse()
+        {
+            tarballs.push((id, entry.path().to_string_lossy().to_string()));
+        }
+    }
+
+    Ok(tarballs)
+}
+
+/// Deletes what keeps a checkpoint outside the database: the snapshot refs of
+/// a repository or the tarball. Refs of a checkpoint taken in a worktree that
+/// has since been removed are deleted through `repo_dir`, a directory of the
+/// same repository.
+pub async fn remove(checkpoint: &Checkpoint, repo_dir: &str) -> Result<()> {
+    match checkpoint.kind {
+        CheckpointKind::Git => {
+            let dir = if Path::new(&checkpoint.root).exists() { checkpoint.root.as_str() } else { repo_dir };
+            snapshot::remove_refs(dir, checkpoint.task_id).await
+        }
+        CheckpointKind::Tarball => match std::fs::remove_file(&checkpoint.location) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        },
+    }
+}
+
+/// Lists the files under `root`, or returns `None` once their total size
+/// exceeds `max_bytes`. Symlinks are recorded but not followed.
+pub fn manifest(root: &str, max_bytes: u64) -> Result<Option<Manifest>> {
+    let mut manifest = Manifest::new();
+    let mut total = 0u64;
+    let mut pending = vec![Path::new(root).to_path_buf()];
+
+    while let Some(dir) = pending.pop() {
+        for entry in std::fs::read_dir(&dir)? {
+            let entry = entry?;
+            let metadata = entry.path().symlink_metadata()?;
+            if metadata.is_dir() {
+                pending.push(entry.path());
+                continue;
+            }
+
+            total += metadata.len();
+            if total > max_bytes {
+                return Ok(None);
+            }
+            let modified = metadata
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map(|time| time.as_nanos())
+                .unwrap_or_default();
+            let path = entry.path();
+            let relative = path.strip_prefix(root).unwrap_or(&path);
+            manifest.insert(relative.to_string_lossy().to_string(), (metadata.len(), modified));
+        }
+    }
+
+    Ok(Some(manifest))
+}
+
+/// Paths that were added, removed or modified between two manifests.
+pub fn manifest_changes(before: &Manifest, after: &Manifest) -> Vec<String> {
+    let paths: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+    paths
+        .into_iter()
+        .filter(|path| before.get(*path) != after.get(*path))
+        .cloned()
+        .collect()
+}
+
+/// Packs `root` into a gzipped tarball at `path`.
+pub async fn create_tarball(root: &str, path: &str) -> Result<()> {
+    if let Some(parent) = Path::new(path).parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let output = Command::new("tar").args(["-czf", path, "-C", root, "."]).output().await?;
+    if !output.status.success() {
+        return Err(CcschedError::Checkpoint(format!(
+            "Failed to create checkpoint {}: {}",
+            path,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(())
+}
+
+/// Files under `root` that differ between two git snapshot commits.
+pub async fn git_changes(root: &str, from: &str, to: &str) -> Result<Vec<String>> {
+    let names = git(root, &["diff", "--no-renames", "--name-only", "-z", from, to]).await?;
+    Ok(split_nul(&names))
+}
+
+/// Restores `files` under the repository `root` to their content in the
+/// snapshot commit, deleting those that did not exist yet. The index is left
+/// alone.
+pub async fn restore_git(root: &str, snapshot: &str, files: &[String]) -> Result<()> {
+    let mut args = vec!["--literal-pathspecs", "ls-tree", "-r", "--name-only", "-z", snapshot, "--"];
+    args.extend(files.iter().map(String::as_str));
+    let existing: BTreeSet<String> = split_nul(&git(root, &args).await?).into_iter().collect();
+
+    let restored: Vec<&str> = files.iter().map(String::as_str).filter(|file| existing.contains(*file)).collect();
+    if !restored.is_empty() {
+        let source = format!("--source={snapshot}");
+        let mut args = vec!["--literal-pathspecs", "restore", source.as_str(), "--worktree", "--"];
+        args.extend(restored);
+        git(root, &args).await?;
+    }
+
+    for file in files.iter().filter(|file| !existing.contains(*file)) {
+        remove_file(root, file)?;
+    }
+
+    Ok(())
+}
+
+/// Restores `files` under `root` from a tarball created by `create_tarball`,
+/// deleting those the tarball does not contain.
+pub async fn restore_tarball(root: &str, tarball: &str, before: &Manifest, files: &[String]) -> Result<()> {
+    let restored: Vec<String> = files
+        .iter()
+        .filter(|file| before.contains_key(*file))
+        .map(|file| format!("./{file}"))
+        .collect();
+    if !restored.is_empty() {
+        let output = Command::new("tar")
+            .args(["-xzf", tarball, "-C", root, "--"])
+            .args(&restored)
+            .output()
+            .await?;
+        if !output.status.success() {
+            return Err(CcschedError::Checkpoint(format!(
+                "Failed to restore from checkpoint {}: {}",
+                tarball,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+    }
+
+    for file in files.iter().filter(|file| !before.contains_key(*file)) {
+        remove_file(root, file)?;
+    }
+
+    Ok(())
+}
+
+fn split_nul(names: &str) -> Vec<String> {
+    names.split('\0').filter(|name| !name.is_empty()).map(str::to_string).collect()
+}
+
+fn remove_file(root: &str, file: &str) -> Result<()> {
+    match std::fs::remove_file(Path::new(root).join(file)) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
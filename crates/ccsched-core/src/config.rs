@@ -1,4 +1,5 @@
 use crate::error::{CcschedError, Result};
+use crate::models::{FailureKind, Hooks, PermissionMode};
 use std::collections::HashMap;
 use std::env;
 
@@ -8,6 +9,23 @@ pub struct Config {
     pub host: String,
     pub port: u16,
     pub claude_path: String,
+    pub max_concurrency: usize,
+    /// Default per-task wall-clock timeout in seconds
+    pub task_timeout: Option<u64>,
+    /// Default per-task idle-output timeout in seconds
+    pub idle_timeout: Option<u64>,
+    pub verification: VerificationConfig,
+    pub retry: RetryConfig,
+    pub budget: BudgetConfig,
+    pub permissions: PermissionConfig,
+    /// Accounts tasks can run under; there is always at least one
+    pub profiles: Vec<ProfileConfig>,
+    /// Directory task worktrees are created in
+    pub worktree_dir: String,
+    /// Hooks run around every task, in addition to the task's own
+    pub hooks: Hooks,
+    /// Largest working directory outside git that is checkpointed as a tarball; 0 disables them
+    pub checkpoint_max_bytes: u64,
     pub env_vars: HashMap<String, String>,
 }
 
@@ -29,6 +47,24 @@ impl Config {
         let claude_path = env::var("CLAUDE_PATH")
             .unwrap_or_else(|_| "claude".to_string());
 
+        let max_concurrency = env::var("CCSCHED_MAX_CONCURRENCY")
+            .unwrap_or_else(|_| "1".to_string())
+            .parse()
+            .map_err(|e| CcschedError::Config(format!("Invalid max concurrency: {e}")))?;
+        Self::validate_max_concurrency(max_concurrency)?;
+
+        let task_timeout = optional_secs_var("CCSCHED_TASK_TIMEOUT")?;
+        let idle_timeout = optional_secs_var("CCSCHED_IDLE_TIMEOUT")?;
+        let verification = VerificationConfig::from_env()?;
+        let retry = RetryConfig::from_env()?;
+        let budget = BudgetConfig::from_env()?;
+        let permissions = PermissionConfig::from_env()?;
+        let profiles = ProfileConfig::all_from_env()?;
+        let hooks = hooks_from_env();
+        let worktree_dir = env::var("CCSCHED_WORKTREE_DIR")
+            .unwrap_or_else(|_| "./worktrees".to_string());
+        let checkpoint_max_bytes = checkpoint_max_bytes_from_env()?;
+
         let env_vars = env::vars().collect();
 
         Ok(Self {
@@ -36,6 +72,17 @@ impl Config {
             host,
             port,
             claude_path,
+            max_concurrency,
+            task_timeout,
+            idle_timeout,
+            verification,
+            retry,
+            budget,
+            permissions,
+            profiles,
+            worktree_dir,
+            hooks,
+            checkpoint_max_bytes,
             env_vars,
         })
     }
@@ -44,6 +91,7 @@ impl Config {
         host: Option<String>,
         port: Option<u16>,
         claude_path: Option<String>,
+        max_concurrency: Option<usize>,
         env_file: Option<String>,
     ) -> Result<Self> {
         // 1. Load .env file (lowest priority)
@@ -63,6 +111,7 @@ impl Config {
         let env_host = env::var("CCSCHED_HOST").ok();
         let env_port = env::var("CCSCHED_PORT").ok();
         let env_claude_path = env::var("CLAUDE_PATH").ok();
+        let env_max_concurrency = env::var("CCSCHED_MAX_CONCURRENCY").ok();
 
         // 4. CLI arguments override environment variables (highest priority)
         let final_host = host
@@ -77,6 +126,27 @@ impl Config {
             .or(env_claude_path)
             .unwrap_or_else(|| "claude".to_string());
 
+        let final_max_concurrency = match (max_concurrency, env_max_concurrency) {
+            (Some(n), _) => n,
+            (None, Some(n)) => n
+                .parse()
+                .map_err(|e| CcschedError::Config(format!("Invalid max concurrency: {e}")))?,
+            (None, None) => 1,
+        };
+        Self::validate_max_concurrency(final_max_concurrency)?;
+
+        let task_timeout = optional_secs_var("CCSCHED_TASK_TIMEOUT")?;
+        let idle_timeout = optional_secs_var("CCSCHED_IDLE_TIMEOUT")?;
+        let verification = VerificationConfig::from_env()?;
+        let retry = RetryConfig::from_env()?;
+        let budget = BudgetConfig::from_env()?;
+        let permissions = PermissionConfig::from_env()?;
+        let profiles = ProfileConfig::all_from_env()?;
+        let hooks = hooks_from_env();
+        let worktree_dir = env::var("CCSCHED_WORKTREE_DIR")
+            .unwrap_or_else(|_| "./worktrees".to_string());
+        let checkpoint_max_bytes = checkpoint_max_bytes_from_env()?;
+
         let env_vars = env::vars().collect();
 
         Ok(Self {
@@ -84,11 +154,332 @@ impl Config {
             host: final_host,
             port: final_port,
             claude_path: final_claude_path,
+            max_concurrency: final_max_concurrency,
+            task_timeout,
+            idle_timeout,
+            verification,
+            retry,
+            budget,
+            permissions,
+            profiles,
+            worktree_dir,
+            hooks,
+            checkpoint_max_bytes,
             env_vars,
         })
     }
 
+    fn validate_max_concurrency(max_concurrency: usize) -> Result<()> {
+        if max_concurrency == 0 {
+            return Err(CcschedError::Config(
+                "Max concurrency must be at least 1".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
     pub fn bind_address(&self) -> String {
         format!("{}:{}", self.host, self.port)
     }
-}
\ No newline at end of file
+}
+
+/// Name of the profile used when `CCSCHED_PROFILES` is not set.
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// A named execution profile, typically one Claude account. Tasks run under any
+/// profile that is not rate limited, except that a task resuming a session stays
+/// with the profile holding it.
+#[derive(Debug, Clone)]
+pub struct ProfileConfig {
+    pub name: String,
+    /// Claude Code executable for this profile (default: the server's `claude_path`)
+    pub claude_path: Option<String>,
+    /// Environment variables set for every invocation, e.g. `CLAUDE_CONFIG_DIR`
+    pub env: HashMap<String, String>,
+}
+
+impl ProfileConfig {
+    /// Reads the profiles listed in `CCSCHED_PROFILES`. For a profile named
+    /// `work`, `CCSCHED_PROFILE_WORK_CLAUDE_PATH` overrides the executable and
+    /// every `CCSCHED_PROFILE_WORK_ENV_<NAME>` variable sets `<NAME>`.
+    fn all_from_env() -> Result<Vec<Self>> {
+        let names = env::var("CCSCHED_PROFILES").unwrap_or_default();
+        let names: Vec<&str> = names.split(',').map(str::trim).filter(|name| !name.is_empty()).collect();
+
+        if names.is_empty() {
+            return Ok(vec![Self {
+                name: DEFAULT_PROFILE.to_string(),
+                claude_path: None,
+                env: HashMap::new(),
+            }]);
+        }
+
+        let mut profiles: Vec<Self> = Vec::new();
+        for name in names {
+            if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+                return Err(CcschedError::Config(format!(
+                    "Invalid profile name '{name}': use letters, digits, '-' and '_'"
+                )));
+            }
+            if profiles.iter().any(|profile| profile.name == name) {
+                return Err(CcschedError::Config(format!("Duplicate profile name '{name}'")));
+            }
+
+            let prefix = format!("CCSCHED_PROFILE_{}_", name.to_uppercase().replace('-', "_"));
+            let env_prefix = format!("{prefix}ENV_");
+
+            profiles.push(Self {
+                name: name.to_string(),
+                claude_path: env::var(format!("{prefix}CLAUDE_PATH")).ok(),
+                env: env::vars()
+                    .filter_map(|(key, value)| {
+                        key.strip_prefix(&env_prefix).map(|key| (key.to_string(), value))
+                    })
+                    .collect(),
+            });
+        }
+
+        Ok(profiles)
+    }
+}
+
+pub const DEFAULT_VERIFICATION_TEMPLATE: &str = "{{prompt}}\n\n如果你确认任务成功，能够正确完成用户的每一个需求，则回复 {{success_marker}}；如果其中有的需求没有完成，再继续进行任务；如果你确认因为某些原因，在没有用户干预的情况下无法完成任务，则回复 {{failure_marker}}";
+
+/// Server-wide defaults for the verification rounds that follow a task's first
+/// pass. Tasks can override each of them at submit time.
+#[derive(Debug, Clone)]
+pub struct VerificationConfig {
+    /// Prompt sent on each verification round. `{{prompt}}`, `{{success_marker}}`
+    /// and `{{failure_marker}}` are substituted.
+    pub template: String,
+    pub success_marker: String,
+    pub failure_marker: String,
+    pub max_rounds: u32,
+}
+
+impl VerificationConfig {
+    fn from_env() -> Result<Self> {
+        let template = match env::var("CCSCHED_VERIFICATION_TEMPLATE_FILE") {
+            Ok(path) => std::fs::read_to_string(&path).map_err(|e| {
+                CcschedError::Config(format!("Failed to read verification template {path}: {e}"))
+            })?,
+            Err(_) => DEFAULT_VERIFICATION_TEMPLATE.to_string(),
+        };
+
+        let success_marker = env::var("CCSCHED_SUCCESS_MARKER")
+            .unwrap_or_else(|_| "CLAUDE_CODE_SCHEDULER_SUCCESS".to_string());
+
+        let failure_marker = env::var("CCSCHED_FAILURE_MARKER")
+            .unwrap_or_else(|_| "CLAUDE_CODE_SCHEDULER_FAILED".to_string());
+
+        // Every output contains an empty marker
+        for (name, marker) in [("CCSCHED_SUCCESS_MARKER", &success_marker), ("CCSCHED_FAILURE_MARKER", &failure_marker)] {
+            if marker.trim().is_empty() {
+                return Err(CcschedError::Config(format!("{name} must not be empty")));
+            }
+        }
+
+        let max_rounds = env::var("CCSCHED_MAX_VERIFICATION_ROUNDS")
+            .unwrap_or_else(|_| "3".to_string())
+            .parse()
+            .map_err(|e| CcschedError::Config(format!("Invalid max verification rounds: {e}")))?;
+        if max_rounds == 0 {
+            return Err(CcschedError::Config("Max verification rounds must be at least 1".to_string()));
+        }
+
+        Ok(Self {
+            template,
+            success_marker,
+            failure_marker,
+            max_rounds,
+        })
+    }
+}
+
+/// Server-wide retry policy for failed tasks. Tasks can override everything but
+/// the backoff cap at submit time.
+#[derive(Debug, Clone)]
+pub struct RetryConfig {
+    /// Total number of attempts; 1 disables retries
+    pub max_attempts: u32,
+    /// Delay before the first retry in seconds, doubled for every further one
+    pub backoff_secs: u64,
+    /// Upper bound for the retry delay in seconds
+    pub max_backoff_secs: u64,
+    pub retry_on: Vec<FailureKind>,
+}
+
+impl RetryConfig {
+    fn from_env() -> Result<Self> {
+        let max_attempts = env::var("CCSCHED_MAX_ATTEMPTS")
+            .unwrap_or_else(|_| "1".to_string())
+            .parse()
+            .map_err(|e| CcschedError::Config(format!("Invalid max attempts: {e}")))?;
+        if max_attempts == 0 {
+            return Err(CcschedError::Config("Max attempts must be at least 1".to_string()));
+        }
+
+        let backoff_secs = env::var("CCSCHED_RETRY_BACKOFF")
+            .unwrap_or_else(|_| "60".to_string())
+            .parse()
+            .map_err(|e| CcschedError::Config(format!("Invalid retry backoff: {e}")))?;
+
+        let max_backoff_secs = env::var("CCSCHED_MAX_RETRY_BACKOFF")
+            .unwrap_or_else(|_| "3600".to_string())
+            .parse()
+            .map_err(|e| CcschedError::Config(format!("Invalid max retry backoff: {e}")))?;
+
+        let retry_on = FailureKind::parse_list(
+            &env::var("CCSCHED_RETRY_ON").unwrap_or_else(|_| "crash,timeout".to_string()),
+        )
+        .map_err(CcschedError::Config)?;
+
+        Ok(Self {
+            max_attempts,
+            backoff_secs,
+            max_backoff_secs,
+            retry_on,
+        })
+    }
+
+    /// Delay before retrying after the given failed attempt (1-based): the base
+    /// delay doubled per attempt and capped, with the upper half randomized so
+    /// tasks that failed together don't retry in lockstep.
+    pub fn backoff(&self, base_secs: u64, failed_attempt: u32) -> u64 {
+        let exponent = failed_attempt.saturating_sub(1).min(32);
+        let delay = base_secs
+            .saturating_mul(1 << exponent)
+            .min(self.max_backoff_secs);
+        let half = delay / 2;
+        half + fastrand::u64(0..=delay - half)
+    }
+}
+
+/// Spending limits in USD. Unset or `0` disables a limit.
+#[derive(Debug, Clone)]
+pub struct BudgetConfig {
+    /// Default maximum cost of a single task
+    pub task_max_cost_usd: Option<f64>,
+    /// Maximum spend over any rolling 24 hours
+    pub daily_usd: Option<f64>,
+    /// Maximum spend over any rolling 7 days
+    pub weekly_usd: Option<f64>,
+}
+
+impl BudgetConfig {
+    fn from_env() -> Result<Self> {
+        Ok(Self {
+            task_max_cost_usd: optional_usd_var("CCSCHED_TASK_MAX_COST")?,
+            daily_usd: optional_usd_var("CCSCHED_DAILY_BUDGET")?,
+            weekly_usd: optional_usd_var("CCSCHED_WEEKLY_BUDGET")?,
+        })
+    }
+}
+
+/// Permission policy for Claude's tool use.
+#[derive(Debug, Clone)]
+pub struct PermissionConfig {
+    /// Mode for tasks submitted without one
+    pub default_mode: PermissionMode,
+    /// Most permissive mode the server runs tasks with
+    pub max_mode: PermissionMode,
+    /// Seconds a permission request waits for an answer before it is denied
+    pub approval_timeout_secs: u64,
+}
+
+impl PermissionConfig {
+    fn from_env() -> Result<Self> {
+        let mode_var = |name: &str, default: PermissionMode| -> Result<PermissionMode> {
+            match env::var(name) {
+                Ok(value) => value
+                    .parse()
+                    .map_err(|e| CcschedError::Config(format!("Invalid {name}: {e}"))),
+                Err(_) => Ok(default),
+            }
+        };
+
+        let max_mode = mode_var("CCSCHED_MAX_PERMISSION_MODE", PermissionMode::Skip)?;
+        let default_mode = mode_var("CCSCHED_PERMISSION_MODE", max_mode)?;
+        if default_mode > max_mode {
+            return Err(CcschedError::Config(format!(
+                "Default permission mode '{default_mode}' exceeds the maximum '{max_mode}'"
+            )));
+        }
+
+        let approval_timeout_secs = env::var("CCSCHED_APPROVAL_TIMEOUT")
+            .unwrap_or_else(|_| "3600".to_string())
+            .parse()
+            .map_err(|e| CcschedError::Config(format!("Invalid approval timeout: {e}")))?;
+
+        Ok(Self { default_mode, max_mode, approval_timeout_secs })
+    }
+
+    /// Fails if tasks may not run with `mode`.
+    pub fn check(&self, mode: PermissionMode) -> Result<()> {
+        if mode > self.max_mode {
+            return Err(CcschedError::PolicyViolation(format!(
+                "permission mode '{mode}' exceeds the server maximum '{}'",
+                self.max_mode
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Reads an optional amount in USD from the environment; unset or `0` means no
+/// limit.
+fn optional_usd_var(name: &str) -> Result<Option<f64>> {
+    match env::var(name) {
+        Ok(value) => {
+            let usd: f64 = value
+                .parse()
+                .map_err(|e| CcschedError::Config(format!("Invalid {name}: {e}")))?;
+            if !usd.is_finite() || usd < 0.0 {
+                return Err(CcschedError::Config(format!("Invalid {name}: must not be negative")));
+            }
+            Ok((usd > 0.0).then_some(usd))
+        }
+        Err(_) => Ok(None),
+    }
+}
+
+/// Server hooks from `CCSCHED_HOOK_PRE_RUN`, `CCSCHED_HOOK_POST_SUCCESS`,
+/// `CCSCHED_HOOK_POST_FAILURE` and `CCSCHED_HOOK_POST_ALWAYS`.
+fn hooks_from_env() -> Hooks {
+    let hook = |name: &str| {
+        env::var(format!("CCSCHED_HOOK_{name}"))
+            .ok()
+            .filter(|command| !command.trim().is_empty())
+    };
+    Hooks {
+        pre_run: hook("PRE_RUN"),
+        post_success: hook("POST_SUCCESS"),
+        post_failure: hook("POST_FAILURE"),
+        post_always: hook("POST_ALWAYS"),
+    }
+}
+
+/// Size limit for tarball checkpoints from `CCSCHED_CHECKPOINT_MAX_MB`.
+fn checkpoint_max_bytes_from_env() -> Result<u64> {
+    let max_mb: u64 = env::var("CCSCHED_CHECKPOINT_MAX_MB")
+        .unwrap_or_else(|_| "100".to_string())
+        .parse()
+        .map_err(|e| CcschedError::Config(format!("Invalid CCSCHED_CHECKPOINT_MAX_MB: {e}")))?;
+    max_mb
+        .checked_mul(1024 * 1024)
+        .ok_or_else(|| CcschedError::Config("Invalid CCSCHED_CHECKPOINT_MAX_MB: too large".to_string()))
+}
+
+/// Reads an optional number of seconds from the environment; unset or `0` means
+/// no limit.
+fn optional_secs_var(name: &str) -> Result<Option<u64>> {
+    match env::var(name) {
+        Ok(value) => {
+            let secs: u64 = value
+                .parse()
+                .map_err(|e| CcschedError::Config(format!("Invalid {name}: {e}")))?;
+            Ok((secs > 0).then_some(secs))
+        }
+        Err(_) => Ok(None),
+    }
+}
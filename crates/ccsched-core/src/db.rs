@@ -1,7 +1,12 @@
 use crate::error::{CcschedError, Result};
-use crate::models::{Task, TaskStatus};
+use crate::executor::DEFAULT_EXECUTOR;
+use crate::models::{
+    Approval, ApprovalStatus, AttemptKind, Checkpoint, CheckpointKind, CreateTaskRequest, Dependency,
+    DependencyCondition, FailureKind, PermissionMode, Run, RunStatus,
+    StatsResponse, Task, TaskAttempt, TaskDiff, TaskStatus, Usage, UsageGroup,
+};
 use chrono::{NaiveDateTime, Utc};
-use rusqlite::{params, Connection, OptionalExtension};
+use rusqlite::{params, Connection, OptionalExtension, Row};
 use std::collections::{HashMap, HashSet};
 use std::str::FromStr;
 use std::sync::{Arc, Mutex};
@@ -11,6 +16,25 @@ pub struct Database {
     conn: Arc<Mutex<Connection>>,
 }
 
+/// Tasks whose status changed because of how their dependencies finished.
+#[derive(Debug, Default)]
+pub struct DependentUpdates {
+    pub blocked: Vec<i64>,
+    pub skipped: Vec<i64>,
+    pub reopened: Vec<i64>,
+}
+
+/// How a pending task is settled when its dependencies can no longer all be met.
+enum Settlement {
+    /// Waits on the failed or cancelled ancestor with this ID
+    Blocked(i64),
+    /// Can never run, for this reason
+    Skipped(String),
+}
+
+/// Whether the dependency edge `td` on the task `dep` is satisfied.
+const DEPENDENCY_SATISFIED: &str = "((td.condition = 'success' AND dep.status = 'done') OR (td.condition = 'failure' AND dep.status = 'failed') OR (td.condition = 'any' AND dep.status IN ('done', 'failed', 'cancelled', 'skipped', 'blocked')))";
+
 impl Database {
     pub async fn new(database_url: &str) -> Result<Self> {
         // Extract file path from database URL
@@ -39,13 +63,14 @@ impl Database {
     fn run_migrations(conn: &Connection) -> Result<()> {
         // Create tasks table
         conn.execute(
-            r#"
+            &format!(
+                r#"
             CREATE TABLE IF NOT EXISTS tasks (
                 id INTEGER PRIMARY KEY AUTOINCREMENT,
                 name TEXT NOT NULL,
                 prompt TEXT NOT NULL,
                 cwd TEXT NOT NULL,
-                status TEXT NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'running', 'done', 'failed', 'waiting')),
+                status TEXT NOT NULL DEFAULT 'pending' CHECK ({}),
                 session_id TEXT,
                 submitted_at DATETIME NOT NULL DEFAULT (datetime('now', 'utc')),
                 finished_at DATETIME,
@@ -54,6 +79,8 @@ impl Database {
                 resume_at DATETIME
             )
             "#,
+                status_check_constraint()
+            ),
             [],
         )?;
 
@@ -71,11 +98,101 @@ impl Database {
             [],
         )?;
 
+        // Create task_attempts table
+        conn.execute(
+            r#"
+            CREATE TABLE IF NOT EXISTS task_attempts (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                task_id INTEGER NOT NULL,
+                attempt INTEGER NOT NULL DEFAULT 1,
+                kind TEXT NOT NULL CHECK (kind IN ('initial', 'verification', 'resume')),
+                started_at DATETIME NOT NULL,
+                finished_at DATETIME,
+                exit_code INTEGER,
+                session_id_in TEXT,
+                session_id_out TEXT,
+                log_start INTEGER NOT NULL,
+                log_end INTEGER,
+                result TEXT,
+                FOREIGN KEY (task_id) REFERENCES tasks(id) ON DELETE CASCADE
+            )
+            "#,
+            [],
+        )?;
+
+        // Create approvals table
+        conn.execute(
+            r#"
+            CREATE TABLE IF NOT EXISTS approvals (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                task_id INTEGER NOT NULL,
+                tool_name TEXT NOT NULL,
+                input TEXT NOT NULL,
+                status TEXT NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'approved', 'denied', 'expired')),
+                requested_at DATETIME NOT NULL,
+                decided_at DATETIME,
+                message TEXT,
+                FOREIGN KEY (task_id) REFERENCES tasks(id) ON DELETE CASCADE
+            )
+            "#,
+            [],
+        )?;
+
+        // Create task diffs table
+        conn.execute(
+            r#"
+            CREATE TABLE IF NOT EXISTS task_diffs (
+                task_id INTEGER PRIMARY KEY,
+                before_snapshot TEXT NOT NULL,
+                after_snapshot TEXT,
+                stat TEXT,
+                patch TEXT,
+                updated_at DATETIME NOT NULL,
+                FOREIGN KEY (task_id) REFERENCES tasks(id) ON DELETE CASCADE
+            )
+            "#,
+            [],
+        )?;
+
+        // Create checkpoints table
+        conn.execute(
+            r#"
+            CREATE TABLE IF NOT EXISTS checkpoints (
+                task_id INTEGER PRIMARY KEY,
+                kind TEXT NOT NULL CHECK (kind IN ('git', 'tarball')),
+                root TEXT NOT NULL,
+                location TEXT NOT NULL,
+                manifest TEXT,
+                changed_files TEXT,
+                created_at DATETIME NOT NULL,
+                rolled_back_at DATETIME,
+                FOREIGN KEY (task_id) REFERENCES tasks(id) ON DELETE CASCADE
+            )
+            "#,
+            [],
+        )?;
+
+        // Create runs table
+        conn.execute(
+            r#"
+            CREATE TABLE IF NOT EXISTS runs (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL,
+                created_at DATETIME NOT NULL
+            )
+            "#,
+            [],
+        )?;
+
         // Create indexes for better performance
         conn.execute(
             "CREATE INDEX IF NOT EXISTS idx_tasks_status ON tasks(status)",
             [],
         )?;
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_checkpoints_root ON checkpoints(root)",
+            [],
+        )?;
         conn.execute(
             "CREATE INDEX IF NOT EXISTS idx_tasks_session_id ON tasks(session_id)",
             [],
@@ -88,6 +205,14 @@ impl Database {
             "CREATE INDEX IF NOT EXISTS idx_task_dependencies_depends_on_id ON task_dependencies(depends_on_id)",
             [],
         )?;
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_task_attempts_task_id ON task_attempts(task_id)",
+            [],
+        )?;
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_approvals_status ON approvals(status)",
+            [],
+        )?;
 
         // Migration: Add resume_at column if it doesn't exist
         let _ = conn.execute("ALTER TABLE tasks ADD COLUMN resume_at DATETIME", []);
@@ -95,245 +220,1101 @@ impl Database {
         // Migration: Add result column if it doesn't exist
         let _ = conn.execute("ALTER TABLE tasks ADD COLUMN result TEXT", []);
 
+        // Migration: Add timeout columns if they don't exist
+        let _ = conn.execute("ALTER TABLE tasks ADD COLUMN timeout_secs INTEGER", []);
+        let _ = conn.execute("ALTER TABLE tasks ADD COLUMN idle_timeout_secs INTEGER", []);
+
+        // Migration: Add failure_reason column if it doesn't exist
+        let _ = conn.execute("ALTER TABLE tasks ADD COLUMN failure_reason TEXT", []);
+
+        // Migration: Add executor column if it doesn't exist
+        let _ = conn.execute("ALTER TABLE tasks ADD COLUMN executor TEXT NOT NULL DEFAULT 'claude'", []);
+
+        // Migration: Add verification columns if they don't exist
+        let _ = conn.execute("ALTER TABLE tasks ADD COLUMN verify INTEGER NOT NULL DEFAULT 1", []);
+        let _ = conn.execute("ALTER TABLE tasks ADD COLUMN verification_template TEXT", []);
+        let _ = conn.execute("ALTER TABLE tasks ADD COLUMN success_marker TEXT", []);
+        let _ = conn.execute("ALTER TABLE tasks ADD COLUMN failure_marker TEXT", []);
+        let _ = conn.execute("ALTER TABLE tasks ADD COLUMN max_verification_rounds INTEGER", []);
+        let _ = conn.execute("ALTER TABLE tasks ADD COLUMN verification_rounds INTEGER NOT NULL DEFAULT 0", []);
+
+        // Migration: Add verification command columns if they don't exist
+        let _ = conn.execute("ALTER TABLE tasks ADD COLUMN verify_cmd TEXT", []);
+        let _ = conn.execute("ALTER TABLE tasks ADD COLUMN check_output TEXT", []);
+
+        // Migration: Add retry policy columns if they don't exist
+        let _ = conn.execute("ALTER TABLE tasks ADD COLUMN failure_kind TEXT", []);
+        let _ = conn.execute("ALTER TABLE tasks ADD COLUMN attempt INTEGER NOT NULL DEFAULT 1", []);
+        let _ = conn.execute("ALTER TABLE tasks ADD COLUMN max_attempts INTEGER", []);
+        let _ = conn.execute("ALTER TABLE tasks ADD COLUMN retry_backoff_secs INTEGER", []);
+        let _ = conn.execute("ALTER TABLE tasks ADD COLUMN retry_on TEXT", []);
+        let _ = conn.execute("ALTER TABLE tasks ADD COLUMN retry_at DATETIME", []);
+
+        // Migration: Add max_cost_usd column if it doesn't exist
+        let _ = conn.execute("ALTER TABLE tasks ADD COLUMN max_cost_usd REAL", []);
+
+        // Migration: Add permission_mode column if it doesn't exist
+        let _ = conn.execute("ALTER TABLE tasks ADD COLUMN permission_mode TEXT", []);
+
+        // Migration: Add hooks column if it doesn't exist
+        let _ = conn.execute("ALTER TABLE tasks ADD COLUMN hooks TEXT", []);
+
+        // Migration: Add claude_options column if it doesn't exist
+        let _ = conn.execute("ALTER TABLE tasks ADD COLUMN claude_options TEXT", []);
+
+        // Migration: Add worktree columns if they don't exist
+        let _ = conn.execute("ALTER TABLE tasks ADD COLUMN worktree BOOLEAN NOT NULL DEFAULT 0", []);
+        let _ = conn.execute("ALTER TABLE tasks ADD COLUMN base_ref TEXT", []);
+        let _ = conn.execute("ALTER TABLE tasks ADD COLUMN worktree_path TEXT", []);
+        let _ = conn.execute("ALTER TABLE tasks ADD COLUMN branch TEXT", []);
+        let _ = conn.execute("ALTER TABLE tasks ADD COLUMN base_commit TEXT", []);
+        let _ = conn.execute("ALTER TABLE tasks ADD COLUMN final_commit TEXT", []);
+
+        // Migration: Add condition column to task_dependencies if it doesn't exist
+        let _ = conn.execute(
+            "ALTER TABLE task_dependencies ADD COLUMN condition TEXT NOT NULL DEFAULT 'success' CHECK (condition IN ('success', 'failure', 'any'))",
+            [],
+        );
+
+        // Migration: Add blocked_by column if it doesn't exist
+        let _ = conn.execute("ALTER TABLE tasks ADD COLUMN blocked_by INTEGER", []);
+
+        // Migration: Add run_id column if it doesn't exist
+        let _ = conn.execute("ALTER TABLE tasks ADD COLUMN run_id INTEGER REFERENCES runs(id)", []);
+        conn.execute("CREATE INDEX IF NOT EXISTS idx_tasks_run_id ON tasks(run_id)", [])?;
+
+        // Migration: Add prompt template columns if they don't exist
+        let _ = conn.execute("ALTER TABLE tasks ADD COLUMN vars TEXT", []);
+        let _ = conn.execute("ALTER TABLE tasks ADD COLUMN rendered_prompt TEXT", []);
+
+        // Migration: Add session continuation columns if they don't exist
+        let _ = conn.execute("ALTER TABLE tasks ADD COLUMN continue_from INTEGER", []);
+        let _ = conn.execute("ALTER TABLE tasks ADD COLUMN fork_session BOOLEAN NOT NULL DEFAULT 0", []);
+        let _ = conn.execute("ALTER TABLE tasks ADD COLUMN parent_session_id TEXT", []);
+
+        // Migration: Add run_time_ms column if it doesn't exist
+        let _ = conn.execute("ALTER TABLE tasks ADD COLUMN run_time_ms INTEGER NOT NULL DEFAULT 0", []);
+
+        // Migration: Add profile columns if they don't exist
+        let _ = conn.execute("ALTER TABLE tasks ADD COLUMN profile TEXT", []);
+        let _ = conn.execute("ALTER TABLE task_attempts ADD COLUMN profile TEXT", []);
+
+        // Migration: Add usage columns to attempts and their per-task rollup
+        for table in ["task_attempts", "tasks"] {
+            for column in [
+                "input_tokens INTEGER NOT NULL DEFAULT 0",
+                "output_tokens INTEGER NOT NULL DEFAULT 0",
+                "cache_read_tokens INTEGER NOT NULL DEFAULT 0",
+                "cache_creation_tokens INTEGER NOT NULL DEFAULT 0",
+                "cost_usd REAL NOT NULL DEFAULT 0",
+                "num_turns INTEGER NOT NULL DEFAULT 0",
+                "duration_ms INTEGER NOT NULL DEFAULT 0",
+            ] {
+                let _ = conn.execute(&format!("ALTER TABLE {table} ADD COLUMN {column}"), []);
+            }
+        }
+
+        // Migration: Allow newly introduced task statuses
+        Self::migrate_status_constraint(conn)?;
+
+        Ok(())
+    }
+
+    /// SQLite cannot alter a CHECK constraint in place, so when the set of task
+    /// statuses grows the tasks table is rebuilt with the new constraint.
+    fn migrate_status_constraint(conn: &Connection) -> Result<()> {
+        let table_sql: String = conn.query_row(
+            "SELECT sql FROM sqlite_master WHERE type = 'table' AND name = 'tasks'",
+            [],
+            |row| row.get(0),
+        )?;
+
+        let check = status_check_constraint();
+        if table_sql.contains(&check) {
+            return Ok(());
+        }
+
+        let start = table_sql.find("status IN (").ok_or_else(|| {
+            CcschedError::Config("Unrecognized tasks table schema".to_string())
+        })?;
+        let end = table_sql[start..].find(')').map(|i| start + i + 1).ok_or_else(|| {
+            CcschedError::Config("Unrecognized tasks table schema".to_string())
+        })?;
+        let columns_start = table_sql.find('(').unwrap_or(0);
+        let new_sql = format!(
+            "CREATE TABLE tasks_new {}{}{}",
+            &table_sql[columns_start..start],
+            check,
+            &table_sql[end..]
+        );
+
+        let tx = conn.unchecked_transaction()?;
+        tx.execute(&new_sql, [])?;
+        tx.execute("INSERT INTO tasks_new SELECT * FROM tasks", [])?;
+        tx.execute("DROP TABLE tasks", [])?;
+        tx.execute("ALTER TABLE tasks_new RENAME TO tasks", [])?;
+        tx.execute("CREATE INDEX IF NOT EXISTS idx_tasks_status ON tasks(status)", [])?;
+        tx.execute("CREATE INDEX IF NOT EXISTS idx_tasks_session_id ON tasks(session_id)", [])?;
+        tx.commit()?;
+
         Ok(())
     }
 
-    pub async fn create_task(
+    pub async fn create_task(&self, request: &CreateTaskRequest) -> Result<i64> {
+        let conn = self.conn.lock().unwrap();
+        let tx = conn.unchecked_transaction()?;
+        let task_id = Self::insert_task(&tx, request, None)?;
+        tx.commit()?;
+        Ok(task_id)
+    }
+
+    /// Creates a run of several tasks, with dependencies among them given as the
+    /// index of the dependent task, the index of its dependency and the condition.
+    /// Either everything is created or nothing is. Returns the run ID and the task
+    /// IDs in order.
+    pub async fn create_run(
         &self,
         name: &str,
-        prompt: &str,
-        cwd: &str,
-        dependencies: &[i64],
-    ) -> Result<i64> {
+        requests: &[CreateTaskRequest],
+        dependencies: &[(usize, usize, DependencyCondition)],
+    ) -> Result<(i64, Vec<i64>)> {
         let conn = self.conn.lock().unwrap();
         let tx = conn.unchecked_transaction()?;
 
+        tx.execute(
+            "INSERT INTO runs (name, created_at) VALUES (?, ?)",
+            params![name, Utc::now().naive_utc()],
+        )?;
+        let run_id = tx.last_insert_rowid();
+
+        let mut task_ids = Vec::with_capacity(requests.len());
+        for request in requests {
+            task_ids.push(Self::insert_task(&tx, request, Some(run_id))?);
+        }
+        for &(task, dependency, condition) in dependencies {
+            tx.execute(
+                "INSERT INTO task_dependencies (task_id, depends_on_id, condition) VALUES (?, ?, ?)",
+                params![task_ids[task], task_ids[dependency], condition.to_string()],
+            )?;
+        }
+
+        tx.commit()?;
+        Ok((run_id, task_ids))
+    }
+
+    fn insert_task(tx: &Connection, request: &CreateTaskRequest, run_id: Option<i64>) -> Result<i64> {
         let status = TaskStatus::Pending.to_string();
         let submitted_at = Utc::now().naive_utc();
 
         tx.execute(
-            "INSERT INTO tasks (name, prompt, cwd, status, submitted_at) VALUES (?, ?, ?, ?, ?)",
-            params![name, prompt, cwd, status, submitted_at],
+            "INSERT INTO tasks (name, prompt, cwd, status, submitted_at, timeout_secs, idle_timeout_secs, executor, verify, verification_template, success_marker, failure_marker, max_verification_rounds, verify_cmd, max_attempts, retry_backoff_secs, retry_on, max_cost_usd, claude_options, permission_mode, hooks, worktree, base_ref, run_id, vars, continue_from, fork_session) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            params![
+                request.name,
+                request.prompt,
+                request.cwd,
+                status,
+                submitted_at,
+                request.timeout_secs,
+                request.idle_timeout_secs,
+                request.executor.as_deref().unwrap_or(DEFAULT_EXECUTOR),
+                request.verify.unwrap_or(true),
+                request.verification_template,
+                request.success_marker,
+                request.failure_marker,
+                request.max_verification_rounds,
+                request.verify_cmd,
+                request.max_attempts,
+                request.retry_backoff_secs,
+                request.retry_on.as_deref().map(FailureKind::join),
+                request.max_cost_usd,
+                (!request.claude_options.is_default())
+                    .then(|| serde_json::to_string(&request.claude_options))
+                    .transpose()?,
+                request.permission_mode.map(|mode| mode.to_string()),
+                (!request.hooks.is_default())
+                    .then(|| serde_json::to_string(&request.hooks))
+                    .transpose()?,
+                request.worktree,
+                request.base_ref,
+                run_id,
+                (!request.vars.is_empty())
+                    .then(|| serde_json::to_string(&request.vars))
+                    .transpose()?,
+                request.continue_from,
+                request.fork_session,
+            ],
         )?;
         let task_id = tx.last_insert_rowid();
 
         // Insert dependencies
-        for &dep_id in dependencies {
+        for dependency in &request.depends_on {
             tx.execute(
-                "INSERT INTO task_dependencies (task_id, depends_on_id) VALUES (?, ?)",
-                params![task_id, dep_id],
+                "INSERT INTO task_dependencies (task_id, depends_on_id, condition) VALUES (?, ?, ?)",
+                params![task_id, dependency.id, dependency.condition.to_string()],
             )?;
         }
 
-        tx.commit()?;
         Ok(task_id)
     }
 
-    pub async fn get_task(&self, id: i64) -> Result<Task> {
+    pub async fn list_runs(&self) -> Result<Vec<Run>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT id, name, created_at FROM runs ORDER BY id")?;
+        let rows = stmt.query_map([], |row| Ok((row.get("id")?, row.get("name")?, row.get("created_at")?)))?;
+
+        let mut runs = Vec::new();
+        for row in rows {
+            let (id, name, created_at) = row?;
+            runs.push(Self::load_run(&conn, id, name, created_at)?.0);
+        }
+
+        Ok(runs)
+    }
+
+    /// A run and its tasks.
+    pub async fn get_run(&self, id: i64) -> Result<(Run, Vec<Task>)> {
+        let conn = self.conn.lock().unwrap();
+        let (name, created_at) = conn
+            .query_row("SELECT name, created_at FROM runs WHERE id = ?", params![id], |row| {
+                Ok((row.get("name")?, row.get("created_at")?))
+            })
+            .optional()?
+            .ok_or_else(|| CcschedError::Config(format!("Run not found: {id}")))?;
+
+        Self::load_run(&conn, id, name, created_at)
+    }
+
+    fn load_run(conn: &Connection, id: i64, name: String, created_at: NaiveDateTime) -> Result<(Run, Vec<Task>)> {
+        let mut stmt = conn.prepare(&format!("SELECT {TASK_COLUMNS} FROM tasks WHERE run_id = ? ORDER BY id"))?;
+        let rows = stmt.query_map(params![id], task_from_row)?;
+        let mut tasks = Vec::new();
+        for row in rows {
+            tasks.push(row?);
+        }
+
+        let started_at: Option<NaiveDateTime> = conn.query_row(
+            "SELECT MIN(a.started_at) FROM task_attempts a JOIN tasks t ON t.id = a.task_id WHERE t.run_id = ?",
+            params![id],
+            |row| row.get(0),
+        )?;
+        let statuses: Vec<TaskStatus> = tasks.iter().map(|task| task.status.clone()).collect();
+        let status = RunStatus::of(&statuses);
+        let finished_at = if status.is_finished() {
+            tasks.iter().filter_map(|task| task.finished_at).max()
+        } else {
+            None
+        };
+
+        let run = Run {
+            id,
+            name,
+            created_at,
+            status,
+            started_at,
+            finished_at,
+            task_count: tasks.len(),
+            done_count: statuses.iter().filter(|status| **status == TaskStatus::Done).count(),
+            cost_usd: tasks.iter().map(|task| task.usage.cost_usd).sum(),
+        };
+        Ok((run, tasks))
+    }
+
+    /// Queues the failed, cancelled and skipped tasks of a run to run again from
+    /// scratch. Returns their IDs.
+    pub async fn retry_run(&self, id: i64) -> Result<Vec<i64>> {
+        let conn = self.conn.lock().unwrap();
+        let tx = conn.unchecked_transaction()?;
+
+        let mut stmt = tx.prepare(
+            "SELECT id FROM tasks WHERE run_id = ? AND status IN ('failed', 'cancelled', 'skipped') ORDER BY id",
+        )?;
+        let rows = stmt.query_map(params![id], |row| row.get::<_, i64>("id"))?;
+        let mut task_ids = Vec::new();
+        for row in rows {
+            task_ids.push(row?);
+        }
+        drop(stmt);
+
+        for task_id in &task_ids {
+            tx.execute(
+                "UPDATE tasks SET status = 'pending', session_id = NULL, finished_at = NULL, output = NULL, result = NULL, resume_at = NULL, failure_reason = NULL, verification_rounds = 0, check_output = NULL, failure_kind = NULL, attempt = 1, retry_at = NULL, blocked_by = NULL, rendered_prompt = NULL, run_time_ms = 0 WHERE id = ?",
+                params![task_id],
+            )?;
+        }
+
+        tx.commit()?;
+        Ok(task_ids)
+    }
+
+    pub async fn get_task(&self, id: i64) -> Result<Task> {
+        let conn = self.conn.lock().unwrap();
+        
+        let row = conn.query_row(
+            &format!("SELECT {TASK_COLUMNS} FROM tasks WHERE id = ?"),
+            params![id],
+            task_from_row,
+        ).optional()?
+        .ok_or(CcschedError::TaskNotFound(id))?;
+
+        Ok(row)
+    }
+
+    pub async fn get_task_by_session_id(&self, session_id: &str) -> Result<Task> {
+        let conn = self.conn.lock().unwrap();
+        
+        let row = conn.query_row(
+            &format!("SELECT {TASK_COLUMNS} FROM tasks WHERE session_id = ?"),
+            params![session_id],
+            task_from_row,
+        ).optional()?
+        .ok_or_else(|| CcschedError::Config(format!("Task not found for session_id: {session_id}")))?;
+
+        Ok(row)
+    }
+
+    pub async fn list_tasks(&self) -> Result<Vec<Task>> {
+        let conn = self.conn.lock().unwrap();
+        
+        let mut stmt = conn.prepare(
+            &format!("SELECT {TASK_COLUMNS} FROM tasks ORDER BY submitted_at ASC")
+        )?;
+
+        let rows = stmt.query_map([], task_from_row)?;
+
+        let mut tasks = Vec::new();
+        for row in rows {
+            tasks.push(row?);
+        }
+
+        Ok(tasks)
+    }
+
+    pub async fn update_task_status(
+        &self,
+        id: i64,
+        status: TaskStatus,
+        session_id: Option<&str>,
+        finished_at: Option<NaiveDateTime>,
+    ) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        let status_str = status.to_string();
+
+        conn.execute(
+            "UPDATE tasks SET status = ?, session_id = COALESCE(?, session_id), finished_at = COALESCE(?, finished_at) WHERE id = ?",
+            params![status_str, session_id, finished_at, id],
+        )?;
+
+        Ok(())
+    }
+
+    pub async fn update_task_status_with_resume_at(
+        &self,
+        id: i64,
+        status: TaskStatus,
+        session_id: Option<&str>,
+        finished_at: Option<NaiveDateTime>,
+        resume_at: Option<NaiveDateTime>,
+    ) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        let status_str = status.to_string();
+
+        conn.execute(
+            "UPDATE tasks SET status = ?, session_id = COALESCE(?, session_id), finished_at = COALESCE(?, finished_at), resume_at = ? WHERE id = ?",
+            params![status_str, session_id, finished_at, resume_at, id],
+        )?;
+
+        Ok(())
+    }
+
+    pub async fn update_task_verification_rounds(&self, id: i64, rounds: u32) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE tasks SET verification_rounds = ? WHERE id = ?",
+            params![rounds, id],
+        )?;
+
+        Ok(())
+    }
+
+    /// Adds to the time the task's current attempt has run, when it stops
+    /// before finishing and resumes later.
+    pub async fn add_task_run_time(&self, id: i64, run_time_ms: u64) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE tasks SET run_time_ms = run_time_ms + ? WHERE id = ?",
+            params![run_time_ms, id],
+        )?;
+
+        Ok(())
+    }
+
+    pub async fn update_task_check_output(&self, id: i64, check_output: &str) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE tasks SET check_output = ? WHERE id = ?",
+            params![check_output, id],
+        )?;
+
+        Ok(())
+    }
+
+    pub async fn mark_task_failed(&self, id: i64, reason: &str, kind: FailureKind) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE tasks SET status = 'failed', finished_at = ?, failure_reason = ?, failure_kind = ? WHERE id = ?",
+            params![Utc::now().naive_utc(), reason, kind.to_string(), id],
+        )?;
+
+        Ok(())
+    }
+
+    /// Re-queues a failed task as a fresh attempt that is not started before
+    /// `retry_at`. The reason of the failed attempt is kept until then.
+    pub async fn schedule_task_retry(
+        &self,
+        id: i64,
+        attempt: u32,
+        retry_at: NaiveDateTime,
+        reason: &str,
+        kind: FailureKind,
+    ) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE tasks SET status = 'pending', session_id = NULL, finished_at = NULL, output = NULL, result = NULL, resume_at = NULL, verification_rounds = 0, check_output = NULL, run_time_ms = 0, attempt = ?, retry_at = ?, failure_reason = ?, failure_kind = ? WHERE id = ?",
+            params![attempt, retry_at, reason, kind.to_string(), id],
+        )?;
+
+        Ok(())
+    }
+
+    pub async fn update_task_result(&self, id: i64, result: Option<&str>) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        let updated = conn.execute(
+            "UPDATE tasks SET result = ? WHERE id = ?",
+            params![result, id],
+        )?;
+        
+        if updated == 0 {
+            return Err(CcschedError::Config(format!("Task not found: {id}")));
+        }
+        
+        Ok(())
+    }
+
+    pub async fn update_task_output_and_result(&self, id: i64, output: Option<&str>, result: Option<&str>) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        let updated = conn.execute(
+            "UPDATE tasks SET output = ?, result = ? WHERE id = ?",
+            params![output, result, id],
+        )?;
+        
+        if updated == 0 {
+            return Err(CcschedError::Config(format!("Task not found: {id}")));
+        }
+        
+        Ok(())
+    }
+
+    /// Records the start of an executor invocation and returns its attempt ID.
+    pub async fn create_task_attempt(
+        &self,
+        task_id: i64,
+        attempt: u32,
+        kind: AttemptKind,
+        session_id_in: Option<&str>,
+        profile: &str,
+        log_start: u64,
+    ) -> Result<i64> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO task_attempts (task_id, attempt, kind, started_at, session_id_in, profile, log_start) VALUES (?, ?, ?, ?, ?, ?, ?)",
+            params![task_id, attempt, kind.to_string(), Utc::now().naive_utc(), session_id_in, profile, log_start],
+        )?;
+
+        Ok(conn.last_insert_rowid())
+    }
+
+    /// Records the end of an executor invocation and adds its usage to the task's
+    /// totals.
+    pub async fn finish_task_attempt(
+        &self,
+        id: i64,
+        exit_code: Option<i32>,
+        session_id_out: Option<&str>,
+        log_end: u64,
+        result: Option<&str>,
+        usage: &Usage,
+    ) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        let tx = conn.unchecked_transaction()?;
+
+        tx.execute(
+            "UPDATE task_attempts SET finished_at = ?, exit_code = ?, session_id_out = ?, log_end = ?, result = ?, input_tokens = ?, output_tokens = ?, cache_read_tokens = ?, cache_creation_tokens = ?, cost_usd = ?, num_turns = ?, duration_ms = ? WHERE id = ?",
+            params![
+                Utc::now().naive_utc(),
+                exit_code,
+                session_id_out,
+                log_end,
+                result,
+                usage.input_tokens,
+                usage.output_tokens,
+                usage.cache_read_tokens,
+                usage.cache_creation_tokens,
+                usage.cost_usd,
+                usage.num_turns,
+                usage.duration_ms,
+                id,
+            ],
+        )?;
+        tx.execute(
+            "UPDATE tasks SET input_tokens = input_tokens + ?, output_tokens = output_tokens + ?, cache_read_tokens = cache_read_tokens + ?, cache_creation_tokens = cache_creation_tokens + ?, cost_usd = cost_usd + ?, num_turns = num_turns + ?, duration_ms = duration_ms + ? WHERE id = (SELECT task_id FROM task_attempts WHERE id = ?)",
+            params![
+                usage.input_tokens,
+                usage.output_tokens,
+                usage.cache_read_tokens,
+                usage.cache_creation_tokens,
+                usage.cost_usd,
+                usage.num_turns,
+                usage.duration_ms,
+                id,
+            ],
+        )?;
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Cost of every invocation of the task so far, across all its attempts.
+    pub async fn get_task_cost(&self, task_id: i64) -> Result<f64> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT cost_usd FROM tasks WHERE id = ?",
+            params![task_id],
+            |row| row.get(0),
+        )
+        .optional()?
+        .ok_or(CcschedError::TaskNotFound(task_id))
+    }
+
+    /// Cost of every invocation that finished at or after `since`, oldest first.
+    pub async fn get_costs_since(&self, since: NaiveDateTime) -> Result<Vec<(NaiveDateTime, f64)>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT finished_at, cost_usd FROM task_attempts WHERE finished_at >= ? AND cost_usd > 0 ORDER BY finished_at ASC",
+        )?;
+
+        let rows = stmt.query_map(params![since], |row| Ok((row.get(0)?, row.get(1)?)))?;
+
+        let mut costs = Vec::new();
+        for row in rows {
+            costs.push(row?);
+        }
+
+        Ok(costs)
+    }
+
+    /// Records a tool permission request of a running task and marks the task as
+    /// awaiting approval.
+    pub async fn create_approval(&self, task_id: i64, tool_name: &str, input: &serde_json::Value) -> Result<Approval> {
+        let conn = self.conn.lock().unwrap();
+        let tx = conn.unchecked_transaction()?;
+
+        let updated = tx.execute(
+            "UPDATE tasks SET status = 'awaiting_approval' WHERE id = ? AND status IN ('running', 'awaiting_approval')",
+            params![task_id],
+        )?;
+        if updated == 0 {
+            let status: Option<String> = tx
+                .query_row("SELECT status FROM tasks WHERE id = ?", params![task_id], |row| row.get(0))
+                .optional()?;
+            return Err(match status {
+                Some(status) => CcschedError::InvalidStatusTransition {
+                    from: status,
+                    to: TaskStatus::AwaitingApproval.to_string(),
+                },
+                None => CcschedError::Config(format!("Task not found: {task_id}")),
+            });
+        }
+
+        tx.execute(
+            "INSERT INTO approvals (task_id, tool_name, input, requested_at) VALUES (?, ?, ?, ?)",
+            params![task_id, tool_name, input.to_string(), Utc::now().naive_utc()],
+        )?;
+        let id = tx.last_insert_rowid();
+        let approval = tx.query_row(
+            &format!("SELECT {APPROVAL_COLUMNS} FROM approvals WHERE id = ?"),
+            params![id],
+            approval_from_row,
+        )?;
+
+        tx.commit()?;
+        Ok(approval)
+    }
+
+    pub async fn get_approval(&self, id: i64) -> Result<Approval> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            &format!("SELECT {APPROVAL_COLUMNS} FROM approvals WHERE id = ?"),
+            params![id],
+            approval_from_row,
+        )
+        .optional()?
+        .ok_or_else(|| CcschedError::Config(format!("Approval not found: {id}")))
+    }
+
+    pub async fn get_pending_approvals(&self) -> Result<Vec<Approval>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {APPROVAL_COLUMNS} FROM approvals WHERE status = 'pending' ORDER BY id ASC"
+        ))?;
+
+        let rows = stmt.query_map([], approval_from_row)?;
+
+        let mut approvals = Vec::new();
+        for row in rows {
+            approvals.push(row?);
+        }
+
+        Ok(approvals)
+    }
+
+    /// Answers a pending approval. The task goes back to running once none of its
+    /// approvals are pending.
+    pub async fn decide_approval(&self, id: i64, status: ApprovalStatus, message: Option<&str>) -> Result<Approval> {
+        let conn = self.conn.lock().unwrap();
+        let tx = conn.unchecked_transaction()?;
+
+        let approval = tx
+            .query_row(
+                &format!("SELECT {APPROVAL_COLUMNS} FROM approvals WHERE id = ?"),
+                params![id],
+                approval_from_row,
+            )
+            .optional()?
+            .ok_or_else(|| CcschedError::Config(format!("Approval not found: {id}")))?;
+        if approval.status != ApprovalStatus::Pending {
+            return Err(CcschedError::InvalidStatusTransition {
+                from: approval.status.to_string(),
+                to: status.to_string(),
+            });
+        }
+
+        tx.execute(
+            "UPDATE approvals SET status = ?, decided_at = ?, message = ? WHERE id = ?",
+            params![status.to_string(), Utc::now().naive_utc(), message, id],
+        )?;
+        tx.execute(
+            "UPDATE tasks SET status = 'running' WHERE id = ? AND status = 'awaiting_approval' AND NOT EXISTS (SELECT 1 FROM approvals WHERE task_id = ? AND status = 'pending')",
+            params![approval.task_id, approval.task_id],
+        )?;
+        let approval = tx.query_row(
+            &format!("SELECT {APPROVAL_COLUMNS} FROM approvals WHERE id = ?"),
+            params![id],
+            approval_from_row,
+        )?;
+
+        tx.commit()?;
+        Ok(approval)
+    }
+
+    /// IDs of pending approvals requested before `cutoff`.
+    pub async fn get_stale_approvals(&self, cutoff: NaiveDateTime) -> Result<Vec<i64>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id FROM approvals WHERE status = 'pending' AND requested_at < ? ORDER BY id ASC",
+        )?;
+
+        let rows = stmt.query_map(params![cutoff], |row| row.get::<_, i64>("id"))?;
+
+        let mut ids = Vec::new();
+        for row in rows {
+            ids.push(row?);
+        }
+
+        Ok(ids)
+    }
+
+    /// Expires the pending approvals of a task that stopped running.
+    pub async fn expire_task_approvals(&self, task_id: i64) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE approvals SET status = 'expired', decided_at = ?, message = 'Task stopped' WHERE task_id = ? AND status = 'pending'",
+            params![Utc::now().naive_utc(), task_id],
+        )?;
+
+        Ok(())
+    }
+
+    /// When the task last waited on an approval: now if one is pending,
+    /// otherwise when the last one was answered.
+    pub async fn last_approval_activity(&self, task_id: i64) -> Result<Option<NaiveDateTime>> {
+        let conn = self.conn.lock().unwrap();
+        let now = Utc::now().naive_utc();
+        let activity = conn.query_row(
+            "SELECT MAX(CASE WHEN status = 'pending' THEN ? ELSE decided_at END) FROM approvals WHERE task_id = ?",
+            params![now, task_id],
+            |row| row.get(0),
+        )?;
+
+        Ok(activity)
+    }
+
+    pub async fn update_task_profile(&self, id: i64, profile: &str) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE tasks SET profile = ? WHERE id = ?",
+            params![profile, id],
+        )?;
+
+        Ok(())
+    }
+
+    /// Records the prompt sent to Claude after its placeholders were filled in.
+    pub async fn update_task_rendered_prompt(&self, id: i64, rendered_prompt: &str) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE tasks SET rendered_prompt = ? WHERE id = ?",
+            params![rendered_prompt, id],
+        )?;
+
+        Ok(())
+    }
+
+    /// Records the session of its parent task the current attempt continues.
+    pub async fn update_task_parent_session(&self, id: i64, parent_session_id: &str) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE tasks SET parent_session_id = ? WHERE id = ?",
+            params![parent_session_id, id],
+        )?;
+
+        Ok(())
+    }
+
+    /// Forgets the task's session and the parent session it continues, so its
+    /// next invocation starts a new one.
+    pub async fn clear_task_session(&self, id: i64) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE tasks SET session_id = NULL, parent_session_id = NULL WHERE id = ?",
+            params![id],
+        )?;
+
+        Ok(())
+    }
+
+    /// Records the snapshot a task's diff starts from, unless it already has one.
+    pub async fn create_task_diff(&self, task_id: i64, before_snapshot: &str) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR IGNORE INTO task_diffs (task_id, before_snapshot, updated_at) VALUES (?, ?, ?)",
+            params![task_id, before_snapshot, Utc::now().naive_utc()],
+        )?;
+
+        Ok(())
+    }
+
+    pub async fn update_task_diff(&self, task_id: i64, after_snapshot: &str, stat: &str, patch: &str) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE task_diffs SET after_snapshot = ?, stat = ?, patch = ?, updated_at = ? WHERE task_id = ?",
+            params![after_snapshot, stat, patch, Utc::now().naive_utc(), task_id],
+        )?;
+
+        Ok(())
+    }
+
+    pub async fn get_task_diff(&self, task_id: i64) -> Result<Option<TaskDiff>> {
+        let conn = self.conn.lock().unwrap();
+        let diff = conn
+            .query_row(
+                "SELECT task_id, before_snapshot, after_snapshot, stat, patch, updated_at FROM task_diffs WHERE task_id = ?",
+                params![task_id],
+                |row| {
+                    Ok(TaskDiff {
+                        task_id: row.get("task_id")?,
+                        before_snapshot: row.get("before_snapshot")?,
+                        after_snapshot: row.get("after_snapshot")?,
+                        stat: row.get("stat")?,
+                        patch: row.get("patch")?,
+                        updated_at: row.get("updated_at")?,
+                    })
+                },
+            )
+            .optional()?;
+
+        Ok(diff)
+    }
+
+    /// Records the checkpoint of a task, unless it already has one. `manifest`
+    /// lists the files in a tarball checkpoint.
+    pub async fn create_checkpoint(
+        &self,
+        task_id: i64,
+        kind: CheckpointKind,
+        root: &str,
+        location: &str,
+        manifest: Option<&str>,
+    ) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR IGNORE INTO checkpoints (task_id, kind, root, location, manifest, created_at) VALUES (?, ?, ?, ?, ?, ?)",
+            params![task_id, kind.to_string(), root, location, manifest, Utc::now().naive_utc()],
+        )?;
+
+        Ok(())
+    }
+
+    pub async fn get_checkpoint(&self, task_id: i64) -> Result<Option<Checkpoint>> {
+        let conn = self.conn.lock().unwrap();
+        let checkpoint = conn
+            .query_row(
+                &format!("SELECT {CHECKPOINT_COLUMNS} FROM checkpoints WHERE task_id = ?"),
+                params![task_id],
+                checkpoint_from_row,
+            )
+            .optional()?;
+
+        Ok(checkpoint)
+    }
+
+    pub async fn get_checkpoint_manifest(&self, task_id: i64) -> Result<Option<String>> {
+        let conn = self.conn.lock().unwrap();
+        let manifest = conn
+            .query_row(
+                "SELECT manifest FROM checkpoints WHERE task_id = ?",
+                params![task_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        Ok(manifest.flatten())
+    }
+
+    /// Repository roots that git checkpoints were taken in.
+    pub async fn get_checkpoint_repos(&self) -> Result<Vec<String>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT DISTINCT root FROM checkpoints WHERE kind = 'git' ORDER BY root")?;
+
+        let rows = stmt.query_map([], |row| row.get(0))?;
+
+        let mut roots = Vec::new();
+        for row in rows {
+            roots.push(row?);
+        }
+
+        Ok(roots)
+    }
+
+    /// Checkpoints of other tasks over the same root taken after the given one.
+    pub async fn get_later_checkpoints(&self, checkpoint: &Checkpoint) -> Result<Vec<Checkpoint>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {CHECKPOINT_COLUMNS} FROM checkpoints WHERE root = ? AND task_id != ? AND created_at >= ? ORDER BY created_at ASC"
+        ))?;
+
+        let rows = stmt.query_map(
+            params![checkpoint.root, checkpoint.task_id, checkpoint.created_at],
+            checkpoint_from_row,
+        )?;
+
+        let mut checkpoints = Vec::new();
+        for row in rows {
+            checkpoints.push(row?);
+        }
+
+        Ok(checkpoints)
+    }
+
+    pub async fn update_checkpoint_changes(&self, task_id: i64, changed_files: &[String]) -> Result<()> {
         let conn = self.conn.lock().unwrap();
-        
-        let row = conn.query_row(
-            "SELECT id, name, prompt, cwd, status, session_id, submitted_at, finished_at, output, result, resume_at FROM tasks WHERE id = ?",
-            params![id],
-            |row| {
-                Ok(Task {
-                    id: row.get("id")?,
-                    name: row.get("name")?,
-                    prompt: row.get("prompt")?,
-                    cwd: row.get("cwd")?,
-                    status: TaskStatus::from_str(&row.get::<_, String>("status")?).unwrap_or(TaskStatus::Failed),
-                    session_id: row.get("session_id")?,
-                    submitted_at: row.get("submitted_at")?,
-                    finished_at: row.get("finished_at")?,
-                    output: row.get("output")?,
-                    result: row.get("result")?,
-                    resume_at: row.get("resume_at")?,
-                })
-            },
-        ).optional()?
-        .ok_or_else(|| CcschedError::Config(format!("Task not found: {id}")))?;
+        conn.execute(
+            "UPDATE checkpoints SET changed_files = ? WHERE task_id = ?",
+            params![serde_json::to_string(changed_files)?, task_id],
+        )?;
 
-        Ok(row)
+        Ok(())
     }
 
-    pub async fn get_task_by_session_id(&self, session_id: &str) -> Result<Task> {
+    pub async fn mark_checkpoint_rolled_back(&self, task_id: i64) -> Result<()> {
         let conn = self.conn.lock().unwrap();
-        
-        let row = conn.query_row(
-            "SELECT id, name, prompt, cwd, status, session_id, submitted_at, finished_at, output, result, resume_at FROM tasks WHERE session_id = ?",
-            params![session_id],
-            |row| {
-                Ok(Task {
-                    id: row.get("id")?,
-                    name: row.get("name")?,
-                    prompt: row.get("prompt")?,
-                    cwd: row.get("cwd")?,
-                    status: TaskStatus::from_str(&row.get::<_, String>("status")?).unwrap_or(TaskStatus::Failed),
-                    session_id: row.get("session_id")?,
-                    submitted_at: row.get("submitted_at")?,
-                    finished_at: row.get("finished_at")?,
-                    output: row.get("output")?,
-                    result: row.get("result")?,
-                    resume_at: row.get("resume_at")?,
-                })
-            },
-        ).optional()?
-        .ok_or_else(|| CcschedError::Config(format!("Task not found for session_id: {session_id}")))?;
+        conn.execute(
+            "UPDATE checkpoints SET rolled_back_at = ? WHERE task_id = ?",
+            params![Utc::now().naive_utc(), task_id],
+        )?;
 
-        Ok(row)
+        Ok(())
     }
 
-    pub async fn list_tasks(&self) -> Result<Vec<Task>> {
+    pub async fn update_task_worktree(&self, id: i64, path: &str, branch: &str, base_commit: &str) -> Result<()> {
         let conn = self.conn.lock().unwrap();
-        
-        let mut stmt = conn.prepare(
-            "SELECT id, name, prompt, cwd, status, session_id, submitted_at, finished_at, output, result, resume_at FROM tasks ORDER BY submitted_at ASC"
+        conn.execute(
+            "UPDATE tasks SET worktree_path = ?, branch = ?, base_commit = ? WHERE id = ?",
+            params![path, branch, base_commit, id],
         )?;
 
-        let rows = stmt.query_map([], |row| {
-            Ok(Task {
-                id: row.get("id")?,
-                name: row.get("name")?,
-                prompt: row.get("prompt")?,
-                cwd: row.get("cwd")?,
-                status: TaskStatus::from_str(&row.get::<_, String>("status")?).unwrap_or(TaskStatus::Failed),
-                session_id: row.get("session_id")?,
-                submitted_at: row.get("submitted_at")?,
-                finished_at: row.get("finished_at")?,
-                output: row.get("output")?,
-                result: row.get("result")?,
-                resume_at: row.get("resume_at")?,
-            })
-        })?;
-
-        let mut tasks = Vec::new();
-        for row in rows {
-            tasks.push(row?);
-        }
-
-        Ok(tasks)
+        Ok(())
     }
 
-    pub async fn update_task_status(
-        &self,
-        id: i64,
-        status: TaskStatus,
-        session_id: Option<&str>,
-        finished_at: Option<NaiveDateTime>,
-    ) -> Result<()> {
+    pub async fn update_task_final_commit(&self, id: i64, commit: &str) -> Result<()> {
         let conn = self.conn.lock().unwrap();
-        let status_str = status.to_string();
-
         conn.execute(
-            "UPDATE tasks SET status = ?, session_id = COALESCE(?, session_id), finished_at = COALESCE(?, finished_at) WHERE id = ?",
-            params![status_str, session_id, finished_at, id],
+            "UPDATE tasks SET final_commit = ? WHERE id = ?",
+            params![commit, id],
         )?;
 
         Ok(())
     }
 
-    pub async fn update_task_status_with_resume_at(
-        &self,
-        id: i64,
-        status: TaskStatus,
-        session_id: Option<&str>,
-        finished_at: Option<NaiveDateTime>,
-        resume_at: Option<NaiveDateTime>,
-    ) -> Result<()> {
+    /// Forgets a task's worktree once it has been removed; its branch and
+    /// commits stay recorded.
+    pub async fn clear_task_worktree(&self, id: i64) -> Result<()> {
         let conn = self.conn.lock().unwrap();
-        let status_str = status.to_string();
-
         conn.execute(
-            "UPDATE tasks SET status = ?, session_id = COALESCE(?, session_id), finished_at = COALESCE(?, finished_at), resume_at = ? WHERE id = ?",
-            params![status_str, session_id, finished_at, resume_at, id],
+            "UPDATE tasks SET worktree_path = NULL WHERE id = ?",
+            params![id],
         )?;
 
         Ok(())
     }
 
-    pub async fn update_task_result(&self, id: i64, result: Option<&str>) -> Result<()> {
+    /// Number of invocations and usage totals per profile.
+    pub async fn get_profile_usage(&self) -> Result<HashMap<String, (u64, Usage)>> {
         let conn = self.conn.lock().unwrap();
-        let updated = conn.execute(
-            "UPDATE tasks SET result = ? WHERE id = ?",
-            params![result, id],
-        )?;
-        
-        if updated == 0 {
-            return Err(CcschedError::Config(format!("Task not found: {id}")));
+        let mut stmt = conn.prepare(&format!(
+            "SELECT profile, COUNT(*) AS invocations, {USAGE_SUMS} FROM task_attempts WHERE profile IS NOT NULL GROUP BY profile"
+        ))?;
+
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, String>("profile")?, (row.get("invocations")?, usage_from_row(row)?)))
+        })?;
+
+        let mut usage = HashMap::new();
+        for row in rows {
+            let (profile, profile_usage) = row?;
+            usage.insert(profile, profile_usage);
         }
-        
-        Ok(())
+
+        Ok(usage)
     }
 
-    pub async fn update_task_output_and_result(&self, id: i64, output: Option<&str>, result: Option<&str>) -> Result<()> {
+    /// Usage totals grouped by day, working directory and status.
+    pub async fn get_usage_stats(&self) -> Result<StatsResponse> {
         let conn = self.conn.lock().unwrap();
-        let updated = conn.execute(
-            "UPDATE tasks SET output = ?, result = ? WHERE id = ?",
-            params![output, result, id],
+
+        let query_groups = |query: &str| -> Result<Vec<UsageGroup>> {
+            let mut stmt = conn.prepare(query)?;
+            let rows = stmt.query_map([], |row| {
+                Ok(UsageGroup {
+                    key: row.get("key")?,
+                    tasks: row.get("tasks")?,
+                    usage: usage_from_row(row)?,
+                })
+            })?;
+
+            let mut groups = Vec::new();
+            for row in rows {
+                groups.push(row?);
+            }
+            Ok(groups)
+        };
+
+        let by_day = query_groups(&format!(
+            "SELECT date(started_at) AS key, COUNT(DISTINCT task_id) AS tasks, {USAGE_SUMS} FROM task_attempts GROUP BY key ORDER BY key ASC"
+        ))?;
+        let by_cwd = query_groups(&format!(
+            "SELECT cwd AS key, COUNT(*) AS tasks, {USAGE_SUMS} FROM tasks GROUP BY cwd ORDER BY cost_usd DESC"
+        ))?;
+        let by_status = query_groups(&format!(
+            "SELECT status AS key, COUNT(*) AS tasks, {USAGE_SUMS} FROM tasks GROUP BY status ORDER BY key ASC"
+        ))?;
+        let total = conn.query_row(
+            &format!("SELECT {USAGE_SUMS} FROM tasks"),
+            [],
+            usage_from_row,
         )?;
-        
-        if updated == 0 {
-            return Err(CcschedError::Config(format!("Task not found: {id}")));
+
+        Ok(StatsResponse {
+            by_day,
+            by_cwd,
+            by_status,
+            total,
+        })
+    }
+
+    pub async fn get_task_attempts(&self, task_id: i64) -> Result<Vec<TaskAttempt>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            &format!("SELECT id, task_id, attempt, kind, started_at, finished_at, exit_code, session_id_in, session_id_out, profile, log_start, log_end, result, {USAGE_COLUMNS} FROM task_attempts WHERE task_id = ? ORDER BY id ASC"),
+        )?;
+
+        let rows = stmt.query_map(params![task_id], |row| {
+            Ok(TaskAttempt {
+                id: row.get("id")?,
+                task_id: row.get("task_id")?,
+                attempt: row.get("attempt")?,
+                kind: AttemptKind::from_str(&row.get::<_, String>("kind")?).unwrap_or(AttemptKind::Initial),
+                started_at: row.get("started_at")?,
+                finished_at: row.get("finished_at")?,
+                exit_code: row.get("exit_code")?,
+                session_id_in: row.get("session_id_in")?,
+                session_id_out: row.get("session_id_out")?,
+                profile: row.get("profile")?,
+                log_start: row.get("log_start")?,
+                log_end: row.get("log_end")?,
+                result: row.get("result")?,
+                usage: usage_from_row(row)?,
+            })
+        })?;
+
+        let mut attempts = Vec::new();
+        for row in rows {
+            attempts.push(row?);
         }
-        
-        Ok(())
+
+        Ok(attempts)
     }
 
-    pub async fn get_and_claim_next_task(&self) -> Result<Option<Task>> {
+    pub async fn get_and_claim_next_task(&self, max_concurrency: usize) -> Result<Option<Task>> {
         let conn = self.conn.lock().unwrap();
         let tx = conn.unchecked_transaction()?;
         
-        // First check if there's already a running task that's not in waiting state
-        // We allow waiting tasks to be resumed even if there are other running tasks
+        // Respect the worker pool size: never have more running tasks than workers
         let running_count: i64 = tx.query_row(
-            "SELECT COUNT(*) FROM tasks WHERE status = 'running'",
+            "SELECT COUNT(*) FROM tasks WHERE status IN ('running', 'awaiting_approval')",
             [],
             |row| row.get(0)
         )?;
         
-        // If there's already a running task, only allow waiting tasks to be resumed
-        let allow_only_waiting = running_count > 0;
+        if running_count >= max_concurrency as i64 {
+            tx.commit()?;
+            return Ok(None);
+        }
         
         // Find the next ready task and claim it atomically
-        let status_condition = if allow_only_waiting {
-            "(t.status = 'waiting' AND (t.resume_at IS NULL OR t.resume_at <= datetime('now', 'utc')))"
-        } else {
-            "(t.status = 'pending' OR (t.status = 'waiting' AND (t.resume_at IS NULL OR t.resume_at <= datetime('now', 'utc'))))"
-        };
+        // Pending tasks that failed an earlier attempt wait for their retry time
+        let status_condition = "((t.status = 'pending' AND (t.retry_at IS NULL OR t.retry_at <= datetime('now'))) OR (t.status = 'waiting' AND (t.resume_at IS NULL OR t.resume_at <= datetime('now', 'utc'))))";
         
         let query = format!(
             r#"
-            SELECT DISTINCT t.id, t.name, t.prompt, t.cwd, t.status, t.session_id, t.submitted_at, t.finished_at, t.output, t.result, t.resume_at
-            FROM tasks t
-            LEFT JOIN task_dependencies td ON t.id = td.task_id
-            LEFT JOIN tasks dep ON td.depends_on_id = dep.id
-            WHERE {}
-            GROUP BY t.id, t.name, t.prompt, t.cwd, t.status, t.session_id, t.submitted_at, t.finished_at, t.output, t.result, t.resume_at
-            HAVING COUNT(CASE WHEN dep.status IS NOT NULL AND dep.status != 'done' THEN 1 END) = 0
-            ORDER BY t.submitted_at ASC
-            LIMIT 1
-            "#,
-            status_condition
+            SELECT {TASK_COLUMNS} FROM tasks WHERE id = (
+                SELECT t.id
+                FROM tasks t
+                LEFT JOIN task_dependencies td ON t.id = td.task_id
+                LEFT JOIN tasks dep ON td.depends_on_id = dep.id
+                WHERE {status_condition}
+                GROUP BY t.id
+                HAVING COUNT(CASE WHEN dep.status IS NOT NULL AND NOT {DEPENDENCY_SATISFIED} THEN 1 END) = 0
+                ORDER BY t.submitted_at ASC
+                LIMIT 1
+            )
+            "#
         );
         
         let task_opt = tx.query_row(
             &query,
             [],
-            |row| {
-                Ok(Task {
-                    id: row.get("id")?,
-                    name: row.get("name")?,
-                    prompt: row.get("prompt")?,
-                    cwd: row.get("cwd")?,
-                    status: TaskStatus::from_str(&row.get::<_, String>("status")?).unwrap_or(TaskStatus::Failed),
-                    session_id: row.get("session_id")?,
-                    submitted_at: row.get("submitted_at")?,
-                    finished_at: row.get("finished_at")?,
-                    output: row.get("output")?,
-                    result: row.get("result")?,
-                    resume_at: row.get("resume_at")?,
-                })
-            }
+            task_from_row
         ).optional()?;
         
         if let Some(task) = task_opt {
@@ -360,10 +1341,121 @@ impl Database {
         }
     }
 
-    pub async fn get_ready_tasks(&self) -> Result<Vec<Task>> {
+    /// Settles pending tasks whose dependencies can no longer all be met: a task
+    /// waiting on a failed or cancelled task, directly or through other blocked
+    /// tasks, is blocked, and one whose condition on a dependency can never hold
+    /// is skipped. Blocked tasks are re-opened once the ancestor they wait on has
+    /// been re-run and finished, or was deleted.
+    pub async fn update_dependent_tasks(&self) -> Result<DependentUpdates> {
+        let conn = self.conn.lock().unwrap();
+        let tx = conn.unchecked_transaction()?;
+        let mut updates = DependentUpdates::default();
+
+        let mut stmt = tx.prepare(
+            "SELECT id FROM tasks WHERE status = 'blocked' AND NOT EXISTS (SELECT 1 FROM tasks ancestor WHERE ancestor.id = tasks.blocked_by AND ancestor.status NOT IN ('done', 'skipped'))",
+        )?;
+        let rows = stmt.query_map([], |row| row.get::<_, i64>("id"))?;
+        let mut reopened = Vec::new();
+        for row in rows {
+            reopened.push(row?);
+        }
+        drop(stmt);
+        for id in &reopened {
+            tx.execute("UPDATE tasks SET status = 'pending', blocked_by = NULL WHERE id = ?", params![id])?;
+        }
+
+        // Settle one level of dependents at a time, so blocked tasks point at the
+        // ancestor that failed rather than at their direct dependency
+        loop {
+            let mut stmt = tx.prepare(
+                r#"
+                SELECT td.task_id, td.depends_on_id, td.condition, dep.status, dep.blocked_by
+                FROM task_dependencies td
+                JOIN tasks t ON t.id = td.task_id
+                JOIN tasks dep ON dep.id = td.depends_on_id
+                WHERE t.status = 'pending' AND dep.status IN ('done', 'failed', 'cancelled', 'skipped', 'blocked')
+                ORDER BY td.task_id, td.depends_on_id
+                "#,
+            )?;
+            let rows = stmt.query_map([], |row| {
+                let dependency = Dependency {
+                    id: row.get(1)?,
+                    condition: DependencyCondition::from_str(&row.get::<_, String>(2)?).unwrap_or_default(),
+                };
+                let status = TaskStatus::from_str(&row.get::<_, String>(3)?).unwrap_or(TaskStatus::Failed);
+                Ok((row.get::<_, i64>(0)?, dependency, status, row.get::<_, Option<i64>>(4)?))
+            })?;
+
+            let mut settled: Vec<(i64, Settlement)> = Vec::new();
+            for row in rows {
+                let (id, dependency, status, blocked_by) = row?;
+                // A blocked dependency does not run, like a skipped one: it still
+                // blocks `:success` dependents, but meets `:any` and rules out `:failure`
+                let settlement = match (dependency.condition, &status) {
+                    (DependencyCondition::Success, TaskStatus::Blocked) => blocked_by.map(Settlement::Blocked),
+                    (DependencyCondition::Success, TaskStatus::Failed | TaskStatus::Cancelled) => {
+                        Some(Settlement::Blocked(dependency.id))
+                    }
+                    (DependencyCondition::Success, TaskStatus::Skipped)
+                    | (
+                        DependencyCondition::Failure,
+                        TaskStatus::Done | TaskStatus::Cancelled | TaskStatus::Skipped | TaskStatus::Blocked,
+                    ) => {
+                        Some(Settlement::Skipped(format!(
+                            "Dependency {dependency} cannot be met: task {} is {status}",
+                            dependency.id
+                        )))
+                    }
+                    _ => None,
+                };
+                let Some(settlement) = settlement else {
+                    continue;
+                };
+
+                // Skipping is final, so it wins over waiting on a blocked ancestor
+                match settled.last_mut() {
+                    Some((last, previous)) if *last == id => {
+                        if matches!(previous, Settlement::Blocked(_)) && matches!(settlement, Settlement::Skipped(_)) {
+                            *previous = settlement;
+                        }
+                    }
+                    _ => settled.push((id, settlement)),
+                }
+            }
+            drop(stmt);
+            if settled.is_empty() {
+                break;
+            }
+
+            for (id, settlement) in settled {
+                match settlement {
+                    Settlement::Blocked(ancestor) => {
+                        tx.execute(
+                            "UPDATE tasks SET status = 'blocked', blocked_by = ? WHERE id = ?",
+                            params![ancestor, id],
+                        )?;
+                        updates.blocked.push(id);
+                    }
+                    Settlement::Skipped(reason) => {
+                        tx.execute(
+                            "UPDATE tasks SET status = 'skipped', finished_at = ?, failure_reason = ? WHERE id = ?",
+                            params![Utc::now().naive_utc(), reason, id],
+                        )?;
+                        updates.skipped.push(id);
+                    }
+                }
+            }
+        }
+
+        tx.commit()?;
+        updates.reopened = reopened.into_iter().filter(|id| !updates.blocked.contains(id)).collect();
+        Ok(updates)
+    }
+
+    pub async fn get_ready_tasks(&self, max_concurrency: usize) -> Result<Vec<Task>> {
         // This method is kept for backward compatibility but should not be used for scheduling
         // Use get_and_claim_next_task instead
-        match self.get_and_claim_next_task().await? {
+        match self.get_and_claim_next_task(max_concurrency).await? {
             Some(task) => Ok(vec![task]),
             None => Ok(vec![]),
         }
@@ -387,6 +1479,62 @@ impl Database {
         Ok(())
     }
 
+    pub async fn get_task_dependencies(&self, task_id: i64) -> Result<Vec<Dependency>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT depends_on_id, condition FROM task_dependencies WHERE task_id = ? ORDER BY depends_on_id",
+        )?;
+        let rows = stmt.query_map(params![task_id], |row| {
+            Ok(Dependency {
+                id: row.get("depends_on_id")?,
+                condition: DependencyCondition::from_str(&row.get::<_, String>("condition")?).unwrap_or_default(),
+            })
+        })?;
+
+        let mut dependencies = Vec::new();
+        for row in rows {
+            dependencies.push(row?);
+        }
+
+        Ok(dependencies)
+    }
+
+    /// Replaces the dependencies of a task that has not started yet or has
+    /// finished. A blocked or skipped task is re-opened, so it is settled again
+    /// against its new dependencies. Returns false if the task is running.
+    pub async fn set_task_dependencies(&self, task_id: i64, dependencies: &[Dependency]) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let tx = conn.unchecked_transaction()?;
+
+        let status = tx
+            .query_row("SELECT status FROM tasks WHERE id = ?", params![task_id], |row| row.get::<_, String>("status"))
+            .optional()?
+            .ok_or(CcschedError::TaskNotFound(task_id))?;
+        if matches!(
+            TaskStatus::from_str(&status),
+            Ok(TaskStatus::Running | TaskStatus::AwaitingApproval | TaskStatus::Waiting)
+        ) {
+            return Ok(false);
+        }
+
+        tx.execute("DELETE FROM task_dependencies WHERE task_id = ?", params![task_id])?;
+        for dependency in dependencies {
+            tx.execute(
+                "INSERT INTO task_dependencies (task_id, depends_on_id, condition) VALUES (?, ?, ?)",
+                params![task_id, dependency.id, dependency.condition.to_string()],
+            )?;
+        }
+        tx.execute(
+            "UPDATE tasks SET status = 'pending', blocked_by = NULL, finished_at = NULL, failure_reason = NULL WHERE id = ? AND status IN ('blocked', 'skipped')",
+            params![task_id],
+        )?;
+
+        tx.commit()?;
+        Ok(true)
+    }
+
+    /// Checks that giving `task_id` exactly `dependencies` keeps the task graph
+    /// free of cycles.
     pub async fn check_circular_dependency(&self, task_id: i64, dependencies: &[i64]) -> Result<()> {
         let conn = self.conn.lock().unwrap();
         
@@ -402,10 +1550,8 @@ impl Database {
             dependency_graph.entry(task).or_default().push(dep);
         }
 
-        // Add the new dependencies
-        for &dep_id in dependencies {
-            dependency_graph.entry(task_id).or_default().push(dep_id);
-        }
+        // The task's dependencies become the given ones
+        dependency_graph.insert(task_id, dependencies.to_vec());
 
         // Check for circular dependencies using DFS
         let mut visited = HashSet::new();
@@ -456,6 +1602,11 @@ impl Database {
             params![id, id],
         )?;
 
+        tx.execute("DELETE FROM task_attempts WHERE task_id = ?", params![id])?;
+        tx.execute("DELETE FROM approvals WHERE task_id = ?", params![id])?;
+        tx.execute("DELETE FROM task_diffs WHERE task_id = ?", params![id])?;
+        tx.execute("DELETE FROM checkpoints WHERE task_id = ?", params![id])?;
+
         // Delete the task
         let deleted = tx.execute("DELETE FROM tasks WHERE id = ?", params![id])?;
         
@@ -480,7 +1631,10 @@ impl Database {
 
     pub async fn update_task_prompt(&self, id: i64, prompt: &str) -> Result<()> {
         let conn = self.conn.lock().unwrap();
-        let updated = conn.execute("UPDATE tasks SET prompt = ? WHERE id = ?", params![prompt, id])?;
+        let updated = conn.execute(
+            "UPDATE tasks SET prompt = ?, rendered_prompt = NULL WHERE id = ?",
+            params![prompt, id],
+        )?;
         
         if updated == 0 {
             return Err(CcschedError::Config(format!("Task not found: {id}")));
@@ -492,7 +1646,7 @@ impl Database {
     pub async fn update_task_prompt_and_reset_status(&self, id: i64, prompt: &str) -> Result<()> {
         let conn = self.conn.lock().unwrap();
         let updated = conn.execute(
-            "UPDATE tasks SET prompt = ?, status = 'pending', finished_at = NULL, output = NULL, result = NULL, resume_at = NULL WHERE id = ?", 
+            "UPDATE tasks SET prompt = ?, status = 'pending', finished_at = NULL, output = NULL, result = NULL, resume_at = NULL, failure_reason = NULL, verification_rounds = 0, check_output = NULL, failure_kind = NULL, attempt = 1, retry_at = NULL, rendered_prompt = NULL, run_time_ms = 0 WHERE id = ?", 
             params![prompt, id]
         )?;
         
@@ -503,29 +1657,27 @@ impl Database {
         Ok(())
     }
 
+    /// Marks a task that no worker is executing as cancelled. Returns false if the
+    /// task has already finished.
+    pub async fn mark_task_cancelled(&self, id: i64) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let updated = conn.execute(
+            "UPDATE tasks SET status = 'cancelled', finished_at = ?, resume_at = NULL, blocked_by = NULL WHERE id = ? AND status IN ('pending', 'waiting', 'running', 'awaiting_approval', 'blocked')",
+            params![Utc::now().naive_utc(), id],
+        )?;
+
+        Ok(updated == 1)
+    }
+
     pub async fn get_tasks_by_status(&self, status: TaskStatus) -> Result<Vec<Task>> {
         let conn = self.conn.lock().unwrap();
         let status_str = status.to_string();
         
         let mut stmt = conn.prepare(
-            "SELECT id, name, prompt, cwd, status, session_id, submitted_at, finished_at, output, result, resume_at FROM tasks WHERE status = ? ORDER BY submitted_at ASC"
+            &format!("SELECT {TASK_COLUMNS} FROM tasks WHERE status = ? ORDER BY submitted_at ASC")
         )?;
 
-        let rows = stmt.query_map([status_str], |row| {
-            Ok(Task {
-                id: row.get("id")?,
-                name: row.get("name")?,
-                prompt: row.get("prompt")?,
-                cwd: row.get("cwd")?,
-                status: TaskStatus::from_str(&row.get::<_, String>("status")?).unwrap_or(TaskStatus::Failed),
-                session_id: row.get("session_id")?,
-                submitted_at: row.get("submitted_at")?,
-                finished_at: row.get("finished_at")?,
-                output: row.get("output")?,
-                result: row.get("result")?,
-                resume_at: row.get("resume_at")?,
-            })
-        })?;
+        let rows = stmt.query_map([status_str], task_from_row)?;
 
         let mut tasks = Vec::new();
         for row in rows {
@@ -539,24 +1691,10 @@ impl Database {
         let conn = self.conn.lock().unwrap();
         
         let mut stmt = conn.prepare(
-            "SELECT id, name, prompt, cwd, status, session_id, submitted_at, finished_at, output, result, resume_at FROM tasks WHERE status = 'waiting' AND (resume_at IS NULL OR resume_at <= datetime('now')) ORDER BY submitted_at ASC"
+            &format!("SELECT {TASK_COLUMNS} FROM tasks WHERE status = 'waiting' AND (resume_at IS NULL OR resume_at <= datetime('now')) ORDER BY submitted_at ASC")
         )?;
 
-        let rows = stmt.query_map([], |row| {
-            Ok(Task {
-                id: row.get("id")?,
-                name: row.get("name")?,
-                prompt: row.get("prompt")?,
-                cwd: row.get("cwd")?,
-                status: TaskStatus::from_str(&row.get::<_, String>("status")?).unwrap_or(TaskStatus::Failed),
-                session_id: row.get("session_id")?,
-                submitted_at: row.get("submitted_at")?,
-                finished_at: row.get("finished_at")?,
-                output: row.get("output")?,
-                result: row.get("result")?,
-                resume_at: row.get("resume_at")?,
-            })
-        })?;
+        let rows = stmt.query_map([], task_from_row)?;
 
         let mut tasks = Vec::new();
         for row in rows {
@@ -568,6 +1706,18 @@ impl Database {
 
     pub async fn cleanup_orphaned_running_tasks(&self) -> Result<Vec<i64>> {
         let conn = self.conn.lock().unwrap();
+
+        // The Claude processes that asked for these are gone
+        let pending_approvals = conn.execute(
+            "UPDATE approvals SET status = 'expired', decided_at = ?, message = 'Scheduler restarted' WHERE status = 'pending'",
+            params![Utc::now().naive_utc()],
+        )?;
+        if pending_approvals > 0 {
+            conn.execute(
+                "UPDATE tasks SET status = 'running' WHERE status = 'awaiting_approval'",
+                [],
+            )?;
+        }
         
         // Find running tasks without session_id (orphaned tasks)
         let mut stmt = conn.prepare(
@@ -575,7 +1725,7 @@ impl Database {
         )?;
         
         let rows = stmt.query_map([], |row| {
-            Ok(row.get::<_, i64>("id")?)
+            row.get::<_, i64>("id")
         })?;
         
         let mut orphaned_ids = Vec::new();
@@ -598,4 +1748,284 @@ impl Database {
         
         Ok(orphaned_ids)
     }
-}
\ No newline at end of file
+}
+
+const TASK_COLUMNS: &str = "id, name, prompt, cwd, status, session_id, submitted_at, finished_at, output, result, resume_at, timeout_secs, idle_timeout_secs, failure_reason, executor, verify, verification_template, success_marker, failure_marker, max_verification_rounds, verification_rounds, verify_cmd, check_output, failure_kind, attempt, max_attempts, retry_backoff_secs, retry_on, retry_at, input_tokens, output_tokens, cache_read_tokens, cache_creation_tokens, cost_usd, num_turns, duration_ms, max_cost_usd, profile, claude_options, permission_mode, hooks, worktree, base_ref, worktree_path, branch, base_commit, final_commit, blocked_by, run_id, vars, rendered_prompt, continue_from, fork_session, parent_session_id, run_time_ms";
+
+const APPROVAL_COLUMNS: &str = "id, task_id, tool_name, input, status, requested_at, decided_at, message";
+
+fn approval_from_row(row: &Row) -> rusqlite::Result<Approval> {
+    Ok(Approval {
+        id: row.get("id")?,
+        task_id: row.get("task_id")?,
+        tool_name: row.get("tool_name")?,
+        input: serde_json::from_str(&row.get::<_, String>("input")?).unwrap_or_default(),
+        status: ApprovalStatus::from_str(&row.get::<_, String>("status")?).unwrap_or(ApprovalStatus::Pending),
+        requested_at: row.get("requested_at")?,
+        decided_at: row.get("decided_at")?,
+        message: row.get("message")?,
+    })
+}
+
+const CHECKPOINT_COLUMNS: &str = "task_id, kind, root, location, changed_files, created_at, rolled_back_at";
+
+fn checkpoint_from_row(row: &Row) -> rusqlite::Result<Checkpoint> {
+    Ok(Checkpoint {
+        task_id: row.get("task_id")?,
+        kind: CheckpointKind::from_str(&row.get::<_, String>("kind")?).unwrap_or(CheckpointKind::Git),
+        root: row.get("root")?,
+        location: row.get("location")?,
+        changed_files: row
+            .get::<_, Option<String>>("changed_files")?
+            .and_then(|files| serde_json::from_str(&files).ok()),
+        created_at: row.get("created_at")?,
+        rolled_back_at: row.get("rolled_back_at")?,
+    })
+}
+
+const USAGE_COLUMNS: &str = "input_tokens, output_tokens, cache_read_tokens, cache_creation_tokens, cost_usd, num_turns, duration_ms";
+
+const USAGE_SUMS: &str = "COALESCE(SUM(input_tokens), 0) AS input_tokens, COALESCE(SUM(output_tokens), 0) AS output_tokens, COALESCE(SUM(cache_read_tokens), 0) AS cache_read_tokens, COALESCE(SUM(cache_creation_tokens), 0) AS cache_creation_tokens, COALESCE(SUM(cost_usd), 0.0) AS cost_usd, COALESCE(SUM(num_turns), 0) AS num_turns, COALESCE(SUM(duration_ms), 0) AS duration_ms";
+
+fn task_from_row(row: &Row) -> rusqlite::Result<Task> {
+    Ok(Task {
+        id: row.get("id")?,
+        name: row.get("name")?,
+        prompt: row.get("prompt")?,
+        cwd: row.get("cwd")?,
+        status: TaskStatus::from_str(&row.get::<_, String>("status")?).unwrap_or(TaskStatus::Failed),
+        session_id: row.get("session_id")?,
+        submitted_at: row.get("submitted_at")?,
+        finished_at: row.get("finished_at")?,
+        output: row.get("output")?,
+        result: row.get("result")?,
+        resume_at: row.get("resume_at")?,
+        timeout_secs: row.get("timeout_secs")?,
+        idle_timeout_secs: row.get("idle_timeout_secs")?,
+        failure_reason: row.get("failure_reason")?,
+        executor: row.get("executor")?,
+        verify: row.get("verify")?,
+        verification_template: row.get("verification_template")?,
+        success_marker: row.get("success_marker")?,
+        failure_marker: row.get("failure_marker")?,
+        max_verification_rounds: row.get("max_verification_rounds")?,
+        verification_rounds: row.get("verification_rounds")?,
+        verify_cmd: row.get("verify_cmd")?,
+        check_output: row.get("check_output")?,
+        failure_kind: row
+            .get::<_, Option<String>>("failure_kind")?
+            .and_then(|kind| kind.parse().ok()),
+        attempt: row.get("attempt")?,
+        max_attempts: row.get("max_attempts")?,
+        retry_backoff_secs: row.get("retry_backoff_secs")?,
+        retry_on: row
+            .get::<_, Option<String>>("retry_on")?
+            .and_then(|kinds| FailureKind::parse_list(&kinds).ok()),
+        retry_at: row.get("retry_at")?,
+        usage: usage_from_row(row)?,
+        max_cost_usd: row.get("max_cost_usd")?,
+        profile: row.get("profile")?,
+        claude_options: row
+            .get::<_, Option<String>>("claude_options")?
+            .and_then(|options| serde_json::from_str(&options).ok())
+            .unwrap_or_default(),
+        // Tasks from before permission modes always skipped permissions
+        permission_mode: row
+            .get::<_, Option<String>>("permission_mode")?
+            .and_then(|mode| mode.parse().ok())
+            .unwrap_or(PermissionMode::Skip),
+        hooks: row
+            .get::<_, Option<String>>("hooks")?
+            .and_then(|hooks| serde_json::from_str(&hooks).ok())
+            .unwrap_or_default(),
+        worktree: row.get("worktree")?,
+        base_ref: row.get("base_ref")?,
+        worktree_path: row.get("worktree_path")?,
+        branch: row.get("branch")?,
+        base_commit: row.get("base_commit")?,
+        final_commit: row.get("final_commit")?,
+        blocked_by: row.get("blocked_by")?,
+        run_id: row.get("run_id")?,
+        vars: row
+            .get::<_, Option<String>>("vars")?
+            .and_then(|vars| serde_json::from_str(&vars).ok())
+            .unwrap_or_default(),
+        rendered_prompt: row.get("rendered_prompt")?,
+        continue_from: row.get("continue_from")?,
+        fork_session: row.get("fork_session")?,
+        parent_session_id: row.get("parent_session_id")?,
+        run_time_ms: row.get("run_time_ms")?,
+    })
+}
+
+fn usage_from_row(row: &Row) -> rusqlite::Result<Usage> {
+    Ok(Usage {
+        input_tokens: row.get("input_tokens")?,
+        output_tokens: row.get("output_tokens")?,
+        cache_read_tokens: row.get("cache_read_tokens")?,
+        cache_creation_tokens: row.get("cache_creation_tokens")?,
+        cost_usd: row.get("cost_usd")?,
+        num_turns: row.get("num_turns")?,
+        duration_ms: row.get("duration_ms")?,
+    })
+}
+
+fn status_check_constraint() -> String {
+    let statuses = TaskStatus::ALL
+        .iter()
+        .map(|status| format!("'{status}'"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!("status IN ({statuses})")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn create_task(db: &Database, depends_on: &[(i64, DependencyCondition)]) -> i64 {
+        let request = CreateTaskRequest {
+            name: "task".to_string(),
+            depends_on: depends_on.iter().map(|&(id, condition)| Dependency { id, condition }).collect(),
+            ..Default::default()
+        };
+        db.create_task(&request).await.unwrap()
+    }
+
+    async fn set_status(db: &Database, id: i64, status: TaskStatus) {
+        db.update_task_status(id, status, None, None).await.unwrap();
+    }
+
+    async fn status(db: &Database, id: i64) -> TaskStatus {
+        db.get_task(id).await.unwrap().status
+    }
+
+    #[tokio::test]
+    async fn failure_edge_on_a_done_task_is_skipped() {
+        let db = Database::new(":memory:").await.unwrap();
+        let build = create_task(&db, &[]).await;
+        let fallback = create_task(&db, &[(build, DependencyCondition::Failure)]).await;
+        set_status(&db, build, TaskStatus::Done).await;
+
+        let updates = db.update_dependent_tasks().await.unwrap();
+
+        assert_eq!(updates.skipped, vec![fallback]);
+        assert!(updates.blocked.is_empty());
+        let task = db.get_task(fallback).await.unwrap();
+        assert_eq!(task.status, TaskStatus::Skipped);
+        assert!(task.failure_reason.is_some_and(|reason| reason.contains(&format!("task {build} is done"))));
+    }
+
+    #[tokio::test]
+    async fn dependents_of_a_failed_task_are_blocked_on_it() {
+        let db = Database::new(":memory:").await.unwrap();
+        let build = create_task(&db, &[]).await;
+        let test = create_task(&db, &[(build, DependencyCondition::Success)]).await;
+        let deploy = create_task(&db, &[(test, DependencyCondition::Success)]).await;
+        db.mark_task_failed(build, "broken", FailureKind::Crash).await.unwrap();
+
+        let updates = db.update_dependent_tasks().await.unwrap();
+
+        assert_eq!(updates.blocked, vec![test, deploy]);
+        for id in [test, deploy] {
+            let task = db.get_task(id).await.unwrap();
+            assert_eq!(task.status, TaskStatus::Blocked);
+            assert_eq!(task.blocked_by, Some(build));
+        }
+    }
+
+    #[tokio::test]
+    async fn blocked_tasks_are_reopened_once_the_ancestor_is_done() {
+        let db = Database::new(":memory:").await.unwrap();
+        let build = create_task(&db, &[]).await;
+        let test = create_task(&db, &[(build, DependencyCondition::Success)]).await;
+        db.mark_task_failed(build, "broken", FailureKind::Crash).await.unwrap();
+        db.update_dependent_tasks().await.unwrap();
+
+        // Still failed: nothing changes
+        let updates = db.update_dependent_tasks().await.unwrap();
+        assert!(updates.reopened.is_empty());
+        assert_eq!(status(&db, test).await, TaskStatus::Blocked);
+
+        set_status(&db, build, TaskStatus::Done).await;
+        let updates = db.update_dependent_tasks().await.unwrap();
+
+        assert_eq!(updates.reopened, vec![test]);
+        let task = db.get_task(test).await.unwrap();
+        assert_eq!(task.status, TaskStatus::Pending);
+        assert_eq!(task.blocked_by, None);
+    }
+
+    #[tokio::test]
+    async fn skipping_wins_over_blocking() {
+        let db = Database::new(":memory:").await.unwrap();
+        let build = create_task(&db, &[]).await;
+        let lint = create_task(&db, &[]).await;
+        let report = create_task(
+            &db,
+            &[(build, DependencyCondition::Success), (lint, DependencyCondition::Failure)],
+        )
+        .await;
+        db.mark_task_failed(build, "broken", FailureKind::Crash).await.unwrap();
+        set_status(&db, lint, TaskStatus::Done).await;
+
+        let updates = db.update_dependent_tasks().await.unwrap();
+
+        assert_eq!(updates.skipped, vec![report]);
+        assert!(updates.blocked.is_empty());
+        assert_eq!(status(&db, report).await, TaskStatus::Skipped);
+    }
+
+    #[tokio::test]
+    async fn a_task_that_is_retried_does_not_settle_its_dependents() {
+        let db = Database::new(":memory:").await.unwrap();
+        let build = create_task(&db, &[]).await;
+        let fallback = create_task(&db, &[(build, DependencyCondition::Failure)]).await;
+        let deploy = create_task(&db, &[(build, DependencyCondition::Success)]).await;
+        set_status(&db, build, TaskStatus::Running).await;
+        let retry_at = Utc::now().naive_utc() + chrono::Duration::minutes(5);
+        db.schedule_task_retry(build, 2, retry_at, "broken", FailureKind::Crash).await.unwrap();
+
+        let updates = db.update_dependent_tasks().await.unwrap();
+
+        assert!(updates.blocked.is_empty() && updates.skipped.is_empty());
+        assert!(db.get_and_claim_next_task(4).await.unwrap().is_none());
+        for id in [fallback, deploy] {
+            assert_eq!(status(&db, id).await, TaskStatus::Pending);
+        }
+    }
+
+    #[tokio::test]
+    async fn only_success_edges_on_a_blocked_task_are_blocked() {
+        let db = Database::new(":memory:").await.unwrap();
+        let build = create_task(&db, &[]).await;
+        let test = create_task(&db, &[(build, DependencyCondition::Success)]).await;
+        let deploy = create_task(&db, &[(test, DependencyCondition::Success)]).await;
+        let fallback = create_task(&db, &[(test, DependencyCondition::Failure)]).await;
+        let cleanup = create_task(&db, &[(test, DependencyCondition::Any)]).await;
+        db.mark_task_failed(build, "broken", FailureKind::Crash).await.unwrap();
+
+        let updates = db.update_dependent_tasks().await.unwrap();
+
+        assert_eq!(updates.blocked, vec![test, deploy]);
+        assert_eq!(updates.skipped, vec![fallback]);
+        let task = db.get_task(fallback).await.unwrap();
+        assert!(task.failure_reason.is_some_and(|reason| reason.contains(&format!("task {test} is blocked"))));
+        assert_eq!(status(&db, cleanup).await, TaskStatus::Pending);
+        let claimed = db.get_and_claim_next_task(4).await.unwrap();
+        assert_eq!(claimed.map(|task| task.id), Some(cleanup));
+    }
+
+    #[tokio::test]
+    async fn any_edge_is_met_by_a_failed_task() {
+        let db = Database::new(":memory:").await.unwrap();
+        let build = create_task(&db, &[]).await;
+        let cleanup = create_task(&db, &[(build, DependencyCondition::Any)]).await;
+        db.mark_task_failed(build, "broken", FailureKind::Crash).await.unwrap();
+
+        let updates = db.update_dependent_tasks().await.unwrap();
+
+        assert!(updates.blocked.is_empty() && updates.skipped.is_empty());
+        assert_eq!(status(&db, cleanup).await, TaskStatus::Pending);
+    }
+}
@@ -1,3 +1,4 @@
+use crate::models::FailureKind;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -20,6 +21,36 @@ pub enum CcschedError {
     #[error("Circular dependency detected in task graph")]
     CircularDependency,
     
+    #[error("Task {0} was cancelled")]
+    TaskCancelled(i64),
+    
+    #[error("Task timed out after {0}s")]
+    TaskTimedOut(u64),
+    
+    #[error("Task produced no output for {0}s")]
+    TaskIdleTimedOut(u64),
+    
+    #[error("Verification failed: {0}")]
+    VerificationFailed(String),
+    
+    #[error("Budget exceeded: {0}")]
+    BudgetExceeded(String),
+    
+    #[error("Permission policy not allowed: {0}")]
+    PolicyViolation(String),
+    
+    #[error("Hook failed: {0}")]
+    HookFailed(String),
+    
+    #[error("Template error: {0}")]
+    Template(String),
+    
+    #[error("Git error: {0}")]
+    Git(String),
+    
+    #[error("Checkpoint error: {0}")]
+    Checkpoint(String),
+    
     #[error("Claude execution error: {0}")]
     ClaudeExecution(String),
     
@@ -27,4 +58,19 @@ pub enum CcschedError {
     Config(String),
 }
 
+impl CcschedError {
+    /// Classifies a task execution error for the retry policy.
+    pub fn failure_kind(&self) -> FailureKind {
+        match self {
+            CcschedError::TaskTimedOut(_) | CcschedError::TaskIdleTimedOut(_) => FailureKind::Timeout,
+            CcschedError::VerificationFailed(_) => FailureKind::Verification,
+            CcschedError::BudgetExceeded(_) => FailureKind::Budget,
+            CcschedError::PolicyViolation(_) => FailureKind::Policy,
+            CcschedError::HookFailed(_) => FailureKind::Hook,
+            CcschedError::Template(_) => FailureKind::Template,
+            _ => FailureKind::Crash,
+        }
+    }
+}
+
 pub type Result<T> = std::result::Result<T, CcschedError>;
\ No newline at end of file
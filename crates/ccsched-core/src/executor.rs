@@ -0,0 +1,359 @@
+use crate::config::{Config, ProfileConfig};
+use crate::error::{CcschedError, Result};
+use crate::models::{PermissionMode, Task, Usage};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::ExitStatus;
+use std::sync::{Arc, Mutex};
+use tokio::process::Command;
+
+pub const DEFAULT_EXECUTOR: &str = "claude";
+
+/// Name under which Claude Code sees the tool that queues permission requests
+/// for human approval: the `approve` tool of the `ccsched` MCP server.
+pub const APPROVAL_TOOL: &str = "mcp__ccsched__approve";
+
+/// Names accepted for a task's `executor` field.
+pub const EXECUTOR_NAMES: &[&str] = &["claude", "shell", "fake"];
+
+/// Runs a task's prompt as an external process and interprets its output.
+///
+/// The worker owns the process: it spawns the command, writes the prompt to
+/// stdin, streams stdout into the task log and enforces cancellation and
+/// timeouts. The executor only decides what to run and what the output means.
+pub trait Executor: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Whether invocations can resume an earlier session. Executors without
+    /// sessions run each task in a single pass with no verification rounds.
+    fn supports_sessions(&self) -> bool;
+
+    /// Builds the command for one invocation under `profile`, resuming
+    /// `session_id` if given. The worker sets the profile's environment.
+    fn command(&self, task: &Task, session_id: Option<&str>, profile: &ProfileConfig) -> Result<Command>;
+
+    /// Interprets one line of the process's stdout.
+    fn parse_line(&self, line: &str) -> LineEvent;
+
+    /// Decides how the invocation ended from the exit status and the last result
+    /// event reported by `parse_line`.
+    fn outcome(&self, exit_status: ExitStatus, result: Option<&Value>) -> Outcome;
+}
+
+/// What a single stdout line told us about the invocation.
+#[derive(Debug, Default)]
+pub struct LineEvent {
+    pub session_id: Option<String>,
+    /// Final result event of the invocation
+    pub result: Option<Value>,
+}
+
+#[derive(Debug)]
+pub struct Outcome {
+    pub success: bool,
+    /// Unix timestamp at which a usage limit resets
+    pub rate_limit_timestamp: Option<i64>,
+    pub usage: Usage,
+}
+
+/// The executors available to workers, looked up by task executor name.
+#[derive(Clone)]
+pub struct Executors {
+    executors: HashMap<&'static str, Arc<dyn Executor>>,
+}
+
+impl Executors {
+    pub fn new(config: &Config) -> Self {
+        let executors: [Arc<dyn Executor>; 3] = [
+            Arc::new(ClaudeCodeExecutor::new(config)),
+            Arc::new(ShellExecutor),
+            Arc::new(FakeExecutor::default()),
+        ];
+
+        Self {
+            executors: executors
+                .into_iter()
+                .map(|executor| (executor.name(), executor))
+                .collect(),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Result<Arc<dyn Executor>> {
+        self.executors
+            .get(name)
+            .cloned()
+            .ok_or_else(|| CcschedError::Config(format!("Unknown executor: {name}")))
+    }
+}
+
+pub fn validate_executor_name(name: &str) -> Result<()> {
+    if EXECUTOR_NAMES.contains(&name) {
+        Ok(())
+    } else {
+        Err(CcschedError::Config(format!(
+            "Unknown executor '{name}', expected one of: {}",
+            EXECUTOR_NAMES.join(", ")
+        )))
+    }
+}
+
+/// Runs Claude Code in headless mode with stream-json output.
+pub struct ClaudeCodeExecutor {
+    claude_path: String,
+    /// Address the approval MCP server reports permission requests to
+    host: String,
+    port: u16,
+}
+
+impl ClaudeCodeExecutor {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            claude_path: config.claude_path.clone(),
+            host: config.host.clone(),
+            port: config.port,
+        }
+    }
+
+    /// MCP config running `ccsched approval-server` for the task, which forwards
+    /// Claude's permission prompts to the scheduler's approval queue.
+    fn approval_server_config(&self, task: &Task) -> Result<String> {
+        let ccsched_path = std::env::current_exe()?;
+        let config = serde_json::json!({
+            "mcpServers": {
+                "ccsched": {
+                    "command": ccsched_path,
+                    "args": [
+                        "approval-server",
+                        task.id.to_string(),
+                        "--host",
+                        self.host,
+                        "--port",
+                        self.port.to_string(),
+                    ],
+                },
+            },
+        });
+        Ok(config.to_string())
+    }
+}
+
+impl Executor for ClaudeCodeExecutor {
+    fn name(&self) -> &'static str {
+        "claude"
+    }
+
+    fn supports_sessions(&self) -> bool {
+        true
+    }
+
+    fn command(&self, task: &Task, session_id: Option<&str>, profile: &ProfileConfig) -> Result<Command> {
+        let claude_path = profile.claude_path.as_ref().unwrap_or(&self.claude_path);
+
+        // Resolve claude_path to absolute path if it's relative
+        let claude_path = if Path::new(claude_path).is_absolute() {
+            claude_path.clone()
+        } else {
+            // Resolve relative path based on current working directory
+            let current_dir = std::env::current_dir()
+                .map_err(|e| CcschedError::ClaudeExecution(format!("Failed to get current directory: {}", e)))?;
+            current_dir.join(claude_path)
+                .to_string_lossy()
+                .to_string()
+        };
+
+        let mut cmd = Command::new(&claude_path);
+        cmd.args(["--output-format", "stream-json", "--verbose"]);
+        cmd.args(task.permission_mode.args());
+        cmd.args(task.claude_options.args());
+
+        let mut mcp_configs: Vec<String> = task.claude_options.mcp_config.iter().cloned().collect();
+        if task.permission_mode == PermissionMode::Ask {
+            cmd.args(["--permission-prompt-tool", APPROVAL_TOOL]);
+            mcp_configs.push(self.approval_server_config(task)?);
+        }
+        if !mcp_configs.is_empty() {
+            cmd.arg("--mcp-config").args(mcp_configs);
+        }
+
+        if let Some(session_id) = session_id {
+            cmd.args(["-r", session_id]);
+            // A forked continuation branches off its parent's session instead of appending to it
+            if task.fork_session && task.parent_session_id.as_deref() == Some(session_id) {
+                cmd.arg("--fork-session");
+            }
+        }
+
+        Ok(cmd)
+    }
+
+    fn parse_line(&self, line: &str) -> LineEvent {
+        parse_stream_json_line(line)
+    }
+
+    fn outcome(&self, exit_status: ExitStatus, result: Option<&Value>) -> Outcome {
+        stream_json_outcome(exit_status, result)
+    }
+}
+
+/// Pipes the prompt into `sh`; the task succeeds if the script exits with 0.
+pub struct ShellExecutor;
+
+impl Executor for ShellExecutor {
+    fn name(&self) -> &'static str {
+        "shell"
+    }
+
+    fn supports_sessions(&self) -> bool {
+        false
+    }
+
+    fn command(&self, _task: &Task, _session_id: Option<&str>, _profile: &ProfileConfig) -> Result<Command> {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-s");
+        Ok(cmd)
+    }
+
+    fn parse_line(&self, _line: &str) -> LineEvent {
+        LineEvent::default()
+    }
+
+    fn outcome(&self, exit_status: ExitStatus, _result: Option<&Value>) -> Outcome {
+        Outcome {
+            success: exit_status.success(),
+            rate_limit_timestamp: None,
+            usage: Usage::default(),
+        }
+    }
+}
+
+/// Replays a canned stream-json file instead of calling Claude, so whole task
+/// graphs can be exercised offline.
+///
+/// The task's prompt is the path of the file to replay, relative to the task's
+/// working directory. Lines consisting of `---` split the file into sections:
+/// the first invocation of a task replays the first section, each verification
+/// round the next one, and the last section is repeated once they run out.
+#[derive(Default)]
+pub struct FakeExecutor {
+    invocations: Mutex<HashMap<i64, usize>>,
+}
+
+impl Executor for FakeExecutor {
+    fn name(&self) -> &'static str {
+        "fake"
+    }
+
+    fn supports_sessions(&self) -> bool {
+        true
+    }
+
+    fn command(&self, task: &Task, session_id: Option<&str>, _profile: &ProfileConfig) -> Result<Command> {
+        let script_path = Path::new(&task.cwd).join(task.prompt.trim());
+        let script = std::fs::read_to_string(&script_path).map_err(|e| {
+            CcschedError::ClaudeExecution(format!(
+                "Failed to read fake script {}: {}",
+                script_path.display(),
+                e
+            ))
+        })?;
+
+        let mut invocations = self.invocations.lock().unwrap();
+        let invocation = invocations.entry(task.id).or_default();
+        if session_id.is_none() {
+            *invocation = 0;
+        }
+
+        // Line ranges (1-based, inclusive) of every section of the script
+        let mut sections = Vec::new();
+        let mut start = 1;
+        let line_count = script.lines().count();
+        for (i, line) in script.lines().enumerate() {
+            if line.trim() == "---" {
+                sections.push((start, i));
+                start = i + 2;
+            }
+        }
+        sections.push((start, line_count));
+
+        let (first, last) = sections[(*invocation).min(sections.len() - 1)];
+        *invocation += 1;
+
+        let mut cmd = Command::new("sed");
+        cmd.arg("-n")
+            .arg(format!("{first},{last}p"))
+            .arg(&script_path);
+        Ok(cmd)
+    }
+
+    fn parse_line(&self, line: &str) -> LineEvent {
+        parse_stream_json_line(line)
+    }
+
+    fn outcome(&self, exit_status: ExitStatus, result: Option<&Value>) -> Outcome {
+        stream_json_outcome(exit_status, result)
+    }
+}
+
+/// Extracts the session ID and final result event from a Claude Code
+/// stream-json line.
+fn parse_stream_json_line(line: &str) -> LineEvent {
+    let Ok(json_value) = serde_json::from_str::<Value>(line) else {
+        return LineEvent::default();
+    };
+
+    let session_id = json_value
+        .get("session_id")
+        .and_then(|v| v.as_str())
+        .map(str::to_string);
+
+    let result = (json_value.get("type").and_then(|v| v.as_str()) == Some("result"))
+        .then_some(json_value);
+
+    LineEvent { session_id, result }
+}
+
+fn stream_json_outcome(exit_status: ExitStatus, result: Option<&Value>) -> Outcome {
+    let success = exit_status.success()
+        && result
+            .and_then(|v| v.get("subtype"))
+            .and_then(|v| v.as_str())
+            == Some("success")
+        && result
+            .and_then(|v| v.get("is_error"))
+            .and_then(|v| v.as_bool())
+            == Some(false);
+
+    // Check for rate limit error
+    let mut rate_limit_timestamp = None;
+    if let Some(last) = result
+        && last.get("is_error").and_then(|v| v.as_bool()) == Some(true)
+        && let Some(result) = last.get("result").and_then(|v| v.as_str())
+        && let Some(timestamp_str) = result.strip_prefix("Claude AI usage limit reached|")
+        && let Ok(timestamp) = timestamp_str.parse::<i64>()
+    {
+        rate_limit_timestamp = Some(timestamp);
+    }
+
+    Outcome {
+        success,
+        rate_limit_timestamp,
+        usage: result.map(stream_json_usage).unwrap_or_default(),
+    }
+}
+
+/// Reads token usage, cost, turns and duration from a result event.
+fn stream_json_usage(result: &Value) -> Usage {
+    let number = |value: Option<&Value>| value.and_then(|v| v.as_u64()).unwrap_or(0);
+    let tokens = |field: &str| number(result.get("usage").and_then(|usage| usage.get(field)));
+
+    Usage {
+        input_tokens: tokens("input_tokens"),
+        output_tokens: tokens("output_tokens"),
+        cache_read_tokens: tokens("cache_read_input_tokens"),
+        cache_creation_tokens: tokens("cache_creation_input_tokens"),
+        cost_usd: result.get("total_cost_usd").and_then(|v| v.as_f64()).unwrap_or(0.0),
+        num_turns: number(result.get("num_turns")),
+        duration_ms: number(result.get("duration_ms")),
+    }
+}
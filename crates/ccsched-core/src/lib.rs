@@ -1,6 +1,12 @@
+pub mod checkpoint;
 pub mod config;
 pub mod db;
 pub mod error;
+pub mod executor;
 pub mod models;
+pub mod profile;
 pub mod scheduler;
-pub mod worker;
\ No newline at end of file
+pub mod snapshot;
+pub mod template;
+pub mod worker;
+pub mod worktree;
\ No newline at end of file
@@ -1,5 +1,7 @@
-use chrono::NaiveDateTime;
+use chrono::{DateTime, NaiveDateTime, Utc};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
@@ -9,6 +11,34 @@ pub enum TaskStatus {
     Done,
     Failed,
     Waiting,
+    Cancelled,
+    /// Running, but blocked on a human answering a tool permission request
+    #[serde(rename = "awaiting_approval")]
+    AwaitingApproval,
+    /// Not scheduled because a task it depends on failed or was cancelled
+    Blocked,
+    /// Never run because the condition of one of its dependencies cannot be met
+    Skipped,
+}
+
+impl TaskStatus {
+    pub const ALL: &'static [TaskStatus] = &[
+        TaskStatus::Pending,
+        TaskStatus::Running,
+        TaskStatus::Done,
+        TaskStatus::Failed,
+        TaskStatus::Waiting,
+        TaskStatus::Cancelled,
+        TaskStatus::AwaitingApproval,
+        TaskStatus::Blocked,
+        TaskStatus::Skipped,
+    ];
+
+    /// Whether the task has reached a final state and will not be scheduled again
+    /// unless it is edited.
+    pub fn is_finished(&self) -> bool {
+        matches!(self, TaskStatus::Done | TaskStatus::Failed | TaskStatus::Cancelled | TaskStatus::Skipped)
+    }
 }
 
 impl std::fmt::Display for TaskStatus {
@@ -19,6 +49,10 @@ impl std::fmt::Display for TaskStatus {
             TaskStatus::Done => write!(f, "done"),
             TaskStatus::Failed => write!(f, "failed"),
             TaskStatus::Waiting => write!(f, "waiting"),
+            TaskStatus::Cancelled => write!(f, "cancelled"),
+            TaskStatus::AwaitingApproval => write!(f, "awaiting_approval"),
+            TaskStatus::Blocked => write!(f, "blocked"),
+            TaskStatus::Skipped => write!(f, "skipped"),
         }
     }
 }
@@ -33,11 +67,153 @@ impl std::str::FromStr for TaskStatus {
             "done" => Ok(TaskStatus::Done),
             "failed" => Ok(TaskStatus::Failed),
             "waiting" => Ok(TaskStatus::Waiting),
+            "cancelled" => Ok(TaskStatus::Cancelled),
+            "awaiting_approval" => Ok(TaskStatus::AwaitingApproval),
+            "blocked" => Ok(TaskStatus::Blocked),
+            "skipped" => Ok(TaskStatus::Skipped),
             _ => Err(format!("Invalid task status: {s}")),
         }
     }
 }
 
+/// Why a task execution failed; the retry policy decides per kind whether the
+/// task is re-queued.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FailureKind {
+    /// The executor process failed, exited unsuccessfully or could not be run
+    Crash,
+    /// The wall-clock or idle-output timeout was exceeded
+    Timeout,
+    /// Claude reported that the task cannot be completed, or verification did
+    /// not succeed within its rounds
+    Verification,
+    /// The task reached its maximum cost
+    Budget,
+    /// The task's permission mode exceeds what the server allows
+    Policy,
+    /// A `pre_run` hook failed, so Claude was not invoked
+    Hook,
+    /// The prompt refers to a template variable that has no value
+    Template,
+}
+
+impl FailureKind {
+    pub const ALL: &'static [FailureKind] = &[
+        FailureKind::Crash,
+        FailureKind::Timeout,
+        FailureKind::Verification,
+        FailureKind::Budget,
+        FailureKind::Policy,
+        FailureKind::Hook,
+        FailureKind::Template,
+    ];
+
+    /// Parses a comma-separated list such as "crash,timeout".
+    pub fn parse_list(s: &str) -> Result<Vec<FailureKind>, String> {
+        s.split(',')
+            .map(str::trim)
+            .filter(|kind| !kind.is_empty())
+            .map(str::parse)
+            .collect()
+    }
+
+    pub fn join(kinds: &[FailureKind]) -> String {
+        kinds.iter().map(ToString::to_string).collect::<Vec<_>>().join(",")
+    }
+}
+
+impl std::fmt::Display for FailureKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FailureKind::Crash => write!(f, "crash"),
+            FailureKind::Timeout => write!(f, "timeout"),
+            FailureKind::Verification => write!(f, "verification"),
+            FailureKind::Budget => write!(f, "budget"),
+            FailureKind::Policy => write!(f, "policy"),
+            FailureKind::Hook => write!(f, "hook"),
+            FailureKind::Template => write!(f, "template"),
+        }
+    }
+}
+
+impl std::str::FromStr for FailureKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "crash" => Ok(FailureKind::Crash),
+            "timeout" => Ok(FailureKind::Timeout),
+            "verification" => Ok(FailureKind::Verification),
+            "budget" => Ok(FailureKind::Budget),
+            "policy" => Ok(FailureKind::Policy),
+            "hook" => Ok(FailureKind::Hook),
+            "template" => Ok(FailureKind::Template),
+            _ => Err(format!("Invalid failure kind '{s}', expected one of: crash, timeout, verification, budget, policy, hook, template")),
+        }
+    }
+}
+
+/// How Claude handles tool permissions while running a task. Variants are
+/// ordered from most restrictive to most permissive.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "kebab-case")]
+pub enum PermissionMode {
+    /// Every tool use that needs permission is denied
+    DenyByDefault,
+    /// Only the task's allowed tools may be used without permission
+    AllowList,
+    /// Tools that are not allowed up front are queued for a human to approve
+    Ask,
+    /// File edits are accepted automatically, other tools need permission
+    AcceptEdits,
+    /// Permission checks are skipped entirely
+    Skip,
+}
+
+impl PermissionMode {
+    /// The Claude Code arguments selecting this mode. Allowed tools are passed
+    /// separately with the task's Claude options.
+    pub fn args(&self) -> &'static [&'static str] {
+        match self {
+            PermissionMode::DenyByDefault | PermissionMode::AllowList | PermissionMode::Ask => {
+                &["--permission-mode", "default"]
+            }
+            PermissionMode::AcceptEdits => &["--permission-mode", "acceptEdits"],
+            PermissionMode::Skip => &["--dangerously-skip-permissions"],
+        }
+    }
+}
+
+impl std::fmt::Display for PermissionMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PermissionMode::DenyByDefault => write!(f, "deny-by-default"),
+            PermissionMode::AllowList => write!(f, "allow-list"),
+            PermissionMode::Ask => write!(f, "ask"),
+            PermissionMode::AcceptEdits => write!(f, "accept-edits"),
+            PermissionMode::Skip => write!(f, "skip"),
+        }
+    }
+}
+
+impl std::str::FromStr for PermissionMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "deny-by-default" => Ok(PermissionMode::DenyByDefault),
+            "allow-list" => Ok(PermissionMode::AllowList),
+            "ask" => Ok(PermissionMode::Ask),
+            "accept-edits" => Ok(PermissionMode::AcceptEdits),
+            "skip" => Ok(PermissionMode::Skip),
+            _ => Err(format!(
+                "Invalid permission mode '{s}', expected one of: deny-by-default, allow-list, ask, accept-edits, skip"
+            )),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Task {
     pub id: i64,
@@ -51,21 +227,717 @@ pub struct Task {
     pub output: Option<String>,
     pub result: Option<String>,
     pub resume_at: Option<NaiveDateTime>,
+    pub timeout_secs: Option<u64>,
+    pub idle_timeout_secs: Option<u64>,
+    pub failure_reason: Option<String>,
+    pub executor: String,
+    pub verify: bool,
+    pub verification_template: Option<String>,
+    pub success_marker: Option<String>,
+    pub failure_marker: Option<String>,
+    pub max_verification_rounds: Option<u32>,
+    /// Number of verification rounds run so far
+    pub verification_rounds: u32,
+    /// Shell command whose exit status decides verification instead of markers
+    pub verify_cmd: Option<String>,
+    /// Output of the verification command, one section per round
+    pub check_output: Option<String>,
+    /// Kind of the last failure, if any
+    pub failure_kind: Option<FailureKind>,
+    /// Current attempt number, starting at 1
+    pub attempt: u32,
+    pub max_attempts: Option<u32>,
+    /// Base delay in seconds before the first retry; doubled for every further one
+    pub retry_backoff_secs: Option<u64>,
+    /// Failure kinds that are retried
+    pub retry_on: Option<Vec<FailureKind>>,
+    /// The task is not started again before this time after a failed attempt
+    pub retry_at: Option<NaiveDateTime>,
+    /// Usage summed over every invocation of the task
+    pub usage: Usage,
+    /// No further invocation is started once the task has cost this much
+    pub max_cost_usd: Option<f64>,
+    /// Profile the task last ran under
+    pub profile: Option<String>,
+    pub claude_options: ClaudeOptions,
+    pub permission_mode: PermissionMode,
+    pub hooks: Hooks,
+    /// Whether the task runs in a git worktree on a branch of its own
+    pub worktree: bool,
+    /// Ref the task's branch starts from (default: HEAD of `cwd`)
+    pub base_ref: Option<String>,
+    /// Worktree the task runs in, until it is cleaned up
+    pub worktree_path: Option<String>,
+    pub branch: Option<String>,
+    /// Commit `base_ref` pointed to when the worktree was created
+    pub base_commit: Option<String>,
+    /// Commit of the task's branch after a successful run
+    pub final_commit: Option<String>,
+    /// Failed or cancelled ancestor a blocked task is waiting on
+    pub blocked_by: Option<i64>,
+    /// Run the task was submitted in, as part of a workflow
+    pub run_id: Option<i64>,
+    /// Values of the `{{vars.<key>}}` placeholders in the prompt
+    pub vars: BTreeMap<String, String>,
+    /// Prompt with its placeholders filled in, as it was last sent
+    pub rendered_prompt: Option<String>,
+    /// Task whose Claude session this task continues
+    pub continue_from: Option<i64>,
+    /// Whether the task continues in a fork of that session, leaving it unchanged
+    pub fork_session: bool,
+    /// Session of `continue_from` the task's current attempt started from
+    pub parent_session_id: Option<String>,
+    /// Time the current attempt ran before it last stopped to wait for a rate
+    /// limit; counted against its timeout when it resumes
+    pub run_time_ms: u64,
+}
+
+
+/// Token usage and cost reported by Claude Code's result events.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub struct Usage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_read_tokens: u64,
+    pub cache_creation_tokens: u64,
+    pub cost_usd: f64,
+    pub num_turns: u64,
+    pub duration_ms: u64,
+}
+
+impl Usage {
+    pub fn add(&mut self, other: &Usage) {
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+        self.cache_read_tokens += other.cache_read_tokens;
+        self.cache_creation_tokens += other.cache_creation_tokens;
+        self.cost_usd += other.cost_usd;
+        self.num_turns += other.num_turns;
+        self.duration_ms += other.duration_ms;
+    }
+
+    pub fn total_tokens(&self) -> u64 {
+        self.input_tokens + self.output_tokens + self.cache_read_tokens + self.cache_creation_tokens
+    }
+}
+
+/// Claude Code CLI options applied to every invocation of a task, the initial
+/// run as well as each verification round.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ClaudeOptions {
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Model used when the primary one is overloaded
+    #[serde(default)]
+    pub fallback_model: Option<String>,
+    /// Maximum number of agentic turns per invocation
+    #[serde(default)]
+    pub max_turns: Option<u32>,
+    #[serde(default)]
+    pub allowed_tools: Vec<String>,
+    #[serde(default)]
+    pub disallowed_tools: Vec<String>,
+    /// Text appended to Claude Code's system prompt
+    #[serde(default)]
+    pub append_system_prompt: Option<String>,
+    /// Path of the MCP server configuration file
+    #[serde(default)]
+    pub mcp_config: Option<String>,
+}
+
+impl ClaudeOptions {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// The Claude Code arguments these options translate into, except for the
+    /// MCP config, which the executor merges with its own servers.
+    pub fn args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if let Some(model) = &self.model {
+            args.extend(["--model".to_string(), model.clone()]);
+        }
+        if let Some(fallback_model) = &self.fallback_model {
+            args.extend(["--fallback-model".to_string(), fallback_model.clone()]);
+        }
+        if let Some(max_turns) = self.max_turns {
+            args.extend(["--max-turns".to_string(), max_turns.to_string()]);
+        }
+        if !self.allowed_tools.is_empty() {
+            args.extend(["--allowedTools".to_string(), self.allowed_tools.join(",")]);
+        }
+        if !self.disallowed_tools.is_empty() {
+            args.extend(["--disallowedTools".to_string(), self.disallowed_tools.join(",")]);
+        }
+        if let Some(prompt) = &self.append_system_prompt {
+            args.extend(["--append-system-prompt".to_string(), prompt.clone()]);
+        }
+        args
+    }
+}
+
+/// Shell commands the worker runs around a task, in its working directory.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct Hooks {
+    /// Runs before Claude is first invoked; if it fails, so does the task
+    #[serde(default)]
+    pub pre_run: Option<String>,
+    #[serde(default)]
+    pub post_success: Option<String>,
+    /// Runs after a failed or cancelled attempt
+    #[serde(default)]
+    pub post_failure: Option<String>,
+    /// Runs after every attempt, following `post_success` or `post_failure`
+    #[serde(default)]
+    pub post_always: Option<String>,
+}
+
+impl Hooks {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub fn get(&self, kind: HookKind) -> Option<&str> {
+        match kind {
+            HookKind::PreRun => self.pre_run.as_deref(),
+            HookKind::PostSuccess => self.post_success.as_deref(),
+            HookKind::PostFailure => self.post_failure.as_deref(),
+            HookKind::PostAlways => self.post_always.as_deref(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HookKind {
+    PreRun,
+    PostSuccess,
+    PostFailure,
+    PostAlways,
 }
 
+impl HookKind {
+    pub const ALL: &'static [HookKind] = &[
+        HookKind::PreRun,
+        HookKind::PostSuccess,
+        HookKind::PostFailure,
+        HookKind::PostAlways,
+    ];
+}
+
+impl std::fmt::Display for HookKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HookKind::PreRun => write!(f, "pre_run"),
+            HookKind::PostSuccess => write!(f, "post_success"),
+            HookKind::PostFailure => write!(f, "post_failure"),
+            HookKind::PostAlways => write!(f, "post_always"),
+        }
+    }
+}
+
+/// Why the executor was invoked.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AttemptKind {
+    /// First pass of a task attempt
+    Initial,
+    /// Verification round resuming the task's session
+    Verification,
+    /// First pass of a task that resumes its session after waiting for a rate limit
+    Resume,
+}
+
+impl std::fmt::Display for AttemptKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AttemptKind::Initial => write!(f, "initial"),
+            AttemptKind::Verification => write!(f, "verification"),
+            AttemptKind::Resume => write!(f, "resume"),
+        }
+    }
+}
+
+impl std::str::FromStr for AttemptKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "initial" => Ok(AttemptKind::Initial),
+            "verification" => Ok(AttemptKind::Verification),
+            "resume" => Ok(AttemptKind::Resume),
+            _ => Err(format!("Invalid attempt kind: {s}")),
+        }
+    }
+}
+
+/// One invocation of a task's executor.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskAttempt {
+    pub id: i64,
+    pub task_id: i64,
+    /// The task's retry attempt this invocation belongs to
+    pub attempt: u32,
+    pub kind: AttemptKind,
+    pub started_at: NaiveDateTime,
+    pub finished_at: Option<NaiveDateTime>,
+    /// `None` while running, or if the process was killed or never exited
+    pub exit_code: Option<i32>,
+    pub session_id_in: Option<String>,
+    pub session_id_out: Option<String>,
+    pub profile: Option<String>,
+    /// Byte range of the invocation's output in the task log
+    pub log_start: u64,
+    pub log_end: Option<u64>,
+    /// Final result event reported by the executor, as JSON
+    pub result: Option<String>,
+    pub usage: Usage,
+}
+
+/// Answer to a tool permission request.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ApprovalStatus {
+    Pending,
+    Approved,
+    Denied,
+    /// Denied automatically because nobody answered in time, or because the
+    /// task stopped first
+    Expired,
+}
+
+impl std::fmt::Display for ApprovalStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApprovalStatus::Pending => write!(f, "pending"),
+            ApprovalStatus::Approved => write!(f, "approved"),
+            ApprovalStatus::Denied => write!(f, "denied"),
+            ApprovalStatus::Expired => write!(f, "expired"),
+        }
+    }
+}
+
+impl std::str::FromStr for ApprovalStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "pending" => Ok(ApprovalStatus::Pending),
+            "approved" => Ok(ApprovalStatus::Approved),
+            "denied" => Ok(ApprovalStatus::Denied),
+            "expired" => Ok(ApprovalStatus::Expired),
+            _ => Err(format!("Invalid approval status: {s}")),
+        }
+    }
+}
+
+/// A tool use Claude asked permission for while running a task.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Approval {
+    pub id: i64,
+    pub task_id: i64,
+    pub tool_name: String,
+    /// Arguments of the tool use
+    pub input: Value,
+    pub status: ApprovalStatus,
+    pub requested_at: NaiveDateTime,
+    pub decided_at: Option<NaiveDateTime>,
+    /// Explanation passed back to Claude with a denial
+    pub message: Option<String>,
+}
+
+/// What a task changed in its git repository, between a snapshot taken before
+/// it first ran and one taken each time it stops.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskDiff {
+    pub task_id: i64,
+    /// Snapshot commit of the working tree before the task ran; its parent is the HEAD at that time
+    pub before_snapshot: String,
+    /// Snapshot commit of the working tree when the task last stopped
+    pub after_snapshot: Option<String>,
+    /// `git diff --stat` output
+    pub stat: Option<String>,
+    pub patch: Option<String>,
+    pub updated_at: NaiveDateTime,
+}
+
+/// How a checkpoint of a task's working directory is stored.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckpointKind {
+    /// The task's git snapshot commit from before it ran
+    Git,
+    /// A tarball of a working directory outside any git repository
+    Tarball,
+}
+
+impl std::fmt::Display for CheckpointKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CheckpointKind::Git => write!(f, "git"),
+            CheckpointKind::Tarball => write!(f, "tarball"),
+        }
+    }
+}
+
+impl std::str::FromStr for CheckpointKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "git" => Ok(CheckpointKind::Git),
+            "tarball" => Ok(CheckpointKind::Tarball),
+            _ => Err(format!("Invalid checkpoint kind: {s}")),
+        }
+    }
+}
+
+/// State of a task's working directory before it first ran, which a rollback
+/// restores.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub task_id: i64,
+    pub kind: CheckpointKind,
+    /// Repository root, or working directory for tarballs
+    pub root: String,
+    /// Snapshot commit or tarball path
+    pub location: String,
+    /// Files under `root` the task changed, known once it has stopped
+    pub changed_files: Option<Vec<String>>,
+    pub created_at: NaiveDateTime,
+    pub rolled_back_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RollbackRequest {
+    /// Roll back even if later tasks changed the same files
+    #[serde(default)]
+    pub force: bool,
+}
+
+/// A task of a workflow. Its name is the one other tasks of the workflow refer
+/// to in `needs`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkflowTask {
+    /// Names of the workflow tasks this one depends on, each optionally
+    /// suffixed with a condition, e.g. `build` or `build:failure`
+    #[serde(default)]
+    pub needs: Vec<String>,
+    #[serde(flatten)]
+    pub request: CreateTaskRequest,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ApplyWorkflowRequest {
+    /// Name of the run the workflow's tasks are grouped in
+    #[serde(default)]
+    pub name: Option<String>,
+    pub tasks: Vec<WorkflowTask>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApplyWorkflowResponse {
+    pub run_id: i64,
+    /// ID of every task created, by its name in the workflow
+    pub task_ids: BTreeMap<String, i64>,
+}
+
+/// Overall state of a run, rolled up from the statuses of its tasks.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RunStatus {
+    /// No task has started yet
+    Pending,
+    /// Some tasks are running or still to run
+    Running,
+    /// Every task succeeded or was skipped
+    Succeeded,
+    /// Nothing is left to run, and some tasks failed while others succeeded
+    PartiallyFailed,
+    /// Nothing is left to run, and no task succeeded
+    Failed,
+    /// Nothing is left to run, and tasks were cancelled but none failed
+    Cancelled,
+}
+
+impl RunStatus {
+    pub fn of(tasks: &[TaskStatus]) -> Self {
+        let any = |statuses: &[TaskStatus]| tasks.iter().any(|status| statuses.contains(status));
+
+        if any(&[TaskStatus::Running, TaskStatus::AwaitingApproval, TaskStatus::Waiting]) {
+            return RunStatus::Running;
+        }
+        if any(&[TaskStatus::Pending]) {
+            return if tasks.iter().all(|status| *status == TaskStatus::Pending) {
+                RunStatus::Pending
+            } else {
+                RunStatus::Running
+            };
+        }
+
+        if any(&[TaskStatus::Failed, TaskStatus::Blocked]) {
+            if any(&[TaskStatus::Done]) {
+                RunStatus::PartiallyFailed
+            } else {
+                RunStatus::Failed
+            }
+        } else if any(&[TaskStatus::Cancelled]) {
+            RunStatus::Cancelled
+        } else {
+            RunStatus::Succeeded
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        !matches!(self, RunStatus::Pending | RunStatus::Running)
+    }
+}
+
+impl std::fmt::Display for RunStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RunStatus::Pending => write!(f, "pending"),
+            RunStatus::Running => write!(f, "running"),
+            RunStatus::Succeeded => write!(f, "succeeded"),
+            RunStatus::PartiallyFailed => write!(f, "partially_failed"),
+            RunStatus::Failed => write!(f, "failed"),
+            RunStatus::Cancelled => write!(f, "cancelled"),
+        }
+    }
+}
+
+/// A group of tasks submitted together from a workflow.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Run {
+    pub id: i64,
+    pub name: String,
+    pub created_at: NaiveDateTime,
+    pub status: RunStatus,
+    /// When the first of its tasks started
+    pub started_at: Option<NaiveDateTime>,
+    /// When the last of its tasks finished, once nothing is left to run
+    pub finished_at: Option<NaiveDateTime>,
+    pub task_count: usize,
+    /// Number of tasks that succeeded
+    pub done_count: usize,
+    pub cost_usd: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunsResponse {
+    pub runs: Vec<Run>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunDetails {
+    #[serde(flatten)]
+    pub run: Run,
+    pub tasks: Vec<TaskInfo>,
+}
+
+/// Tasks of a run that a cancel or retry acted on.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunActionResponse {
+    pub task_ids: Vec<i64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DependenciesRequest {
+    pub dependencies: Vec<Dependency>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DependenciesResponse {
+    pub dependencies: Vec<Dependency>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RollbackResponse {
+    pub root: String,
+    /// Files restored to their state before the task, relative to `root`
+    pub files: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateApprovalRequest {
+    pub tool_name: String,
+    #[serde(default)]
+    pub input: Value,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ApprovalDecision {
+    /// Explanation passed back to Claude with a denial
+    #[serde(default)]
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApprovalsResponse {
+    pub approvals: Vec<Approval>,
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TaskDependency {
     pub task_id: i64,
     pub depends_on_id: i64,
+    pub condition: DependencyCondition,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// How a task must have finished for a task depending on it to run.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DependencyCondition {
+    /// The dependency succeeded
+    #[default]
+    Success,
+    /// The dependency failed
+    Failure,
+    /// The dependency finished, whatever its outcome
+    Any,
+}
+
+impl std::fmt::Display for DependencyCondition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DependencyCondition::Success => write!(f, "success"),
+            DependencyCondition::Failure => write!(f, "failure"),
+            DependencyCondition::Any => write!(f, "any"),
+        }
+    }
+}
+
+impl std::str::FromStr for DependencyCondition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "success" => Ok(DependencyCondition::Success),
+            "failure" => Ok(DependencyCondition::Failure),
+            "any" => Ok(DependencyCondition::Any),
+            _ => Err(format!("Invalid dependency condition: {s} (expected success, failure or any)")),
+        }
+    }
+}
+
+/// A dependency of a task on another one, written `<id>` or `<id>:<condition>`.
+/// In requests, a plain task ID is accepted for a dependency on success.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(from = "DependencySpec")]
+pub struct Dependency {
+    pub id: i64,
+    pub condition: DependencyCondition,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum DependencySpec {
+    Id(i64),
+    Edge {
+        id: i64,
+        #[serde(default)]
+        condition: DependencyCondition,
+    },
+}
+
+impl From<DependencySpec> for Dependency {
+    fn from(spec: DependencySpec) -> Self {
+        match spec {
+            DependencySpec::Id(id) => Dependency { id, condition: DependencyCondition::Success },
+            DependencySpec::Edge { id, condition } => Dependency { id, condition },
+        }
+    }
+}
+
+impl std::fmt::Display for Dependency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.condition {
+            DependencyCondition::Success => write!(f, "{}", self.id),
+            condition => write!(f, "{}:{}", self.id, condition),
+        }
+    }
+}
+
+impl std::str::FromStr for Dependency {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (id, condition) = match s.trim().split_once(':') {
+            Some((id, condition)) => (id, condition.trim().parse()?),
+            None => (s.trim(), DependencyCondition::Success),
+        };
+        let id = id.trim().parse().map_err(|e| format!("Invalid dependency ID '{id}': {e}"))?;
+
+        Ok(Dependency { id, condition })
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct CreateTaskRequest {
     pub name: String,
     pub prompt: String,
     pub cwd: String,
-    pub depends_on: Vec<i64>,
+    #[serde(default)]
+    pub depends_on: Vec<Dependency>,
+    /// Wall-clock limit in seconds across all Claude invocations of the task
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+    /// Maximum number of seconds without a new stream-json line
+    #[serde(default)]
+    pub idle_timeout_secs: Option<u64>,
+    /// Executor to run the task with (default: "claude")
+    #[serde(default)]
+    pub executor: Option<String>,
+    /// Whether to run verification rounds after the first pass (default: true)
+    #[serde(default)]
+    pub verify: Option<bool>,
+    /// Verification prompt template (default: server setting)
+    #[serde(default)]
+    pub verification_template: Option<String>,
+    #[serde(default)]
+    pub success_marker: Option<String>,
+    #[serde(default)]
+    pub failure_marker: Option<String>,
+    #[serde(default)]
+    pub max_verification_rounds: Option<u32>,
+    /// Shell command run in `cwd` after each pass; the task is done once it exits with 0
+    #[serde(default)]
+    pub verify_cmd: Option<String>,
+    /// Total number of attempts including retries (default: server setting)
+    #[serde(default)]
+    pub max_attempts: Option<u32>,
+    /// Base retry delay in seconds (default: server setting)
+    #[serde(default)]
+    pub retry_backoff_secs: Option<u64>,
+    /// Failure kinds that are retried (default: server setting)
+    #[serde(default)]
+    pub retry_on: Option<Vec<FailureKind>>,
+    /// Maximum cost in USD across all invocations (default: server setting)
+    #[serde(default)]
+    pub max_cost_usd: Option<f64>,
+    #[serde(default)]
+    pub claude_options: ClaudeOptions,
+    /// How Claude handles tool permissions (default: server setting)
+    #[serde(default)]
+    pub permission_mode: Option<PermissionMode>,
+    /// Task hooks, run in addition to the server's
+    #[serde(default)]
+    pub hooks: Hooks,
+    /// Run the task in a git worktree on branch `ccsched/task-<id>`
+    #[serde(default)]
+    pub worktree: bool,
+    /// Ref the worktree's branch starts from (default: HEAD)
+    #[serde(default)]
+    pub base_ref: Option<String>,
+    /// Values of the `{{vars.<key>}}` placeholders in the prompt
+    #[serde(default)]
+    pub vars: BTreeMap<String, String>,
+    /// Continue the latest Claude session of this task, which becomes a dependency
+    #[serde(default)]
+    pub continue_from: Option<i64>,
+    /// Continue in a fork of that session instead of appending to it
+    #[serde(default)]
+    pub fork_session: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -76,6 +948,68 @@ pub struct CreateTaskResponse {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TaskListResponse {
     pub tasks: Vec<TaskInfo>,
+    /// Set while the scheduler is not starting new tasks
+    #[serde(default)]
+    pub paused: Option<SchedulerPause>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorktreeCleanupResponse {
+    /// Paths of the worktrees that were removed
+    pub removed: Vec<String>,
+    /// Snapshot refs and tarballs of deleted tasks' checkpoints that were removed
+    #[serde(default)]
+    pub removed_checkpoints: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProfilesResponse {
+    pub profiles: Vec<ProfileStatus>,
+}
+
+/// Configuration, load and rate-limit state of an execution profile.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProfileStatus {
+    pub name: String,
+    pub claude_path: Option<String>,
+    /// Names of the environment variables the profile overrides
+    pub env: Vec<String>,
+    /// Set while the profile's usage limit has not reset
+    pub limited_until: Option<DateTime<Utc>>,
+    /// Tasks currently executing under the profile
+    pub running: usize,
+    pub invocations: u64,
+    pub usage: Usage,
+}
+
+/// Why the scheduler has stopped starting tasks, and until when.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchedulerPause {
+    pub until: DateTime<Utc>,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskAttemptsResponse {
+    pub attempts: Vec<TaskAttempt>,
+}
+
+/// Usage of a group of tasks in `ccsched stats`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageGroup {
+    /// Day, working directory or status the group is keyed by
+    pub key: String,
+    pub tasks: u64,
+    pub usage: Usage,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatsResponse {
+    /// Grouped by the UTC day each invocation started
+    pub by_day: Vec<UsageGroup>,
+    pub by_cwd: Vec<UsageGroup>,
+    pub by_status: Vec<UsageGroup>,
+    pub total: Usage,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -88,6 +1022,10 @@ pub struct TaskInfo {
     pub submitted_at: NaiveDateTime,
     pub finished_at: Option<NaiveDateTime>,
     pub resume_at: Option<NaiveDateTime>,
+    pub executor: String,
+    pub usage: Usage,
+    pub blocked_by: Option<i64>,
+    pub run_id: Option<i64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -102,6 +1040,43 @@ pub struct TaskInfoWithPrompt {
     pub finished_at: Option<NaiveDateTime>,
     pub result: Option<String>,
     pub resume_at: Option<NaiveDateTime>,
+    pub timeout_secs: Option<u64>,
+    pub idle_timeout_secs: Option<u64>,
+    pub failure_reason: Option<String>,
+    pub executor: String,
+    pub verify: bool,
+    pub verification_template: Option<String>,
+    pub success_marker: Option<String>,
+    pub failure_marker: Option<String>,
+    pub max_verification_rounds: Option<u32>,
+    pub verification_rounds: u32,
+    pub verify_cmd: Option<String>,
+    pub check_output: Option<String>,
+    pub failure_kind: Option<FailureKind>,
+    pub attempt: u32,
+    pub max_attempts: Option<u32>,
+    pub retry_backoff_secs: Option<u64>,
+    pub retry_on: Option<Vec<FailureKind>>,
+    pub retry_at: Option<NaiveDateTime>,
+    pub usage: Usage,
+    pub max_cost_usd: Option<f64>,
+    pub profile: Option<String>,
+    pub claude_options: ClaudeOptions,
+    pub permission_mode: PermissionMode,
+    pub hooks: Hooks,
+    pub worktree: bool,
+    pub base_ref: Option<String>,
+    pub worktree_path: Option<String>,
+    pub branch: Option<String>,
+    pub base_commit: Option<String>,
+    pub final_commit: Option<String>,
+    pub blocked_by: Option<i64>,
+    pub run_id: Option<i64>,
+    pub vars: BTreeMap<String, String>,
+    pub rendered_prompt: Option<String>,
+    pub continue_from: Option<i64>,
+    pub fork_session: bool,
+    pub parent_session_id: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -120,6 +1095,10 @@ impl From<Task> for TaskInfo {
             submitted_at: task.submitted_at,
             finished_at: task.finished_at,
             resume_at: task.resume_at,
+            executor: task.executor,
+            usage: task.usage,
+            blocked_by: task.blocked_by,
+            run_id: task.run_id,
         }
     }
 }
@@ -137,6 +1116,77 @@ impl From<Task> for TaskInfoWithPrompt {
             finished_at: task.finished_at,
             result: task.result,
             resume_at: task.resume_at,
+            timeout_secs: task.timeout_secs,
+            idle_timeout_secs: task.idle_timeout_secs,
+            failure_reason: task.failure_reason,
+            executor: task.executor,
+            verify: task.verify,
+            verification_template: task.verification_template,
+            success_marker: task.success_marker,
+            failure_marker: task.failure_marker,
+            max_verification_rounds: task.max_verification_rounds,
+            verification_rounds: task.verification_rounds,
+            verify_cmd: task.verify_cmd,
+            check_output: task.check_output,
+            failure_kind: task.failure_kind,
+            attempt: task.attempt,
+            max_attempts: task.max_attempts,
+            retry_backoff_secs: task.retry_backoff_secs,
+            retry_on: task.retry_on,
+            retry_at: task.retry_at,
+            usage: task.usage,
+            max_cost_usd: task.max_cost_usd,
+            profile: task.profile,
+            claude_options: task.claude_options,
+            permission_mode: task.permission_mode,
+            hooks: task.hooks,
+            worktree: task.worktree,
+            base_ref: task.base_ref,
+            worktree_path: task.worktree_path,
+            branch: task.branch,
+            base_commit: task.base_commit,
+            final_commit: task.final_commit,
+            blocked_by: task.blocked_by,
+            run_id: task.run_id,
+            vars: task.vars,
+            rendered_prompt: task.rendered_prompt,
+            continue_from: task.continue_from,
+            fork_session: task.fork_session,
+            parent_session_id: task.parent_session_id,
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_status_rolls_up_task_statuses() {
+        use TaskStatus::*;
+
+        let cases: &[(&[TaskStatus], RunStatus)] = &[
+            (&[Pending, Pending], RunStatus::Pending),
+            (&[Done, Pending], RunStatus::Running),
+            (&[Failed, Waiting], RunStatus::Running),
+            (&[Pending, AwaitingApproval], RunStatus::Running),
+            (&[Done, Skipped], RunStatus::Succeeded),
+            (&[Done, Failed, Skipped], RunStatus::PartiallyFailed),
+            (&[Done, Blocked], RunStatus::PartiallyFailed),
+            (&[Failed, Blocked, Skipped], RunStatus::Failed),
+            (&[Done, Cancelled], RunStatus::Cancelled),
+            (&[Failed, Cancelled], RunStatus::Failed),
+        ];
+        for (statuses, expected) in cases {
+            assert_eq!(RunStatus::of(statuses), *expected, "statuses {statuses:?}");
+        }
+    }
+
+    #[test]
+    fn only_pending_and_running_runs_are_unfinished() {
+        assert!(!RunStatus::Pending.is_finished());
+        assert!(!RunStatus::Running.is_finished());
+        assert!(RunStatus::PartiallyFailed.is_finished());
+        assert!(RunStatus::Cancelled.is_finished());
+    }
+}
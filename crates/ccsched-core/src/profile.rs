@@ -0,0 +1,152 @@
+use crate::config::ProfileConfig;
+use crate::models::ProfileStatus;
+use chrono::{DateTime, Utc};
+use std::sync::{Arc, Mutex};
+
+/// Rate-limit and load state of the configured profiles, shared by the scheduler
+/// and its workers. Rate limits are kept in memory only; a profile that is still
+/// limited after a restart is detected again by its next invocation.
+#[derive(Clone)]
+pub struct Profiles {
+    configs: Arc<Vec<ProfileConfig>>,
+    /// Indexed like `configs`
+    states: Arc<Mutex<Vec<ProfileState>>>,
+}
+
+#[derive(Debug, Default)]
+struct ProfileState {
+    limited_until: Option<DateTime<Utc>>,
+    /// Tasks currently executing under the profile
+    running: usize,
+}
+
+impl ProfileState {
+    fn is_limited(&self, now: DateTime<Utc>) -> bool {
+        self.limited_until.is_some_and(|until| until > now)
+    }
+}
+
+impl Profiles {
+    pub fn new(configs: &[ProfileConfig]) -> Self {
+        Self {
+            configs: Arc::new(configs.to_vec()),
+            states: Arc::new(Mutex::new(configs.iter().map(|_| ProfileState::default()).collect())),
+        }
+    }
+
+    /// Picks the profile to run a task under. A task with a session has to stay
+    /// with `session_profile`, the profile holding it, and gets `None` while that
+    /// profile is rate limited. Other tasks get the least busy profile that is
+    /// not limited, or `None` while every profile is.
+    pub fn acquire(&self, session_profile: Option<&str>) -> Option<ProfileLease> {
+        let now = Utc::now();
+        let mut states = self.states.lock().unwrap();
+
+        let index = match session_profile {
+            Some(name) => self
+                .position(name)
+                .filter(|&index| !states[index].is_limited(now))?,
+            None => states
+                .iter()
+                .enumerate()
+                .filter(|(_, state)| !state.is_limited(now))
+                .min_by_key(|(_, state)| state.running)
+                .map(|(index, _)| index)?,
+        };
+
+        states[index].running += 1;
+        Some(ProfileLease {
+            profiles: self.clone(),
+            index,
+        })
+    }
+
+    /// Whether a profile with this name is configured.
+    pub fn contains(&self, name: &str) -> bool {
+        self.position(name).is_some()
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.configs.iter().position(|config| config.name == name)
+    }
+
+    /// Records that a profile hit its usage limit, which resets at `until`.
+    pub fn mark_limited(&self, name: &str, until: DateTime<Utc>) {
+        if let Some(index) = self.position(name) {
+            self.states.lock().unwrap()[index].limited_until = Some(until);
+        }
+    }
+
+    /// When the named profile becomes available again, or `None` if it is
+    /// available now.
+    pub fn limited_until(&self, name: &str) -> Option<DateTime<Utc>> {
+        let now = Utc::now();
+        let states = self.states.lock().unwrap();
+
+        self.position(name)
+            .map(|index| &states[index])
+            .filter(|state| state.is_limited(now))
+            .and_then(|state| state.limited_until)
+    }
+
+    /// When the first profile becomes available again, or `None` if one is
+    /// available now.
+    pub fn all_limited_until(&self) -> Option<DateTime<Utc>> {
+        let now = Utc::now();
+        let states = self.states.lock().unwrap();
+
+        if states.iter().any(|state| !state.is_limited(now)) {
+            return None;
+        }
+        states.iter().filter_map(|state| state.limited_until).min()
+    }
+
+    /// Current state of every profile. Usage is left empty for the caller to
+    /// fill in from the database.
+    pub fn statuses(&self) -> Vec<ProfileStatus> {
+        let now = Utc::now();
+        let states = self.states.lock().unwrap();
+
+        self.configs
+            .iter()
+            .zip(states.iter())
+            .map(|(config, state)| {
+                let mut env: Vec<String> = config.env.keys().cloned().collect();
+                env.sort();
+                ProfileStatus {
+                    name: config.name.clone(),
+                    claude_path: config.claude_path.clone(),
+                    env,
+                    limited_until: state.limited_until.filter(|_| state.is_limited(now)),
+                    running: state.running,
+                    invocations: 0,
+                    usage: Default::default(),
+                }
+            })
+            .collect()
+    }
+}
+
+/// A task's claim on a profile; the profile counts the task as running until the
+/// lease is dropped.
+pub struct ProfileLease {
+    profiles: Profiles,
+    index: usize,
+}
+
+impl ProfileLease {
+    pub fn config(&self) -> &ProfileConfig {
+        &self.profiles.configs[self.index]
+    }
+
+    pub fn name(&self) -> &str {
+        &self.config().name
+    }
+}
+
+impl Drop for ProfileLease {
+    fn drop(&mut self) {
+        let mut states = self.profiles.states.lock().unwrap();
+        states[self.index].running = states[self.index].running.saturating_sub(1);
+    }
+}
@@ -1,8 +1,10 @@
-use crate::config::Config;
+use crate::config::{BudgetConfig, Config};
 use crate::db::Database;
-use crate::error::Result;
-use crate::models::{Task, TaskStatus};
-use crate::worker::Worker;
+use crate::error::{CcschedError, Result};
+use crate::executor::Executors;
+use crate::models::{ApprovalStatus, ProfileStatus, SchedulerPause, Task, TaskStatus};
+use crate::profile::Profiles;
+use crate::worker::{ActiveTasks, Worker};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::{mpsc, watch};
@@ -10,12 +12,77 @@ use tokio::time;
 use tracing::{error, info, warn};
 use chrono::{DateTime, Utc};
 
+/// Cloneable handle for acting on the scheduler's tasks from outside its run loop.
+#[derive(Clone)]
+pub struct SchedulerHandle {
+    db: Arc<Database>,
+    active_tasks: ActiveTasks,
+    profiles: Profiles,
+    pause_receiver: watch::Receiver<Option<SchedulerPause>>,
+}
+
+impl SchedulerHandle {
+    /// Why the scheduler is currently not starting tasks, if it is paused.
+    pub fn pause(&self) -> Option<SchedulerPause> {
+        if let Some(pause) = self.pause_receiver.borrow().clone() {
+            return Some(pause);
+        }
+
+        self.profiles.all_limited_until().map(|until| SchedulerPause {
+            until,
+            reason: "Claude usage limit reached on every profile".to_string(),
+        })
+    }
+
+    /// Load and rate-limit state of every execution profile.
+    pub fn profiles(&self) -> Vec<ProfileStatus> {
+        self.profiles.statuses()
+    }
+
+    /// Cancels a task. A task being executed has its Claude process terminated and
+    /// is marked cancelled by its worker; any other unfinished task is marked
+    /// cancelled immediately so the scheduler skips it.
+    pub async fn cancel_task(&self, id: i64) -> Result<()> {
+        let task = self.db.get_task(id).await?;
+        if task.status.is_finished() {
+            return Err(CcschedError::InvalidStatusTransition {
+                from: task.status.to_string(),
+                to: TaskStatus::Cancelled.to_string(),
+            });
+        }
+
+        let signalled = match self.active_tasks.lock().unwrap().get(&id) {
+            Some(cancel_sender) => cancel_sender.send(true).is_ok(),
+            None => false,
+        };
+        if signalled {
+            info!("Sent cancel signal to worker executing task {}", id);
+            return Ok(());
+        }
+
+        if !self.db.mark_task_cancelled(id).await? {
+            let task = self.db.get_task(id).await?;
+            return Err(CcschedError::InvalidStatusTransition {
+                from: task.status.to_string(),
+                to: TaskStatus::Cancelled.to_string(),
+            });
+        }
+        info!("Cancelled task {}", id);
+
+        Ok(())
+    }
+}
+
 pub struct Scheduler {
     db: Arc<Database>,
     task_sender: mpsc::Sender<Task>,
+    max_concurrency: usize,
+    active_tasks: ActiveTasks,
+    profiles: Profiles,
     check_interval: Duration,
-    pause_sender: watch::Sender<Option<DateTime<Utc>>>,
-    rate_limit_receiver: mpsc::Receiver<DateTime<Utc>>,
+    budget: BudgetConfig,
+    approval_timeout_secs: u64,
+    pause_sender: watch::Sender<Option<SchedulerPause>>,
 }
 
 impl Scheduler {
@@ -23,20 +90,39 @@ impl Scheduler {
         let db = Arc::new(db);
         let (task_sender, task_receiver) = mpsc::channel::<Task>(100);
         let (pause_sender, pause_receiver) = watch::channel(None);
-        let (rate_limit_sender, rate_limit_receiver) = mpsc::channel::<DateTime<Utc>>(10);
-        
-        let worker = Arc::new(Worker::new(db.as_ref().clone(), config, rate_limit_sender));
-        let worker_clone = worker.clone();
-        tokio::spawn(async move {
-            worker_clone.run(task_receiver, pause_receiver).await;
-        });
+        let max_concurrency = config.max_concurrency;
+        let active_tasks = ActiveTasks::default();
+        let profiles = Profiles::new(&config.profiles);
+        let task_receiver = Arc::new(tokio::sync::Mutex::new(task_receiver));
+        let executors = Executors::new(&config);
+
+        info!("Starting {} worker(s)", max_concurrency);
+        for worker_id in 0..max_concurrency {
+            let worker = Worker::new(
+                worker_id,
+                db.as_ref().clone(),
+                config.clone(),
+                executors.clone(),
+                profiles.clone(),
+                active_tasks.clone(),
+            );
+            let task_receiver = task_receiver.clone();
+            let pause_receiver = pause_receiver.clone();
+            tokio::spawn(async move {
+                worker.run(task_receiver, pause_receiver).await;
+            });
+        }
 
         Self {
             db,
             task_sender,
+            max_concurrency,
+            active_tasks,
+            profiles,
             check_interval: Duration::from_secs(5),
+            budget: config.budget.clone(),
+            approval_timeout_secs: config.permissions.approval_timeout_secs,
             pause_sender,
-            rate_limit_receiver,
         }
     }
 
@@ -64,97 +150,153 @@ impl Scheduler {
         let mut paused_until: Option<DateTime<Utc>> = None;
 
         loop {
-            tokio::select! {
-                _ = interval.tick() => {
-                    // Check if we're currently paused
-                    if let Some(resume_time) = paused_until {
-                        let now = Utc::now();
-                        if now < resume_time {
-                            // Still paused, skip scheduling
-                            continue;
-                        } else {
-                            // Resume time reached, clear pause and resume waiting tasks
-                            info!("Resuming scheduler, resume time reached");
-                            paused_until = None;
-                            if let Err(e) = self.pause_sender.send(None) {
-                                error!("Failed to send resume signal: {}", e);
-                            }
-                            if let Err(e) = self.resume_waiting_tasks().await {
-                                error!("Error resuming waiting tasks: {}", e);
-                            }
-                            // Immediately try to schedule resumed tasks
-                            if let Err(e) = self.schedule_ready_tasks().await {
-                                error!("Error scheduling resumed tasks: {}", e);
-                            }
-                        }
+            interval.tick().await;
+
+            if let Err(e) = self.expire_stale_approvals().await {
+                error!("Error expiring approvals: {}", e);
+            }
+
+            if let Err(e) = self.update_dependent_tasks().await {
+                error!("Error updating dependent tasks: {}", e);
+            }
+
+            // Check if we're currently paused
+            if let Some(resume_time) = paused_until {
+                let now = Utc::now();
+                if now < resume_time {
+                    // Still paused, skip scheduling
+                    continue;
+                } else {
+                    // Resume time reached, clear pause and resume waiting tasks
+                    info!("Resuming scheduler, resume time reached");
+                    paused_until = None;
+                    if let Err(e) = self.pause_sender.send(None) {
+                        error!("Failed to send resume signal: {}", e);
                     }
-                    
-                    if paused_until.is_none() {
-                        if let Err(e) = self.schedule_ready_tasks().await {
-                            error!("Error during task scheduling: {}", e);
-                        }
+                    if let Err(e) = self.resume_waiting_tasks().await {
+                        error!("Error resuming waiting tasks: {}", e);
                     }
                 }
-                rate_limit_time = self.rate_limit_receiver.recv() => {
-                    if let Some(resume_time) = rate_limit_time {
-                        warn!("Received rate limit signal, pausing scheduler until {:?}", resume_time);
-                        paused_until = Some(resume_time);
-                        
-                        // Send pause signal to worker
-                        if let Err(e) = self.pause_sender.send(Some(resume_time)) {
-                            error!("Failed to send pause signal: {}", e);
-                        }
-                        
-                        // Convert any running tasks to waiting
-                        if let Err(e) = self.convert_running_to_waiting(resume_time).await {
-                            error!("Error converting running tasks to waiting: {}", e);
-                        }
+            }
+
+            // Stop starting tasks while a spending budget is exhausted
+            match self.budget_pause().await {
+                Ok(Some(pause)) => {
+                    warn!("{}, pausing scheduler until {:?}", pause.reason, pause.until);
+                    paused_until = Some(pause.until);
+                    if let Err(e) = self.pause_sender.send(Some(pause)) {
+                        error!("Failed to send pause signal: {}", e);
                     }
+                    continue;
                 }
+                Ok(None) => {}
+                Err(e) => error!("Error checking budgets: {}", e),
+            }
+
+            // Tasks claimed now would only go back to waiting
+            if self.profiles.all_limited_until().is_some() {
+                continue;
+            }
+
+            if let Err(e) = self.schedule_ready_tasks().await {
+                error!("Error during task scheduling: {}", e);
             }
         }
     }
 
-    async fn schedule_ready_tasks(&self) -> Result<()> {
-        // Use the new atomic method to get and claim the next task
-        match self.db.get_and_claim_next_task().await? {
-            Some(task) => {
-                tracing::trace!("Scheduling task {} for execution: {}", task.id, task.name);
-                
-                if let Err(e) = self.task_sender.send(task.clone()).await {
-                    error!("Failed to send task {} to worker: {}", task.id, e);
-                    // If sending fails, revert task status back to pending
-                    if let Err(revert_err) = self.db.update_task_status(task.id, TaskStatus::Pending, None, None).await {
-                        error!("Failed to revert task {} status after send failure: {}", task.id, revert_err);
-                    }
+    /// Checks the rolling daily and weekly budgets. If one is exhausted, returns a
+    /// pause lasting until enough of the spend has left its window.
+    async fn budget_pause(&self) -> Result<Option<SchedulerPause>> {
+        let windows = [
+            ("Daily", chrono::Duration::days(1), self.budget.daily_usd),
+            ("Weekly", chrono::Duration::days(7), self.budget.weekly_usd),
+        ];
+
+        for (name, window, budget) in windows {
+            let Some(budget) = budget else {
+                continue;
+            };
+
+            let now = Utc::now();
+            let costs = self.db.get_costs_since((now - window).naive_utc()).await?;
+            let spent: f64 = costs.iter().map(|(_, cost)| cost).sum();
+            if spent < budget {
+                continue;
+            }
+
+            // Spend drops below the budget once the oldest invocations leave the window
+            let mut remaining = spent;
+            let mut until = now + window;
+            for (finished_at, cost) in &costs {
+                remaining -= cost;
+                if remaining < budget {
+                    until = finished_at.and_utc() + window;
+                    break;
                 }
             }
-            None => {
-                // No tasks ready to schedule, which is normal
-                tracing::trace!("No tasks ready for scheduling");
+
+            return Ok(Some(SchedulerPause {
+                until,
+                reason: format!(
+                    "{name} budget of ${budget:.2} exhausted (${spent:.2} spent in the last {} hours)",
+                    window.num_hours()
+                ),
+            }));
+        }
+
+        Ok(None)
+    }
+
+    /// Denies permission requests nobody answered within the approval timeout.
+    async fn expire_stale_approvals(&self) -> Result<()> {
+        let cutoff = Utc::now().naive_utc() - chrono::Duration::seconds(self.approval_timeout_secs as i64);
+        for id in self.db.get_stale_approvals(cutoff).await? {
+            info!("Approval {} timed out, denying it", id);
+            let message = format!("No answer within {}s", self.approval_timeout_secs);
+            if let Err(e) = self.db.decide_approval(id, ApprovalStatus::Expired, Some(&message)).await {
+                // It may have been answered in the meantime
+                warn!("Failed to expire approval {}: {}", id, e);
             }
         }
 
         Ok(())
     }
 
-    async fn convert_running_to_waiting(&self, resume_time: DateTime<Utc>) -> Result<()> {
-        let running_tasks = self.db.get_tasks_by_status(TaskStatus::Running).await?;
-        
-        for task in running_tasks {
-            info!("Converting running task {} to waiting due to rate limit", task.id);
-            self.db.update_task_status_with_resume_at(
-                task.id,
-                TaskStatus::Waiting,
-                task.session_id.as_deref(),
-                None,
-                Some(resume_time.naive_utc()),
-            ).await?;
+    /// Blocks or skips tasks whose dependencies can no longer all be met, and
+    /// re-opens blocked tasks once the failed task has been re-run.
+    async fn update_dependent_tasks(&self) -> Result<()> {
+        let updates = self.db.update_dependent_tasks().await?;
+        if !updates.blocked.is_empty() {
+            info!("Blocked tasks {:?} after a dependency failed", updates.blocked);
         }
-        
+        if !updates.skipped.is_empty() {
+            info!("Skipped tasks {:?} whose dependency conditions cannot be met", updates.skipped);
+        }
+        if !updates.reopened.is_empty() {
+            info!("Re-opened blocked tasks {:?}", updates.reopened);
+        }
+
+        Ok(())
+    }
+
+    async fn schedule_ready_tasks(&self) -> Result<()> {
+        // Keep claiming until the pool is saturated or nothing is ready
+        while let Some(task) = self.db.get_and_claim_next_task(self.max_concurrency).await? {
+            tracing::trace!("Scheduling task {} for execution: {}", task.id, task.name);
+            
+            if let Err(e) = self.task_sender.send(task.clone()).await {
+                error!("Failed to send task {} to worker: {}", task.id, e);
+                // If sending fails, revert task status back to pending
+                if let Err(revert_err) = self.db.update_task_status(task.id, TaskStatus::Pending, None, None).await {
+                    error!("Failed to revert task {} status after send failure: {}", task.id, revert_err);
+                }
+                break;
+            }
+        }
+
         Ok(())
     }
-    
+
     async fn resume_waiting_tasks(&self) -> Result<()> {
         let waiting_tasks = self.db.get_waiting_tasks_ready_for_resume().await?;
         
@@ -174,4 +316,13 @@ impl Scheduler {
     pub fn get_db(&self) -> Arc<Database> {
         self.db.clone()
     }
+
+    pub fn handle(&self) -> SchedulerHandle {
+        SchedulerHandle {
+            db: self.db.clone(),
+            active_tasks: self.active_tasks.clone(),
+            profiles: self.profiles.clone(),
+            pause_receiver: self.pause_sender.subscribe(),
+        }
+    }
 }
\ No newline at end of file
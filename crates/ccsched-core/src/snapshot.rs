@@ -0,0 +1,88 @@
+use crate::error::Result;
+use crate::worktree::{git, git_with_env};
+
+/// Identity snapshot commits are created with, so they work without a git
+/// identity configured on the server.
+const SNAPSHOT_IDENTITY: &[(&str, &str)] = &[
+    ("GIT_AUTHOR_NAME", "ccsched"),
+    ("GIT_AUTHOR_EMAIL", "ccsched@localhost"),
+    ("GIT_COMMITTER_NAME", "ccsched"),
+    ("GIT_COMMITTER_EMAIL", "ccsched@localhost"),
+];
+
+/// Ref a snapshot of a task is kept under, so it is not garbage collected.
+pub fn snapshot_ref(task_id: i64, name: &str) -> String {
+    format!("refs/ccsched/task-{task_id}/{name}")
+}
+
+/// Deletes the refs keeping a task's snapshots, so git can collect them.
+pub async fn remove_refs(root: &str, task_id: i64) -> Result<()> {
+    for name in ["before", "after"] {
+        git(root, &["update-ref", "-d", &snapshot_ref(task_id, name)]).await?;
+    }
+    Ok(())
+}
+
+/// IDs of the tasks that have snapshot refs in the repository at `root`.
+pub async fn task_ids(root: &str) -> Result<Vec<i64>> {
+    let refs = git(root, &["for-each-ref", "--format=%(refname)", "refs/ccsched/"]).await?;
+    let mut ids: Vec<i64> = refs
+        .lines()
+        .filter_map(|name| name.strip_prefix("refs/ccsched/task-")?.split('/').next()?.parse().ok())
+        .collect();
+    ids.dedup();
+    Ok(ids)
+}
+
+/// Root of the git working tree containing `dir`, if there is one.
+pub async fn repo_root(dir: &str) -> Result<Option<String>> {
+    if git(dir, &["rev-parse", "--is-inside-work-tree"]).await.is_err() {
+        return Ok(None);
+    }
+    Ok(Some(git(dir, &["rev-parse", "--show-toplevel"]).await?))
+}
+
+/// Snapshots the repository containing `dir` and stores it under `ref_name`.
+///
+/// The snapshot is a commit of the whole working tree, including untracked
+/// files that are not ignored, whose parent is the current HEAD. Neither the
+/// working tree nor the index are touched. Returns `None` if `dir` is not in a
+/// git repository.
+pub async fn take(dir: &str, ref_name: &str, message: &str) -> Result<Option<String>> {
+    let Some(root) = repo_root(dir).await? else {
+        return Ok(None);
+    };
+    let head = git(&root, &["rev-parse", "--verify", "--quiet", "HEAD"]).await.ok();
+
+    // Stage everything into a scratch copy of the index, starting from the real
+    // one so unchanged files need not be hashed again
+    let index = git(&root, &["rev-parse", "--path-format=absolute", "--git-path", "index"]).await?;
+    let scratch_index = format!("{index}.ccsched-{}", uuid::Uuid::new_v4());
+    if std::path::Path::new(&index).exists() {
+        std::fs::copy(&index, &scratch_index)?;
+    }
+    let tree = async {
+        let env = [("GIT_INDEX_FILE", scratch_index.as_str())];
+        git_with_env(&root, &["add", "-A", "."], &env).await?;
+        git_with_env(&root, &["write-tree"], &env).await
+    }
+    .await;
+    let _ = std::fs::remove_file(&scratch_index);
+    let tree = tree?;
+
+    let mut args = vec!["commit-tree", tree.as_str(), "-m", message];
+    if let Some(head) = &head {
+        args.extend(["-p", head.as_str()]);
+    }
+    let commit = git_with_env(&root, &args, SNAPSHOT_IDENTITY).await?;
+    git(&root, &["update-ref", ref_name, &commit]).await?;
+
+    Ok(Some(commit))
+}
+
+/// The `git diff --stat` summary and the patch between two snapshots.
+pub async fn diff(dir: &str, from: &str, to: &str) -> Result<(String, String)> {
+    let stat = git(dir, &["diff", "--no-color", "--no-ext-diff", "--stat", from, to]).await?;
+    let patch = git(dir, &["diff", "--no-color", "--no-ext-diff", from, to]).await?;
+    Ok((stat, patch))
+}
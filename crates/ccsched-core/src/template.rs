@@ -0,0 +1,204 @@
+use crate::error::{CcschedError, Result};
+use crate::models::Task;
+use std::collections::BTreeMap;
+
+/// Namespaces of the placeholders filled in by `render`. Other `{{...}}` text,
+/// such as `{{prompt}}` in verification templates, is left alone.
+const NAMESPACES: &[&str] = &["deps.", "task.", "vars."];
+
+/// Values a task's prompt placeholders are filled in from.
+pub struct TemplateContext<'a> {
+    pub task: &'a Task,
+    /// The task's dependencies by ID, as they are when it starts
+    pub dependencies: BTreeMap<i64, Task>,
+}
+
+impl TemplateContext<'_> {
+    fn resolve(&self, name: &str) -> std::result::Result<String, String> {
+        if let Some(key) = name.strip_prefix("vars.") {
+            return self
+                .task
+                .vars
+                .get(key)
+                .cloned()
+                .ok_or_else(|| format!("variable '{key}' was not set with --var"));
+        }
+
+        if let Some(field) = name.strip_prefix("task.") {
+            return match field {
+                "id" => Ok(self.task.id.to_string()),
+                "name" => Ok(self.task.name.clone()),
+                "cwd" => Ok(self.task.cwd.clone()),
+                _ => Err(format!("unknown task field '{field}', expected one of: id, name, cwd")),
+            };
+        }
+
+        let reference = name.strip_prefix("deps.").unwrap_or(name);
+        let Some((id, field)) = reference.split_once('.') else {
+            return Err("expected deps.<id>.result or deps.<id>.session_id".to_string());
+        };
+        let id: i64 = id.parse().map_err(|_| format!("invalid task ID '{id}'"))?;
+        let Some(dependency) = self.dependencies.get(&id) else {
+            return Err(format!("task {id} is not a dependency of this task"));
+        };
+        match field {
+            "result" => dependency.result.clone().ok_or_else(|| format!("task {id} has no result")),
+            "session_id" => dependency.session_id.clone().ok_or_else(|| format!("task {id} has no session")),
+            _ => Err(format!("unknown dependency field '{field}', expected result or session_id")),
+        }
+    }
+}
+
+/// Fills in the `{{deps.<id>.result}}`, `{{deps.<id>.session_id}}`,
+/// `{{task.<field>}}` and `{{vars.<key>}}` placeholders of `template`.
+/// Substituted values are not expanded again. Fails with every placeholder
+/// that has no value.
+pub fn render(template: &str, context: &TemplateContext) -> Result<String> {
+    let mut rendered = String::with_capacity(template.len());
+    let mut errors: Vec<String> = Vec::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        let Some(length) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        let end = start + 2 + length + 2;
+        let name = rest[start + 2..end - 2].trim();
+        rendered.push_str(&rest[..start]);
+
+        if NAMESPACES.iter().any(|namespace| name.starts_with(namespace)) {
+            match context.resolve(name) {
+                Ok(value) => rendered.push_str(&value),
+                Err(e) => {
+                    let error = format!("{{{{{name}}}}}: {e}");
+                    if !errors.contains(&error) {
+                        errors.push(error);
+                    }
+                }
+            }
+        } else {
+            rendered.push_str(&rest[start..end]);
+        }
+        rest = &rest[end..];
+    }
+    rendered.push_str(rest);
+
+    if !errors.is_empty() {
+        return Err(CcschedError::Template(errors.join("; ")));
+    }
+    Ok(rendered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{ClaudeOptions, Hooks, PermissionMode, TaskStatus, Usage};
+
+    fn task(id: i64, name: &str, vars: &[(&str, &str)]) -> Task {
+        Task {
+            id,
+            name: name.to_string(),
+            prompt: String::new(),
+            cwd: "/repo".to_string(),
+            status: TaskStatus::Pending,
+            session_id: None,
+            submitted_at: chrono::NaiveDateTime::default(),
+            finished_at: None,
+            output: None,
+            result: None,
+            resume_at: None,
+            timeout_secs: None,
+            idle_timeout_secs: None,
+            failure_reason: None,
+            executor: "claude".to_string(),
+            verify: false,
+            verification_template: None,
+            success_marker: None,
+            failure_marker: None,
+            max_verification_rounds: None,
+            verification_rounds: 0,
+            verify_cmd: None,
+            check_output: None,
+            failure_kind: None,
+            attempt: 1,
+            max_attempts: None,
+            retry_backoff_secs: None,
+            retry_on: None,
+            retry_at: None,
+            usage: Usage::default(),
+            max_cost_usd: None,
+            profile: None,
+            claude_options: ClaudeOptions::default(),
+            permission_mode: PermissionMode::DenyByDefault,
+            hooks: Hooks::default(),
+            worktree: false,
+            base_ref: None,
+            worktree_path: None,
+            branch: None,
+            base_commit: None,
+            final_commit: None,
+            blocked_by: None,
+            run_id: None,
+            vars: vars.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect(),
+            rendered_prompt: None,
+            continue_from: None,
+            fork_session: false,
+            parent_session_id: None,
+            run_time_ms: 0,
+        }
+    }
+
+    fn render_with_vars(template: &str, vars: &[(&str, &str)]) -> Result<String> {
+        let task = task(1, "build", vars);
+        render(template, &TemplateContext { task: &task, dependencies: BTreeMap::new() })
+    }
+
+    #[test]
+    fn fills_in_task_fields_vars_and_dependencies() {
+        let mut dependency = task(3, "plan", &[]);
+        dependency.result = Some("the plan".to_string());
+        dependency.session_id = Some("session-1".to_string());
+        let task = task(5, "build", &[("branch", "main")]);
+        let template = format!(
+            "{{{{task.name}}}} #{{{{ task.id }}}} in {{{{task.cwd}}}} on {{{{vars.branch}}}}: {{{{deps.{id}.result}}}} ({{{{deps.{id}.session_id}}}})",
+            id = dependency.id
+        );
+        let expected = format!("build #{} in /repo on main: the plan (session-1)", task.id);
+
+        let context = TemplateContext {
+            task: &task,
+            dependencies: BTreeMap::from([(dependency.id, dependency)]),
+        };
+        let rendered = render(&template, &context).unwrap();
+
+        assert_eq!(rendered, expected);
+    }
+
+    #[test]
+    fn reports_every_missing_value_once() {
+        let error = render_with_vars("{{vars.branch}} {{vars.branch}} {{deps.7.result}}", &[]).unwrap_err();
+
+        let CcschedError::Template(message) = error else {
+            panic!("expected a template error, got {error:?}");
+        };
+        assert_eq!(
+            message,
+            "{{vars.branch}}: variable 'branch' was not set with --var; {{deps.7.result}}: task 7 is not a dependency of this task"
+        );
+    }
+
+    #[test]
+    fn leaves_placeholders_outside_its_namespaces_alone() {
+        let rendered =
+            render_with_vars("{{prompt}}\nReply {{ success_marker }} or {{vars.marker}}", &[("marker", "DONE")]).unwrap();
+
+        assert_eq!(rendered, "{{prompt}}\nReply {{ success_marker }} or DONE");
+    }
+
+    #[test]
+    fn keeps_unterminated_placeholders() {
+        let rendered = render_with_vars("{{vars.branch}} then {{vars.branch", &[("branch", "main")]).unwrap();
+
+        assert_eq!(rendered, "main then {{vars.branch");
+    }
+}
@@ -1,39 +1,76 @@
-use crate::config::Config;
+use crate::checkpoint;
+use crate::config::{Config, VerificationConfig};
 use crate::db::Database;
 use crate::error::{CcschedError, Result};
-use crate::models::{Task, TaskStatus};
+use crate::executor::{Executor, Executors};
+use crate::models::{AttemptKind, CheckpointKind, HookKind, SchedulerPause, Task, TaskStatus, Usage};
+use crate::profile::{ProfileLease, Profiles};
+use crate::snapshot;
+use crate::template::{self, TemplateContext};
+use crate::worktree;
 use chrono::{DateTime, Utc};
-use serde_json::{json, Value};
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
 use std::process::Stdio;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
 use tokio::fs::OpenOptions;
-use tokio::process::Command;
+use tokio::process::{Child, Command};
 use tokio::sync::{mpsc, watch};
 use tracing::{debug, error, info, warn};
 
+/// How long a terminated Claude process group gets to exit before it is killed.
+const KILL_GRACE_PERIOD: Duration = Duration::from_secs(10);
+
+/// How long a hook may run before it is killed and counts as failed.
+const HOOK_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Largest amount of verification command output sent back to Claude.
+const MAX_CHECK_FEEDBACK_BYTES: usize = 20_000;
+
+/// Shared receiving end of the scheduler's task channel; every worker in the
+/// pool pulls from the same queue.
+pub type TaskReceiver = Arc<tokio::sync::Mutex<mpsc::Receiver<Task>>>;
+
+/// Tasks currently being executed by a worker in the pool, keyed by task ID.
+/// Sending `true` on a task's channel cancels it.
+pub type ActiveTasks = Arc<Mutex<HashMap<i64, watch::Sender<bool>>>>;
+
 pub struct Worker {
+    id: usize,
     db: Database,
     config: Config,
-    rate_limit_sender: mpsc::Sender<DateTime<Utc>>,
+    executors: Executors,
+    profiles: Profiles,
+    active_tasks: ActiveTasks,
 }
 
 impl Worker {
-    pub fn new(db: Database, config: Config, rate_limit_sender: mpsc::Sender<DateTime<Utc>>) -> Self {
-        Self { db, config, rate_limit_sender }
+    pub fn new(
+        id: usize,
+        db: Database,
+        config: Config,
+        executors: Executors,
+        profiles: Profiles,
+        active_tasks: ActiveTasks,
+    ) -> Self {
+        Self { id, db, config, executors, profiles, active_tasks }
     }
 
-    pub async fn run(&self, mut task_receiver: mpsc::Receiver<Task>, mut pause_receiver: watch::Receiver<Option<DateTime<Utc>>>) {
+    pub async fn run(&self, task_receiver: TaskReceiver, mut pause_receiver: watch::Receiver<Option<SchedulerPause>>) {
         loop {
             tokio::select! {
-                task_opt = task_receiver.recv() => {
+                task_opt = async { task_receiver.lock().await.recv().await } => {
                     if let Some(task) = task_opt {
                         // Check if we're paused before starting task
-                        let current_pause = *pause_receiver.borrow();
+                        let current_pause = pause_receiver.borrow().as_ref().map(|pause| pause.until);
                         if let Some(resume_time) = current_pause {
                             let now = Utc::now();
                             if now < resume_time {
                                 // We're paused, put task back to pending
-                                warn!("Worker is paused, reverting task {} to pending", task.id);
+                                warn!("Worker {} is paused, reverting task {} to pending", self.id, task.id);
                                 if let Err(e) = self.db.update_task_status(
                                     task.id, 
                                     TaskStatus::Pending, 
@@ -47,19 +84,57 @@ impl Worker {
                         }
                         
                         let task_id = task.id;
-                        info!("Starting execution of task {}: {}", task_id, task.name);
+                        info!("Worker {} starting execution of task {}: {}", self.id, task_id, task.name);
                         debug!("Task {} details: {:?}", task_id, task);
 
-                        if let Err(e) = self.execute_task(task).await {
-                            error!("Task {} failed: {}", task_id, e);
-                            if let Err(update_err) = self
-                                .db
-                                .update_task_status(task_id, TaskStatus::Failed, None, Some(Utc::now().naive_utc()))
-                                .await
-                            {
-                                error!("Failed to update task {} status: {}", task_id, update_err);
+                        let (cancel_sender, cancel_receiver) = watch::channel(false);
+                        self.active_tasks.lock().unwrap().insert(task_id, cancel_sender);
+
+                        // The task may have been cancelled while it was queued
+                        let (result, started) = match self.db.get_task(task_id).await {
+                            Ok(current) if current.status == TaskStatus::Cancelled => {
+                                info!("Task {} was cancelled before it started", task_id);
+                                (Ok(()), false)
+                            }
+                            _ => match self.execute_task(task, cancel_receiver).await {
+                                Ok(()) => (self.commit_worktree(task_id).await, true),
+                                Err(e) => (Err(e), true),
+                            },
+                        };
+                        self.active_tasks.lock().unwrap().remove(&task_id);
+
+                        // Nobody can answer permission requests of a stopped Claude process
+                        if let Err(e) = self.db.expire_task_approvals(task_id).await {
+                            error!("Failed to expire approvals of task {}: {}", task_id, e);
+                        }
+
+                        match result {
+                            Ok(()) => {}
+                            Err(CcschedError::TaskCancelled(_)) => {
+                                info!("Task {} cancelled", task_id);
+                                if let Err(update_err) = self
+                                    .db
+                                    .update_task_status(task_id, TaskStatus::Cancelled, None, Some(Utc::now().naive_utc()))
+                                    .await
+                                {
+                                    error!("Failed to update task {} status: {}", task_id, update_err);
+                                }
+                            }
+                            Err(e) => {
+                                error!("Task {} failed: {}", task_id, e);
+                                if let Err(update_err) = self.handle_failure(task_id, &e).await {
+                                    error!("Failed to update task {} status: {}", task_id, update_err);
+                                }
                             }
                         }
+
+                        if started {
+                            self.run_post_hooks(task_id).await;
+                        }
+
+                        if let Err(e) = self.record_changes(task_id).await {
+                            warn!("Failed to record the changes of task {}: {}", task_id, e);
+                        }
                     } else {
                         // Channel closed, exit
                         break;
@@ -73,38 +148,422 @@ impl Worker {
         }
     }
 
-    async fn execute_task(&self, task: Task) -> Result<()> {
+    /// Marks the task failed, or re-queues it for another attempt if its retry
+    /// policy covers this kind of failure. A failed run is still `running` until
+    /// here, so dependents never see a task as failed that is retried.
+    async fn handle_failure(&self, task_id: i64, error: &CcschedError) -> Result<()> {
+        let task = self.db.get_task(task_id).await?;
+        let kind = error.failure_kind();
+        let reason = error.to_string();
+
+        let retry = &self.config.retry;
+        let max_attempts = task.max_attempts.unwrap_or(retry.max_attempts);
+        let retryable = match &task.retry_on {
+            Some(kinds) => kinds.contains(&kind),
+            None => retry.retry_on.contains(&kind),
+        };
+
+        // The task may have been cancelled or deleted in the meantime
+        if !retryable || task.attempt >= max_attempts || task.status == TaskStatus::Cancelled {
+            return self.db.mark_task_failed(task_id, &reason, kind).await;
+        }
+
+        let delay = retry.backoff(task.retry_backoff_secs.unwrap_or(retry.backoff_secs), task.attempt);
+        let retry_at = Utc::now().naive_utc() + chrono::Duration::seconds(delay as i64);
+        info!(
+            "Retrying task {} ({} failure) as attempt {} of {} in {}s",
+            task_id,
+            kind,
+            task.attempt + 1,
+            max_attempts,
+            delay
+        );
+        self.db
+            .schedule_task_retry(task_id, task.attempt + 1, retry_at, &reason, kind)
+            .await
+    }
+
+    /// Points the task at its worktree, creating the worktree and branch on the
+    /// first run. Later attempts continue in the same worktree.
+    async fn enter_worktree(&self, mut task: Task) -> Result<Task> {
+        let worktree_path = match &task.worktree_path {
+            Some(path) if std::path::Path::new(path).exists() => path.clone(),
+            _ => {
+                let base_ref = task.base_ref.as_deref().unwrap_or("HEAD");
+                let created = worktree::create(&task.cwd, self.config.worktree_dir.as_ref(), task.id, base_ref).await?;
+                info!("Created worktree {} on branch {} for task {}", created.path, created.branch, task.id);
+                self.db
+                    .update_task_worktree(task.id, &created.path, &created.branch, &created.base_commit)
+                    .await?;
+                created.path
+            }
+        };
+
+        task.cwd = worktree::working_dir(&task.cwd, &worktree_path).await?;
+        Ok(task)
+    }
+
+    /// Commits what a successful task left in its worktree to the task's branch.
+    async fn commit_worktree(&self, task_id: i64) -> Result<()> {
+        let task = self.db.get_task(task_id).await?;
+        let Some(path) = task.worktree_path.as_deref() else {
+            return Ok(());
+        };
+        if task.status != TaskStatus::Done {
+            return Ok(());
+        }
+
+        let commit = worktree::commit_all(path, &format!("ccsched task {}: {}", task.id, task.name)).await?;
+        info!("Task {} finished at commit {} on branch {}", task_id, commit, task.branch.unwrap_or_default());
+        self.db.update_task_final_commit(task_id, &commit).await
+    }
+
+    /// Runs the task's and then the server's hooks for how the attempt ended.
+    /// Failures are logged but do not change the task's status.
+    async fn run_post_hooks(&self, task_id: i64) {
+        let task = match self.db.get_task(task_id).await {
+            Ok(task) => task,
+            Err(e) => {
+                warn!("Failed to load task {} for its hooks: {}", task_id, e);
+                return;
+            }
+        };
+        let kinds = match task.status {
+            TaskStatus::Done => [HookKind::PostSuccess, HookKind::PostAlways],
+            TaskStatus::Failed | TaskStatus::Cancelled | TaskStatus::Pending => {
+                [HookKind::PostFailure, HookKind::PostAlways]
+            }
+            // Waiting for a rate limit to reset; the attempt goes on later
+            _ => return,
+        };
+
+        let cwd = match &task.worktree_path {
+            Some(path) => worktree::working_dir(&task.cwd, path).await.unwrap_or_else(|_| path.clone()),
+            None => task.cwd.clone(),
+        };
+        for kind in kinds {
+            for command in [task.hooks.get(kind), self.config.hooks.get(kind)].into_iter().flatten() {
+                if let Err(e) = self.run_hook(&task, &cwd, kind, command).await {
+                    warn!("Task {}: {}", task_id, e);
+                }
+            }
+        }
+    }
+
+    /// Runs a hook command with the task's metadata in its environment and
+    /// appends the outcome and output to the task log.
+    async fn run_hook(&self, task: &Task, cwd: &str, kind: HookKind, command: &str) -> Result<()> {
+        info!("Running {} hook of task {}: {}", kind, task.id, command);
+        let log_path = format!("./logs/task_{}.jsonl", task.id);
+
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c")
+            .arg(command)
+            .current_dir(cwd)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .envs(&self.config.env_vars)
+            .env("CCSCHED_HOOK", kind.to_string())
+            .env("CCSCHED_TASK_ID", task.id.to_string())
+            .env("CCSCHED_TASK_NAME", &task.name)
+            .env("CCSCHED_TASK_STATUS", task.status.to_string())
+            .env("CCSCHED_SESSION_ID", task.session_id.as_deref().unwrap_or_default())
+            .env("CCSCHED_TASK_LOG", absolute_path(&log_path));
+        if kind != HookKind::PreRun
+            && let Some(result) = &task.result
+        {
+            let result_path = format!("./logs/task_{}.result", task.id);
+            tokio::fs::write(&result_path, result).await?;
+            cmd.env("CCSCHED_RESULT_PATH", absolute_path(&result_path));
+        }
+
+        let (exit_code, output, failure) = match tokio::time::timeout(HOOK_TIMEOUT, cmd.output()).await {
+            Ok(output) => {
+                let output = output?;
+                let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+                text.push_str(&String::from_utf8_lossy(&output.stderr));
+                let failure = (!output.status.success())
+                    .then(|| format!("{kind} hook `{command}` failed with {}", output.status));
+                (output.status.code(), text, failure)
+            }
+            Err(_) => {
+                let failure = format!("{kind} hook `{command}` timed out after {}s", HOOK_TIMEOUT.as_secs());
+                (None, String::new(), Some(failure))
+            }
+        };
+
+        let event = json!({
+            "type": "ccsched_hook",
+            "hook": kind,
+            "command": command,
+            "exit_code": exit_code,
+            "output": output,
+        });
+        match OpenOptions::new().create(true).append(true).open(&log_path).await {
+            Ok(mut log_file) => {
+                if let Err(e) = log_file.write_all(format!("{event}\n").as_bytes()).await {
+                    warn!("Failed to write to task log: {}", e);
+                }
+            }
+            Err(e) => warn!("Failed to open task log {}: {}", log_path, e),
+        }
+
+        match failure {
+            Some(failure) => Err(CcschedError::HookFailed(failure)),
+            None => Ok(()),
+        }
+    }
+
+    /// Checkpoints the task's working directory before its first run, so its
+    /// diff and a rollback cover every attempt. Repositories get a snapshot
+    /// commit, other directories a tarball.
+    async fn checkpoint(&self, task: &Task) -> Result<()> {
+        if self.db.get_checkpoint(task.id).await?.is_some() {
+            return Ok(());
+        }
+
+        if let Some(root) = snapshot::repo_root(&task.cwd).await? {
+            let message = format!("ccsched snapshot before task {}", task.id);
+            if let Some(snapshot) = snapshot::take(&root, &snapshot::snapshot_ref(task.id, "before"), &message).await? {
+                self.db.create_task_diff(task.id, &snapshot).await?;
+                self.db
+                    .create_checkpoint(task.id, CheckpointKind::Git, &root, &snapshot, None)
+                    .await?;
+            }
+            return Ok(());
+        }
+
+        if self.config.checkpoint_max_bytes == 0 {
+            return Ok(());
+        }
+        let root = std::fs::canonicalize(&task.cwd)?.to_string_lossy().to_string();
+        let Some(manifest) = checkpoint::manifest(&root, self.config.checkpoint_max_bytes)? else {
+            warn!("Working directory {} of task {} is too large to checkpoint", root, task.id);
+            return Ok(());
+        };
+        let tarball = checkpoint::tarball_path(task.id);
+        checkpoint::create_tarball(&root, &tarball).await?;
+        self.db
+            .create_checkpoint(task.id, CheckpointKind::Tarball, &root, &tarball, Some(&serde_json::to_string(&manifest)?))
+            .await
+    }
+
+    /// Records what the task changed since its checkpoint once it stopped: the
+    /// changed files, and for repositories a snapshot and the diff.
+    async fn record_changes(&self, task_id: i64) -> Result<()> {
+        let Some(checkpoint) = self.db.get_checkpoint(task_id).await? else {
+            return Ok(());
+        };
+        let root = &checkpoint.root;
+
+        let changed_files = match checkpoint.kind {
+            CheckpointKind::Git => {
+                let message = format!("ccsched snapshot after task {task_id}");
+                let Some(after) = snapshot::take(root, &snapshot::snapshot_ref(task_id, "after"), &message).await? else {
+                    return Ok(());
+                };
+                let (stat, patch) = snapshot::diff(root, &checkpoint.location, &after).await?;
+                self.db.update_task_diff(task_id, &after, &stat, &patch).await?;
+                checkpoint::git_changes(root, &checkpoint.location, &after).await?
+            }
+            CheckpointKind::Tarball => {
+                let before: checkpoint::Manifest = match self.db.get_checkpoint_manifest(task_id).await? {
+                    Some(manifest) => serde_json::from_str(&manifest)?,
+                    None => return Ok(()),
+                };
+                let after = checkpoint::manifest(root, u64::MAX)?.unwrap_or_default();
+                checkpoint::manifest_changes(&before, &after)
+            }
+        };
+
+        self.db.update_checkpoint_changes(task_id, &changed_files).await
+    }
+
+    /// Looks up the session a task continuing another one starts from: the
+    /// parent's latest session when the attempt starts. The task runs under
+    /// the parent's profile, which holds that session.
+    async fn resolve_parent_session(&self, mut task: Task) -> Result<Task> {
+        let Some(parent_id) = task.continue_from else {
+            return Ok(task);
+        };
+        if task.session_id.is_some() {
+            return Ok(task);
+        }
+
+        let parent = self.db.get_task(parent_id).await.map_err(|e| {
+            CcschedError::ClaudeExecution(format!("Failed to load task {parent_id} to continue from: {e}"))
+        })?;
+        let Some(session_id) = parent.session_id else {
+            return Err(CcschedError::ClaudeExecution(format!(
+                "Task {parent_id} has no session to continue from"
+            )));
+        };
+        info!("Task {} continues session {} of task {}", task.id, session_id, parent_id);
+        self.db.update_task_parent_session(task.id, &session_id).await?;
+        task.parent_session_id = Some(session_id);
+        if parent.profile.is_some() {
+            task.profile = parent.profile;
+        }
+        Ok(task)
+    }
+
+    /// The profile a task has to run under because it holds the session the task
+    /// resumes or continues. A session whose profile is no longer configured
+    /// cannot be resumed, so the task starts a new one instead.
+    async fn session_profile(&self, mut task: Task) -> Result<(Task, Option<String>)> {
+        if task.session_id.is_none() && task.parent_session_id.is_none() {
+            return Ok((task, None));
+        }
+
+        match task.profile.clone() {
+            Some(name) if self.profiles.contains(&name) => Ok((task, Some(name))),
+            profile => {
+                warn!(
+                    "Profile {} holding the session of task {} is not configured, starting a new session",
+                    profile.as_deref().unwrap_or("(none)"),
+                    task.id
+                );
+                self.db.clear_task_session(task.id).await?;
+                task.session_id = None;
+                task.parent_session_id = None;
+                Ok((task, None))
+            }
+        }
+    }
+
+    /// Fills in the placeholders of the task's prompt from its dependencies,
+    /// which have finished by now, and records the prompt that is sent. A task
+    /// coming back from waiting on a rate limit resumes its session with the
+    /// prompt it started with; editing the prompt clears the recorded one.
+    async fn render_prompt(&self, mut task: Task) -> Result<Task> {
+        if task.session_id.is_some()
+            && task.resume_at.is_some()
+            && let Some(rendered) = task.rendered_prompt.take()
+        {
+            task.prompt = rendered;
+            return Ok(task);
+        }
+
+        let mut dependencies = BTreeMap::new();
+        for dependency in self.db.get_task_dependencies(task.id).await? {
+            dependencies.insert(dependency.id, self.db.get_task(dependency.id).await?);
+        }
+
+        let rendered = template::render(&task.prompt, &TemplateContext { task: &task, dependencies })?;
+        self.db.update_task_rendered_prompt(task.id, &rendered).await?;
+        task.prompt = rendered.clone();
+        task.rendered_prompt = Some(rendered);
+        Ok(task)
+    }
+
+    async fn execute_task(&self, task: Task, cancel: watch::Receiver<bool>) -> Result<()> {
         let task_id = task.id;
         
         // Task is already marked as running by the scheduler
-        
-        let task_log_path = format!("./logs/task_{task_id}.jsonl");
-        // Remove logs directory creation since we're writing to current directory
 
-        let initial_result = self.run_claude_initial(&task, &task_log_path, task_id).await?;
+        // The ceiling may have been lowered since the task was submitted
+        self.config.permissions.check(task.permission_mode)?;
 
-        // Check for rate limit in initial result
-        if let Some(timestamp) = initial_result.rate_limit_timestamp {
-            let resume_at_utc = DateTime::from_timestamp(timestamp, 0)
-                .unwrap_or_else(|| Utc::now() + chrono::Duration::hours(1));
-            let resume_at = resume_at_utc.naive_utc();
-            
-            info!("Task {} hit rate limit, will resume at {:?}", task_id, resume_at);
-            
-            // Send global rate limit signal to scheduler
-            if let Err(e) = self.rate_limit_sender.send(resume_at_utc).await {
-                error!("Failed to send rate limit signal to scheduler: {}", e);
+        let task = self.resolve_parent_session(task).await?;
+        let (task, session_profile) = self.session_profile(task).await?;
+
+        let Some(profile) = self.profiles.acquire(session_profile.as_deref()) else {
+            let resume_at = match &session_profile {
+                Some(name) => self.profiles.limited_until(name),
+                None => self.profiles.all_limited_until(),
             }
-            
-            self.db
+            .unwrap_or_else(Utc::now);
+            info!("No profile available for task {}, it will resume at {:?}", task_id, resume_at);
+            return self
+                .db
                 .update_task_status_with_resume_at(
                     task_id,
                     TaskStatus::Waiting,
-                    initial_result.session_id.as_deref(),
+                    task.session_id.as_deref(),
                     None,
-                    Some(resume_at),
+                    Some(resume_at.naive_utc()),
                 )
+                .await;
+        };
+        info!("Running task {} under profile {}", task_id, profile.name());
+        self.db.update_task_profile(task_id, profile.name()).await?;
+
+        let task = if task.worktree { self.enter_worktree(task).await? } else { task };
+        let task = self.render_prompt(task).await?;
+
+        // A task resuming its session after a rate limit is still in the same run
+        if task.session_id.is_none() {
+            for command in [&self.config.hooks.pre_run, &task.hooks.pre_run].into_iter().flatten() {
+                self.run_hook(&task, &task.cwd, HookKind::PreRun, command).await?;
+            }
+        }
+
+        if let Err(e) = self.checkpoint(&task).await {
+            warn!("Failed to checkpoint the working directory of task {}: {}", task_id, e);
+        }
+
+        let run = TaskRun {
+            task_id,
+            executor: self.executors.get(&task.executor)?,
+            profile,
+            log_path: format!("./logs/task_{task_id}.jsonl"),
+            cancel,
+            started_at: Instant::now(),
+            previous_run_time: Duration::from_millis(task.run_time_ms),
+            timeout_secs: task.timeout_secs.or(self.config.task_timeout),
+            idle_timeout_secs: task.idle_timeout_secs.or(self.config.idle_timeout),
+        };
+        // Remove logs directory creation since we're writing to current directory
+
+        let verification = Verification::for_task(&task, &self.config.verification);
+
+        let initial_result = self.run_initial(&task, &run).await?;
+
+        // Check for rate limit in initial result
+        if let Some(timestamp) = initial_result.rate_limit_timestamp {
+            info!("Task {} hit rate limit", task_id);
+            return self
+                .wait_for_rate_limit(&run, timestamp, initial_result.session_id.as_deref())
+                .await;
+        }
+
+        if !run.executor.supports_sessions() {
+            // Nothing to verify against without a session, the single pass decides
+            let result = extract_work_result(&initial_result.output, &verification);
+            let mut failure = (!initial_result.success).then(|| {
+                CcschedError::ClaudeExecution(format!("{} executor reported failure", run.executor.name()))
+            });
+
+            if failure.is_none()
+                && task.verify
+                && let Some(command) = &task.verify_cmd
+            {
+                self.db.update_task_verification_rounds(task_id, 1).await?;
+                let check = self.run_check(&task, command, &run).await?;
+                self.db
+                    .update_task_check_output(task_id, &check.section(1))
+                    .await?;
+                if !check.success {
+                    failure = Some(CcschedError::VerificationFailed(format!(
+                        "Verification command failed with {}",
+                        check.exit_description()
+                    )));
+                }
+            }
+
+            self.db
+                .update_task_output_and_result(task_id, Some(&initial_result.output), result.as_deref())
+                .await?;
+
+            if let Some(failure) = failure {
+                return Err(failure);
+            }
+            self.db
+                .update_task_status(task_id, TaskStatus::Done, None, Some(Utc::now().naive_utc()))
                 .await?;
+            info!("Task {} completed successfully", task_id);
             return Ok(());
         }
 
@@ -122,81 +581,67 @@ impl Worker {
             .await?;
 
         if !initial_result.success {
-            self.db
-                .update_task_status(
-                    task_id,
-                    TaskStatus::Failed,
-                    None,
-                    Some(Utc::now().naive_utc()),
-                )
-                .await?;
             return Err(CcschedError::ClaudeExecution(
                 "Initial Claude execution failed".to_string(),
             ));
         }
 
-        let verification_prompt = format!(
-            "{}\n\n如果你确认任务成功，能够正确完成用户的每一个需求，则回复 CLAUDE_CODE_SCHEDULER_SUCCESS；如果其中有的需求没有完成，再继续进行任务；如果你确认因为某些原因，在没有用户干预的情况下无法完成任务，则回复 CLAUDE_CODE_SCHEDULER_FAILED",
-            task.prompt
-        );
+        if !task.verify {
+            info!("Task {} completed, verification disabled", task_id);
+            let result = extract_work_result(&initial_result.output, &verification);
+            self.store_task_completion(
+                task_id,
+                &session_id,
+                &initial_result.output,
+                result.as_deref(),
+            ).await?;
+            return Ok(());
+        }
+
+        if let Some(command) = &task.verify_cmd {
+            return self
+                .run_command_verification(&task, command, session_id, initial_result.output, &verification, &run)
+                .await;
+        }
+
+        let verification_prompt = verification.prompt(&task.prompt);
 
-        let mut max_retries = 3;
         let mut current_session_id = session_id;
-        let mut previous_result: Option<String> = None;
+        // The first pass did the work; verification rounds only confirm it
+        let mut previous_result = extract_work_result(&initial_result.output, &verification);
+        let mut rounds = 0;
         
         loop {
+            rounds += 1;
+            self.db.update_task_verification_rounds(task_id, rounds).await?;
+
             let verification_result = self
-                .run_claude_verification(&task, &current_session_id, &verification_prompt, &task_log_path, task_id)
+                .run_verification(&task, &current_session_id, &verification_prompt, &run)
                 .await?;
             
             // Check for rate limit in verification result
             if let Some(timestamp) = verification_result.rate_limit_timestamp {
-                let resume_at_utc = DateTime::from_timestamp(timestamp, 0)
-                    .unwrap_or_else(|| Utc::now() + chrono::Duration::hours(1));
-                let resume_at = resume_at_utc.naive_utc();
-                
-                info!("Task {} hit rate limit during verification, will resume at {:?}", task_id, resume_at);
-                
-                // Send global rate limit signal to scheduler
-                if let Err(e) = self.rate_limit_sender.send(resume_at_utc).await {
-                    error!("Failed to send rate limit signal to scheduler: {}", e);
-                }
-                
-                self.db
-                    .update_task_status_with_resume_at(
-                        task_id,
-                        TaskStatus::Waiting,
-                        Some(&current_session_id),
-                        None,
-                        Some(resume_at),
-                    )
-                    .await?;
-                return Ok(());
+                info!("Task {} hit rate limit during verification", task_id);
+                return self
+                    .wait_for_rate_limit(&run, timestamp, Some(&current_session_id))
+                    .await;
             }
             
             // Update session_id if verification returned a new one, but only if the task is not finished
-            let is_final_result = verification_result.output.contains("CLAUDE_CODE_SCHEDULER_SUCCESS") 
-                || verification_result.output.contains("CLAUDE_CODE_SCHEDULER_FAILED");
+            let is_final_result = verification_result.output.contains(verification.success_marker) 
+                || verification_result.output.contains(verification.failure_marker);
             
-            if !is_final_result {
-                if let Some(new_session_id) = &verification_result.session_id {
-                    current_session_id = new_session_id.clone();
-                    // Update database with the latest session_id
-                    self.db
-                        .update_task_status(task_id, TaskStatus::Running, Some(&current_session_id), None)
-                        .await?;
-                }
+            if !is_final_result
+                && let Some(new_session_id) = &verification_result.session_id
+            {
+                current_session_id = new_session_id.clone();
+                // Update database with the latest session_id
+                self.db
+                    .update_task_status(task_id, TaskStatus::Running, Some(&current_session_id), None)
+                    .await?;
             }
 
             if !verification_result.success {
-                self.db
-                    .update_task_status(
-                        task_id,
-                        TaskStatus::Failed,
-                        None,
-                        Some(Utc::now().naive_utc()),
-                    )
-                    .await?;
                 return Err(CcschedError::ClaudeExecution(
                     "Claude verification execution failed".to_string(),
                 ));
@@ -206,14 +651,13 @@ impl Worker {
             
             if verification_result
                 .output
-                .contains("CLAUDE_CODE_SCHEDULER_SUCCESS")
+                .contains(verification.success_marker)
             {
                 info!("Task {} completed successfully", task_id);
                 
                 // Use the previous result (not the one containing SUCCESS marker)
                 self.store_task_completion(
                     task_id,
-                    TaskStatus::Done,
                     &current_session_id,
                     &verification_result.output,
                     previous_result.as_deref(),
@@ -222,42 +666,33 @@ impl Worker {
                 return Ok(());
             } else if verification_result
                 .output
-                .contains("CLAUDE_CODE_SCHEDULER_FAILED")
+                .contains(verification.failure_marker)
             {
                 info!("Task {} failed as reported by Claude", task_id);
                 
                 // Use the previous result (not the one containing FAILED marker)
-                self.store_task_completion(
-                    task_id,
-                    TaskStatus::Failed,
-                    &current_session_id,
-                    &verification_result.output,
-                    previous_result.as_deref(),
-                ).await?;
+                self.db
+                    .update_task_output_and_result(task_id, Some(&verification_result.output), previous_result.as_deref())
+                    .await?;
                 
-                return Err(CcschedError::ClaudeExecution(
+                return Err(CcschedError::VerificationFailed(
                     "Task failed as reported by Claude".to_string(),
                 ));
             } else {
                 // This is not the final verification, save this result as the previous result
                 // Extract the actual work result from this verification run
-                previous_result = extract_work_result(&verification_result.output);
+                previous_result = extract_work_result(&verification_result.output, &verification);
             }
 
-            max_retries -= 1;
-            if max_retries <= 0 {
+            if rounds >= verification.max_rounds {
                 warn!("Task {} exceeded maximum verification retries", task_id);
                 
                 // Store the final output even when max retries exceeded
-                self.store_task_completion(
-                    task_id,
-                    TaskStatus::Failed,
-                    &current_session_id,
-                    &verification_result.output,
-                    None, // No clean result since verification failed
-                ).await?;
+                self.db
+                    .update_task_output_and_result(task_id, Some(&verification_result.output), None)
+                    .await?;
                 
-                return Err(CcschedError::ClaudeExecution(
+                return Err(CcschedError::VerificationFailed(
                     "Exceeded maximum verification retries".to_string(),
                 ));
             }
@@ -266,97 +701,322 @@ impl Worker {
         }
     }
 
-    async fn run_claude_initial(
+    /// Runs the task's verification command after each pass and feeds its output
+    /// back into the session until the command succeeds or the rounds run out.
+    async fn run_command_verification(
         &self,
         task: &Task,
-        task_log_path: &str,
-        task_id: i64,
-    ) -> Result<ClaudeResult> {
-        self.run_claude_command(task, &task.prompt, task.session_id.as_deref(), task_log_path, task_id)
+        command: &str,
+        mut session_id: String,
+        mut output: String,
+        verification: &Verification<'_>,
+        run: &TaskRun,
+    ) -> Result<()> {
+        let task_id = task.id;
+        let mut check_output = String::new();
+        let mut rounds = 0;
+
+        loop {
+            rounds += 1;
+            self.db.update_task_verification_rounds(task_id, rounds).await?;
+
+            let check = self.run_check(task, command, run).await?;
+            check_output.push_str(&check.section(rounds));
+            self.db.update_task_check_output(task_id, &check_output).await?;
+
+            let result = extract_work_result(&output, verification);
+
+            if check.success {
+                info!("Task {} passed its verification command", task_id);
+                self.store_task_completion(
+                    task_id,
+                    &session_id,
+                    &output,
+                    result.as_deref(),
+                ).await?;
+                return Ok(());
+            }
+
+            if rounds >= verification.max_rounds {
+                warn!("Task {} still fails its verification command after {} rounds", task_id, rounds);
+                self.db
+                    .update_task_output_and_result(task_id, Some(&output), result.as_deref())
+                    .await?;
+                return Err(CcschedError::VerificationFailed(format!(
+                    "Verification command failed with {} after {} rounds",
+                    check.exit_description(),
+                    rounds
+                )));
+            }
+
+            info!("Task {} failed its verification command, sending the output back", task_id);
+            let pass = self
+                .run_verification(task, &session_id, &check.feedback_prompt(command), run)
+                .await?;
+
+            if let Some(timestamp) = pass.rate_limit_timestamp {
+                info!("Task {} hit rate limit during verification", task_id);
+                return self
+                    .wait_for_rate_limit(run, timestamp, Some(&session_id))
+                    .await;
+            }
+
+            if let Some(new_session_id) = &pass.session_id {
+                session_id = new_session_id.clone();
+                self.db
+                    .update_task_status(task_id, TaskStatus::Running, Some(&session_id), None)
+                    .await?;
+            }
+
+            if !pass.success {
+                return Err(CcschedError::ClaudeExecution(
+                    "Claude verification execution failed".to_string(),
+                ));
+            }
+
+            output = pass.output;
+        }
+    }
+
+    /// Marks the task's profile as limited until its usage limit resets and puts
+    /// the task back to waiting. A task with a session resumes once its profile
+    /// is available again; one without is picked up again right away if another
+    /// profile is available, otherwise once the first limit resets.
+    async fn wait_for_rate_limit(
+        &self,
+        run: &TaskRun,
+        timestamp: i64,
+        session_id: Option<&str>,
+    ) -> Result<()> {
+        let task_id = run.task_id;
+        let limited_until = DateTime::from_timestamp(timestamp, 0)
+            .unwrap_or_else(|| Utc::now() + chrono::Duration::hours(1));
+        warn!("Profile {} rate limited until {:?}", run.profile.name(), limited_until);
+        self.profiles.mark_limited(run.profile.name(), limited_until);
+
+        let resume_at = match session_id {
+            Some(_) => Some(limited_until),
+            None => self.profiles.all_limited_until(),
+        }
+        .unwrap_or_else(Utc::now)
+        .naive_utc();
+
+        info!("Task {} will resume at {:?}", task_id, resume_at);
+
+        // The timeout covers the whole attempt, not each execution of it
+        let run_time = run.started_at.elapsed().as_millis() as u64;
+        self.db.add_task_run_time(task_id, run_time).await?;
+        self.db
+            .update_task_status_with_resume_at(
+                task_id,
+                TaskStatus::Waiting,
+                session_id,
+                None,
+                Some(resume_at),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Runs a task's verification command in its working directory. Its output is
+    /// appended to the task log as well as returned.
+    async fn run_check(&self, task: &Task, command: &str, run: &TaskRun) -> Result<CheckResult> {
+        info!("Running verification command for task {}: {}", run.task_id, command);
+
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c")
+            .arg(command)
+            .current_dir(&task.cwd)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .envs(&self.config.env_vars);
+
+        #[cfg(unix)]
+        cmd.process_group(0);
+
+        let mut child = cmd.spawn()?;
+        let mut cancel = run.cancel.clone();
+
+        let mut stdout = child.stdout.take().unwrap();
+        let mut stderr = child.stderr.take().unwrap();
+        let mut stdout_bytes = Vec::new();
+        let mut stderr_bytes = Vec::new();
+
+        let read = async {
+            tokio::try_join!(
+                stdout.read_to_end(&mut stdout_bytes),
+                stderr.read_to_end(&mut stderr_bytes),
+            )
+        };
+        tokio::select! {
+            read = read => { read?; }
+            err = run.interruption(&mut cancel, None) => {
+                info!("Stopping task {}: {}", run.task_id, err);
+                terminate_process_group(&mut child).await;
+                return Err(err);
+            }
+        }
+
+        let exit_status = tokio::select! {
+            status = child.wait() => status?,
+            err = run.interruption(&mut cancel, None) => {
+                info!("Stopping task {}: {}", run.task_id, err);
+                terminate_process_group(&mut child).await;
+                return Err(err);
+            }
+        };
+
+        let mut output = String::from_utf8_lossy(&stdout_bytes).into_owned();
+        output.push_str(&String::from_utf8_lossy(&stderr_bytes));
+
+        let mut log_file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&run.log_path)
+            .await?;
+        if let Err(e) = log_file.write_all(output.as_bytes()).await {
+            warn!("Failed to write to task log: {}", e);
+        }
+
+        Ok(CheckResult {
+            success: exit_status.success(),
+            exit_code: exit_status.code(),
+            output,
+        })
+    }
+
+    async fn run_initial(&self, task: &Task, run: &TaskRun) -> Result<InvocationResult> {
+        let kind = if task.session_id.is_some() { AttemptKind::Resume } else { AttemptKind::Initial };
+        // A task continuing another one starts in its parent's session
+        let session_id = task.session_id.as_deref().or(task.parent_session_id.as_deref());
+        self.run_executor_command(task, &task.prompt, session_id, kind, run)
             .await
     }
 
-    async fn run_claude_verification(
+    async fn run_verification(
         &self,
         task: &Task,
         session_id: &str,
         prompt: &str,
-        task_log_path: &str,
-        task_id: i64,
-    ) -> Result<ClaudeResult> {
-        self.run_claude_command(task, prompt, Some(session_id), task_log_path, task_id)
+        run: &TaskRun,
+    ) -> Result<InvocationResult> {
+        self.run_executor_command(task, prompt, Some(session_id), AttemptKind::Verification, run)
             .await
     }
 
+    /// Stores the output of a task that succeeded and marks it done. A task that
+    /// fails only has its output stored; `handle_failure` settles its status.
     async fn store_task_completion(
         &self,
         task_id: i64,
-        status: TaskStatus,
         session_id: &str,
         output: &str,
         result: Option<&str>,
     ) -> Result<()> {
-        // First update the task status and basic fields
+        // Store the output first so dependents see it as soon as the task is done
+        self.db.update_task_output_and_result(task_id, Some(output), result).await?;
         self.db
             .update_task_status(
                 task_id,
-                status,
+                TaskStatus::Done,
                 Some(session_id),
                 Some(Utc::now().naive_utc()),
             )
-            .await?;
-        
-        // Then update both output and result fields
-        self.db.update_task_output_and_result(task_id, Some(output), result).await?;
-        
-        Ok(())
+            .await
     }
 
-    async fn run_claude_command(
+    /// Runs one executor invocation and records it in the task's attempts.
+    async fn run_executor_command(
         &self,
         task: &Task,
         prompt: &str,
         session_id: Option<&str>,
-        task_log_path: &str,
-        task_id: i64,
-    ) -> Result<ClaudeResult> {
-        // Resolve claude_path to absolute path if it's relative
-        let claude_path = if std::path::Path::new(&self.config.claude_path).is_absolute() {
-            self.config.claude_path.clone()
-        } else {
-            // Resolve relative path based on current working directory
-            let current_dir = std::env::current_dir()
-                .map_err(|e| CcschedError::ClaudeExecution(format!("Failed to get current directory: {}", e)))?;
-            current_dir.join(&self.config.claude_path)
-                .to_string_lossy()
-                .to_string()
-        };
-        
-        let mut cmd = Command::new(&claude_path);
-        cmd.args([
-            "--output-format",
-            "stream-json",
-            "--verbose",
-            "--dangerously-skip-permissions",
-        ]);
+        kind: AttemptKind,
+        run: &TaskRun,
+    ) -> Result<InvocationResult> {
+        if let Some(max_cost) = task.max_cost_usd {
+            let spent = self.db.get_task_cost(task.id).await?;
+            if spent >= max_cost {
+                return Err(CcschedError::BudgetExceeded(format!(
+                    "task has cost ${spent:.4}, reaching its limit of ${max_cost:.4}"
+                )));
+            }
+        }
+
+        let attempt_id = self
+            .db
+            .create_task_attempt(task.id, task.attempt, kind, session_id, run.profile.name(), log_len(&run.log_path).await)
+            .await?;
 
-        if let Some(session_id) = session_id {
-            cmd.args(["-r", session_id]);
+        let invocation = self.invoke_executor(task, prompt, session_id, run).await;
+
+        let (exit_code, session_id_out, result, usage) = match &invocation {
+            Ok(invocation) => (
+                invocation.exit_code,
+                invocation.session_id.as_deref(),
+                invocation.result_event.as_ref().map(|event| event.to_string()),
+                invocation.usage,
+            ),
+            Err(_) => (None, None, None, Usage::default()),
+        };
+        if let Err(e) = self
+            .db
+            .finish_task_attempt(
+                attempt_id,
+                exit_code,
+                session_id_out,
+                log_len(&run.log_path).await,
+                result.as_deref(),
+                &usage,
+            )
+            .await
+        {
+            warn!("Failed to record attempt {} of task {}: {}", attempt_id, task.id, e);
         }
 
+        invocation
+    }
+
+    async fn invoke_executor(
+        &self,
+        task: &Task,
+        prompt: &str,
+        session_id: Option<&str>,
+        run: &TaskRun,
+    ) -> Result<InvocationResult> {
+        let task_id = run.task_id;
+
+        let mut cmd = run.executor.command(task, session_id, run.profile.config())?;
+
         info!("Running command: {:?}", cmd);
         cmd.current_dir(&task.cwd)
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
-            .envs(&self.config.env_vars);
+            .envs(&self.config.env_vars)
+            .envs(&run.profile.config().env);
+
+        // Run the executor in its own process group so cancellation reaches
+        // every process it spawned
+        #[cfg(unix)]
+        cmd.process_group(0);
 
         let mut child = cmd.spawn()?;
+        let mut cancel = run.cancel.clone();
 
         if let Some(stdin) = child.stdin.take() {
             let mut stdin = stdin;
-            stdin.write_all(prompt.as_bytes()).await?;
-            stdin.shutdown().await?;
+            // Executors that don't read the prompt may exit before it is written
+            let written = match stdin.write_all(prompt.as_bytes()).await {
+                Ok(()) => stdin.shutdown().await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = written
+                && e.kind() != std::io::ErrorKind::BrokenPipe
+            {
+                return Err(e.into());
+            }
         }
 
         let stdout = child.stdout.take().unwrap();
@@ -370,10 +1030,30 @@ impl Worker {
         let mut log_file = OpenOptions::new()
             .create(true)
             .append(true)
-            .open(task_log_path)
+            .open(&run.log_path)
             .await?;
 
-        while let Some(line) = lines.next_line().await? {
+        loop {
+            let line = tokio::select! {
+                line = lines.next_line() => match line? {
+                    Some(line) => line,
+                    None => break,
+                },
+                err = run.interruption(&mut cancel, run.idle_deadline()) => {
+                    // Claude is silent while a permission request waits for a human,
+                    // so the idle timeout only counts from the answer
+                    if let CcschedError::TaskIdleTimedOut(secs) = err
+                        && let Ok(Some(activity)) = self.db.last_approval_activity(task_id).await
+                        && activity + chrono::Duration::seconds(secs as i64) > Utc::now().naive_utc()
+                    {
+                        continue;
+                    }
+                    info!("Stopping task {}: {}", task_id, err);
+                    terminate_process_group(&mut child).await;
+                    return Err(err);
+                }
+            };
+
             // Write stdout directly to JSONL file without any wrapping
             let log_msg = format!("{}\n", line);
             if let Err(e) = log_file.write_all(log_msg.as_bytes()).await {
@@ -385,32 +1065,33 @@ impl Worker {
                 }
             }
 
-            if let Ok(json_value) = serde_json::from_str::<Value>(&line) {
-                if let Some(sid) = json_value.get("session_id").and_then(|v| v.as_str()) {
-                    // Output session_id update to stdout immediately
-                    let session_update = json!({
-                        "session_id": sid
-                    });
-                    println!("{}", session_update);
-                    
-                    // Update database with session_id immediately, regardless of current state
-                    if let Err(e) = self.db
-                        .update_task_status(task_id, TaskStatus::Running, Some(sid), None)
-                        .await
-                    {
-                        warn!("Failed to update task {} with session_id {}: {}", task_id, sid, e);
-                    }
-                    
-                    if session_id.is_none() {
-                        session_id = Some(sid.to_string());
-                    }
+            let event = run.executor.parse_line(&line);
+            if let Some(sid) = event.session_id.as_deref()
+                && session_id.as_deref() != Some(sid)
+            {
+                // Output session_id update to stdout immediately
+                let session_update = json!({
+                    "session_id": sid
+                });
+                println!("{}", session_update);
+                
+                // Update database with session_id immediately, regardless of current state
+                if let Err(e) = self.db
+                    .update_task_status(task_id, TaskStatus::Running, Some(sid), None)
+                    .await
+                {
+                    warn!("Failed to update task {} with session_id {}: {}", task_id, sid, e);
                 }
-
-                if json_value.get("type").and_then(|v| v.as_str()) == Some("result") {
-                    last_line = Some(json_value);
+                
+                if session_id.is_none() {
+                    session_id = Some(sid.to_string());
                 }
             }
 
+            if event.result.is_some() {
+                last_line = event.result;
+            }
+
             output_lines.push(line);
         }
 
@@ -431,55 +1112,210 @@ impl Worker {
             }
         }
 
-        let exit_status = child.wait().await?;
-        let success = exit_status.success()
-            && last_line
-                .as_ref()
-                .and_then(|v| v.get("subtype"))
-                .and_then(|v| v.as_str())
-                == Some("success")
-            && last_line
-                .as_ref()
-                .and_then(|v| v.get("is_error"))
-                .and_then(|v| v.as_bool())
-                == Some(false);
-
-        let output = output_lines.join("\n");
-
-        // Check for rate limit error
-        let mut rate_limit_timestamp = None;
-        if let Some(last) = &last_line {
-            if last.get("is_error").and_then(|v| v.as_bool()) == Some(true) {
-                if let Some(result) = last.get("result").and_then(|v| v.as_str()) {
-                    if result.starts_with("Claude AI usage limit reached|") {
-                        if let Some(timestamp_str) = result.strip_prefix("Claude AI usage limit reached|") {
-                            if let Ok(timestamp) = timestamp_str.parse::<i64>() {
-                                rate_limit_timestamp = Some(timestamp);
-                            }
-                        }
-                    }
-                }
+        let exit_status = tokio::select! {
+            status = child.wait() => status?,
+            err = run.interruption(&mut cancel, None) => {
+                info!("Stopping task {}: {}", task_id, err);
+                terminate_process_group(&mut child).await;
+                return Err(err);
             }
-        }
+        };
+        let outcome = run.executor.outcome(exit_status, last_line.as_ref());
+        let output = output_lines.join("\n");
 
-        Ok(ClaudeResult {
-            success,
+        Ok(InvocationResult {
+            success: outcome.success,
             session_id,
             output,
-            rate_limit_timestamp,
+            rate_limit_timestamp: outcome.rate_limit_timestamp,
+            exit_code: exit_status.code(),
+            result_event: last_line,
+            usage: outcome.usage,
         })
     }
 }
 
+/// Outcome of one run of a task's verification command.
+struct CheckResult {
+    success: bool,
+    /// `None` if the command was killed by a signal
+    exit_code: Option<i32>,
+    /// Combined stdout and stderr
+    output: String,
+}
+
+impl CheckResult {
+    fn exit_description(&self) -> String {
+        match self.exit_code {
+            Some(code) => format!("exit code {code}"),
+            None => "no exit code".to_string(),
+        }
+    }
+
+    /// The record of this run kept in the task's `check_output`.
+    fn section(&self, round: u32) -> String {
+        format!(
+            "=== Round {round}: {} ===\n{}\n",
+            self.exit_description(),
+            self.output.trim_end()
+        )
+    }
+
+    /// Prompt asking Claude to fix what the command reported. Only the tail of
+    /// long outputs is sent.
+    fn feedback_prompt(&self, command: &str) -> String {
+        let mut start = self.output.len().saturating_sub(MAX_CHECK_FEEDBACK_BYTES);
+        while !self.output.is_char_boundary(start) {
+            start += 1;
+        }
+
+        format!(
+            "The verification command `{command}` failed with {}. Fix the problems it reports so that it succeeds.\n\n{}",
+            self.exit_description(),
+            &self.output[start..]
+        )
+    }
+}
+
+/// Verification protocol for one task: the task's own overrides, falling back
+/// to the server defaults.
+struct Verification<'a> {
+    template: &'a str,
+    success_marker: &'a str,
+    failure_marker: &'a str,
+    max_rounds: u32,
+}
+
+impl<'a> Verification<'a> {
+    fn for_task(task: &'a Task, defaults: &'a VerificationConfig) -> Self {
+        Self {
+            template: task.verification_template.as_deref().unwrap_or(&defaults.template),
+            success_marker: task.success_marker.as_deref().unwrap_or(&defaults.success_marker),
+            failure_marker: task.failure_marker.as_deref().unwrap_or(&defaults.failure_marker),
+            max_rounds: task.max_verification_rounds.unwrap_or(defaults.max_rounds).max(1),
+        }
+    }
+
+    fn prompt(&self, task_prompt: &str) -> String {
+        self.template
+            .replace("{{prompt}}", task_prompt)
+            .replace("{{success_marker}}", self.success_marker)
+            .replace("{{failure_marker}}", self.failure_marker)
+    }
+}
+
+/// State shared by every Claude invocation made while executing one task.
+struct TaskRun {
+    task_id: i64,
+    executor: Arc<dyn Executor>,
+    /// Profile the task runs under, released when the run ends
+    profile: ProfileLease,
+    log_path: String,
+    cancel: watch::Receiver<bool>,
+    /// When this execution started; the wall-clock timeout covers the initial
+    /// run and every verification round
+    started_at: Instant,
+    /// Time earlier executions of the attempt ran before waiting for a rate limit
+    previous_run_time: Duration,
+    timeout_secs: Option<u64>,
+    idle_timeout_secs: Option<u64>,
+}
+
+impl TaskRun {
+    fn deadline(&self) -> Option<Instant> {
+        self.timeout_secs
+            .map(|secs| self.started_at + Duration::from_secs(secs).saturating_sub(self.previous_run_time))
+    }
+
+    /// Deadline for the next stream-json line, measured from now.
+    fn idle_deadline(&self) -> Option<Instant> {
+        self.idle_timeout_secs
+            .map(|secs| Instant::now() + Duration::from_secs(secs))
+    }
+
+    /// Resolves with the error to stop the running invocation with once the task
+    /// is cancelled or exceeds one of its time limits.
+    async fn interruption(
+        &self,
+        cancel: &mut watch::Receiver<bool>,
+        idle_deadline: Option<Instant>,
+    ) -> CcschedError {
+        tokio::select! {
+            _ = cancelled(cancel) => CcschedError::TaskCancelled(self.task_id),
+            _ = expires(self.deadline()) => {
+                CcschedError::TaskTimedOut(self.timeout_secs.unwrap_or_default())
+            }
+            _ = expires(idle_deadline) => {
+                CcschedError::TaskIdleTimedOut(self.idle_timeout_secs.unwrap_or_default())
+            }
+        }
+    }
+}
+
+/// Resolves at the given instant, or never if there is none.
+async fn expires(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Resolves once the task's cancel flag is set; never resolves if the cancel
+/// channel is closed.
+async fn cancelled(cancel: &mut watch::Receiver<bool>) {
+    if cancel.wait_for(|cancelled| *cancelled).await.is_err() {
+        std::future::pending::<()>().await;
+    }
+}
+
+/// Sends SIGTERM to the child's process group, escalating to SIGKILL if it has
+/// not exited within `KILL_GRACE_PERIOD`.
+async fn terminate_process_group(child: &mut Child) {
+    #[cfg(unix)]
+    if let Some(pid) = child.id() {
+        let pgid = -(pid as libc::pid_t);
+        // SAFETY: kill(2) has no memory safety requirements
+        unsafe { libc::kill(pgid, libc::SIGTERM) };
+        if tokio::time::timeout(KILL_GRACE_PERIOD, child.wait()).await.is_ok() {
+            return;
+        }
+        warn!("Process group {} did not exit after SIGTERM, sending SIGKILL", pid);
+        // SAFETY: kill(2) has no memory safety requirements
+        unsafe { libc::kill(pgid, libc::SIGKILL) };
+    }
+
+    if let Err(e) = child.kill().await {
+        warn!("Failed to kill Claude process: {}", e);
+    }
+}
+
 #[derive(Debug)]
-struct ClaudeResult {
+struct InvocationResult {
     success: bool,
     session_id: Option<String>,
     output: String,
     rate_limit_timestamp: Option<i64>,
+    exit_code: Option<i32>,
+    /// Final result event reported by the executor
+    result_event: Option<serde_json::Value>,
+    usage: Usage,
+}
+
+fn absolute_path(path: &str) -> String {
+    std::path::absolute(path)
+        .map(|path| path.to_string_lossy().to_string())
+        .unwrap_or_else(|_| path.to_string())
 }
 
-fn extract_work_result(output: &str) -> Option<String> {
+/// Current size of the task log, i.e. the offset the next write lands at.
+async fn log_len(log_path: &str) -> u64 {
+    tokio::fs::metadata(log_path)
+        .await
+        .map(|metadata| metadata.len())
+        .unwrap_or(0)
+}
+
+fn extract_work_result(output: &str, verification: &Verification) -> Option<String> {
     // Split by lines and work backwards to find the actual result
     let lines: Vec<&str> = output.lines().collect();
     
@@ -494,17 +1330,16 @@ fn extract_work_result(output: &str) -> Option<String> {
         // Try to parse as JSON to see if it's a Claude output line
         if let Ok(json_value) = serde_json::from_str::<serde_json::Value>(trimmed) {
             // Check if this is a result line with actual content
-            if let Some(result_type) = json_value.get("type").and_then(|v| v.as_str()) {
-                if result_type == "result" {
-                    if let Some(result_content) = json_value.get("result").and_then(|v| v.as_str()) {
-                        let trimmed_content = result_content.trim();
-                        // Make sure it's not a success/failure marker
-                        if !trimmed_content.is_empty() 
-                            && !trimmed_content.contains("CLAUDE_CODE_SCHEDULER_SUCCESS")
-                            && !trimmed_content.contains("CLAUDE_CODE_SCHEDULER_FAILED") {
-                            return Some(trimmed_content.to_string());
-                        }
-                    }
+            if let Some(result_type) = json_value.get("type").and_then(|v| v.as_str())
+                && result_type == "result"
+                && let Some(result_content) = json_value.get("result").and_then(|v| v.as_str())
+            {
+                let trimmed_content = result_content.trim();
+                // Make sure it's not a success/failure marker
+                if !trimmed_content.is_empty() 
+                    && !trimmed_content.contains(verification.success_marker)
+                    && !trimmed_content.contains(verification.failure_marker) {
+                    return Some(trimmed_content.to_string());
                 }
             }
         }
@@ -516,8 +1351,8 @@ fn extract_work_result(output: &str) -> Option<String> {
         if !trimmed.is_empty() 
             && !trimmed.starts_with("{") 
             && !trimmed.contains("\"type\"")
-            && !trimmed.contains("CLAUDE_CODE_SCHEDULER_SUCCESS")
-            && !trimmed.contains("CLAUDE_CODE_SCHEDULER_FAILED") {
+            && !trimmed.contains(verification.success_marker)
+            && !trimmed.contains(verification.failure_marker) {
             return Some(trimmed.to_string());
         }
     }
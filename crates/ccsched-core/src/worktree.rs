@@ -0,0 +1,146 @@
+use crate::error::{CcschedError, Result};
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+/// A git worktree a task runs in, on a branch of its own.
+#[derive(Debug, Clone)]
+pub struct Worktree {
+    /// Root of the worktree
+    pub path: String,
+    pub branch: String,
+    pub base_commit: String,
+}
+
+/// Name of the branch a task's worktree is created on.
+pub fn branch_name(task_id: i64) -> String {
+    format!("ccsched/task-{task_id}")
+}
+
+/// Creates a worktree for a task under `worktree_dir`, on a new branch starting
+/// at `base_ref` of the repository containing `repo_dir`.
+pub async fn create(repo_dir: &str, worktree_dir: &Path, task_id: i64, base_ref: &str) -> Result<Worktree> {
+    let mut base_commit = resolve_commit(repo_dir, base_ref).await?;
+
+    std::fs::create_dir_all(worktree_dir)?;
+    let path = std::fs::canonicalize(worktree_dir)?.join(format!("task-{task_id}"));
+    let path = path.to_string_lossy().to_string();
+    let branch = branch_name(task_id);
+
+    // A task run again after its worktree was cleaned up continues on its branch
+    let branch_ref = format!("refs/heads/{branch}");
+    if git(repo_dir, &["rev-parse", "--verify", "--quiet", &branch_ref]).await.is_ok() {
+        base_commit = git(repo_dir, &["merge-base", &branch_ref, &base_commit]).await?;
+        git(repo_dir, &["worktree", "add", &path, &branch]).await?;
+    } else {
+        git(repo_dir, &["worktree", "add", "-b", &branch, &path, &base_commit]).await?;
+    }
+
+    Ok(Worktree { path, branch, base_commit })
+}
+
+/// Resolves `base_ref` to a commit of the repository containing `repo_dir`.
+pub async fn resolve_commit(repo_dir: &str, base_ref: &str) -> Result<String> {
+    git(repo_dir, &["rev-parse", "--verify", &format!("{base_ref}^{{commit}}")])
+        .await
+        .map_err(|e| CcschedError::Git(format!("Invalid base ref '{base_ref}': {e}")))
+}
+
+/// The directory inside a worktree that corresponds to `dir` in the original
+/// checkout, e.g. `<worktree>/crates/foo` for a task submitted in `crates/foo`.
+pub async fn working_dir(dir: &str, worktree_path: &str) -> Result<String> {
+    let prefix = git(dir, &["rev-parse", "--show-prefix"]).await?;
+    Ok(PathBuf::from(worktree_path).join(prefix).to_string_lossy().to_string())
+}
+
+/// Commits everything in the worktree, if anything changed, and returns the
+/// resulting HEAD commit.
+pub async fn commit_all(path: &str, message: &str) -> Result<String> {
+    git(path, &["add", "-A"]).await?;
+
+    let changed = !git(path, &["status", "--porcelain"]).await?.is_empty();
+    if changed {
+        // Servers often run without a git identity; fall back to one of our own
+        let has_identity = git(path, &["config", "user.email"]).await.is_ok();
+        let mut args = Vec::new();
+        if !has_identity {
+            args.extend(["-c", "user.name=ccsched", "-c", "user.email=ccsched@localhost"]);
+        }
+        args.extend(["commit", "--no-verify", "-m", message]);
+        git(path, &args).await?;
+    }
+
+    git(path, &["rev-parse", "HEAD"]).await
+}
+
+/// Removes a worktree and its files. Its branch is kept.
+pub async fn remove(path: &str) -> Result<()> {
+    if !Path::new(path).exists() {
+        return Ok(());
+    }
+
+    // Run from the main repository, which knows about all of its worktrees
+    let common_dir = git(path, &["rev-parse", "--path-format=absolute", "--git-common-dir"]).await?;
+    let output = Command::new("git")
+        .arg("--git-dir")
+        .arg(&common_dir)
+        .args(["worktree", "remove", "--force", path])
+        .output()
+        .await?;
+    if !output.status.success() {
+        return Err(CcschedError::Git(format!(
+            "Failed to remove worktree {}: {}",
+            path,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(())
+}
+
+/// Task worktrees found in `worktree_dir`, as task ID and path.
+pub fn task_dirs(worktree_dir: &Path) -> Result<Vec<(i64, String)>> {
+    let worktree_dir = match std::fs::canonicalize(worktree_dir) {
+        Ok(dir) => dir,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let entries = std::fs::read_dir(worktree_dir)?;
+
+    let mut dirs = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        if let Some(id) = entry.file_name().to_str().and_then(|name| name.strip_prefix("task-"))
+            && let Ok(id) = id.parse()
+        {
+            dirs.push((id, entry.path().to_string_lossy().to_string()));
+        }
+    }
+
+    Ok(dirs)
+}
+
+/// Runs git in `dir` and returns its trimmed stdout.
+pub(crate) async fn git(dir: &str, args: &[&str]) -> Result<String> {
+    git_with_env(dir, args, &[]).await
+}
+
+/// Runs git in `dir` with extra environment variables and returns its trimmed
+/// stdout.
+pub(crate) async fn git_with_env(dir: &str, args: &[&str], env: &[(&str, &str)]) -> Result<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .envs(env.iter().copied())
+        .output()
+        .await?;
+    if !output.status.success() {
+        return Err(CcschedError::Git(format!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim_end().to_string())
+}
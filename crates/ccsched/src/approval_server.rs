@@ -0,0 +1,186 @@
+use crate::cli::ApprovalServerArgs;
+use crate::models::*;
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+/// How often a queued permission request is checked for an answer.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Runs the MCP server Claude Code uses as its permission prompt tool for tasks
+/// in the `ask` permission mode.
+///
+/// Speaks JSON-RPC over stdin/stdout and offers a single `approve` tool. Each
+/// call queues the permission request on the scheduler and completes once a
+/// human approves or denies it, or the request times out.
+pub async fn run_approval_server(args: ApprovalServerArgs) -> Result<()> {
+    let base_url = format!("http://{}:{}",
+                           args.host.as_ref().unwrap_or(&"localhost".to_string()),
+                           args.port.unwrap_or(39512));
+    let client = reqwest::Client::new();
+
+    // Tool calls are answered concurrently, so all responses go through one writer
+    let (sender, mut receiver) = mpsc::unbounded_channel::<Value>();
+    let writer = tokio::spawn(async move {
+        let mut stdout = tokio::io::stdout();
+        while let Some(message) = receiver.recv().await {
+            let line = format!("{message}\n");
+            if stdout.write_all(line.as_bytes()).await.is_err() || stdout.flush().await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    while let Some(line) = lines.next_line().await? {
+        let Ok(message) = serde_json::from_str::<Value>(&line) else {
+            warn!("Ignoring malformed MCP message: {}", line);
+            continue;
+        };
+
+        // Notifications such as notifications/initialized need no answer
+        let Some(id) = message.get("id").cloned() else {
+            continue;
+        };
+
+        let method = message.get("method").and_then(|v| v.as_str()).unwrap_or_default();
+        match method {
+            "initialize" => {
+                let protocol_version = message
+                    .pointer("/params/protocolVersion")
+                    .cloned()
+                    .unwrap_or_else(|| json!("2024-11-05"));
+                let _ = sender.send(response(id, json!({
+                    "protocolVersion": protocol_version,
+                    "capabilities": { "tools": {} },
+                    "serverInfo": { "name": "ccsched", "version": env!("CARGO_PKG_VERSION") },
+                })));
+            }
+            "ping" => {
+                let _ = sender.send(response(id, json!({})));
+            }
+            "tools/list" => {
+                let _ = sender.send(response(id, json!({ "tools": [approve_tool()] })));
+            }
+            "tools/call" => {
+                let params = message.get("params").cloned().unwrap_or_default();
+                let sender = sender.clone();
+                let client = client.clone();
+                let base_url = base_url.clone();
+                let task_id = args.task_id;
+                tokio::spawn(async move {
+                    let decision = match params.get("name").and_then(|v| v.as_str()) {
+                        Some("approve") => {
+                            let arguments = params.get("arguments").cloned().unwrap_or_default();
+                            request_decision(&client, &base_url, task_id, &arguments).await
+                        }
+                        name => deny(&format!("Unknown tool: {}", name.unwrap_or_default())),
+                    };
+                    let _ = sender.send(response(id, json!({
+                        "content": [{ "type": "text", "text": decision.to_string() }],
+                    })));
+                });
+            }
+            _ => {
+                let _ = sender.send(json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "error": { "code": -32601, "message": format!("Method not found: {method}") },
+                }));
+            }
+        }
+    }
+
+    drop(sender);
+    let _ = writer.await;
+    Ok(())
+}
+
+fn response(id: Value, result: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "result": result })
+}
+
+fn approve_tool() -> Value {
+    json!({
+        "name": "approve",
+        "description": "Asks the ccsched user whether a tool may be used",
+        "inputSchema": {
+            "type": "object",
+            "properties": {
+                "tool_name": { "type": "string" },
+                "input": { "type": "object" },
+                "tool_use_id": { "type": "string" },
+            },
+            "required": ["tool_name", "input"],
+        },
+    })
+}
+
+fn deny(message: &str) -> Value {
+    json!({ "behavior": "deny", "message": message })
+}
+
+/// Queues the permission request and waits for its answer, in the format Claude
+/// Code expects from a permission prompt tool.
+async fn request_decision(client: &reqwest::Client, base_url: &str, task_id: i64, arguments: &Value) -> Value {
+    let input = arguments.get("input").cloned().unwrap_or_else(|| json!({}));
+    let request = CreateApprovalRequest {
+        tool_name: arguments
+            .get("tool_name")
+            .and_then(|v| v.as_str())
+            .unwrap_or_default()
+            .to_string(),
+        input: input.clone(),
+    };
+
+    match wait_for_approval(client, base_url, task_id, &request).await {
+        Ok(approval) if approval.status == ApprovalStatus::Approved => {
+            json!({ "behavior": "allow", "updatedInput": input })
+        }
+        Ok(approval) => deny(
+            approval
+                .message
+                .as_deref()
+                .unwrap_or("The ccsched user denied this tool use"),
+        ),
+        Err(e) => {
+            warn!("Permission request of task {} failed: {}", task_id, e);
+            deny(&format!("The permission request could not be answered: {e}"))
+        }
+    }
+}
+
+async fn wait_for_approval(
+    client: &reqwest::Client,
+    base_url: &str,
+    task_id: i64,
+    request: &CreateApprovalRequest,
+) -> Result<Approval> {
+    let response = client
+        .post(format!("{base_url}/task/{task_id}/approvals"))
+        .json(request)
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        let message = response.text().await.unwrap_or_default();
+        return Err(anyhow!("Failed to queue the request: {}", message));
+    }
+    let mut approval: Approval = response.json().await?;
+    info!("Waiting for approval {} to use {}", approval.id, approval.tool_name);
+
+    while approval.status == ApprovalStatus::Pending {
+        tokio::time::sleep(POLL_INTERVAL).await;
+        approval = client
+            .get(format!("{base_url}/approval/{}", approval.id))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+    }
+
+    Ok(approval)
+}
@@ -1,4 +1,5 @@
-use clap::{Parser, Subcommand};
+use crate::models::{FailureKind, PermissionMode};
+use clap::{Args, Parser, Subcommand};
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -19,6 +20,12 @@ pub enum Commands {
     /// Add a file as a task (aliases: a) - filename becomes task command, file content becomes prompt
     #[command(alias = "a")]
     Add(AddArgs),
+    /// Submit every task of a workflow file (YAML or TOML) at once
+    Apply(ApplyArgs),
+    /// List workflow runs and their overall status
+    Runs(RunsArgs),
+    /// Show, cancel or retry a workflow run
+    Run(RunArgs),
     /// List all tasks and their status (aliases: ls, l)
     #[command(alias = "ls", alias = "l")]
     List(ListArgs),
@@ -37,6 +44,34 @@ pub enum Commands {
     /// Edit a task's prompt (alias: e)
     #[command(alias = "e")]
     Edit(EditArgs),
+    /// Cancel a pending, waiting or running task (alias: c)
+    #[command(alias = "c")]
+    Cancel(CancelArgs),
+    /// Show or change the dependencies of a task
+    Deps(DepsArgs),
+    /// Show every executor invocation of a task (alias: at)
+    #[command(alias = "at")]
+    Attempts(AttemptsArgs),
+    /// Show what a task changed in its git repository
+    Diff(DiffArgs),
+    /// Restore the files a task changed to their state before it ran
+    Rollback(RollbackArgs),
+    /// Show token usage and cost by day, working directory and status
+    Stats(StatsArgs),
+    /// Show execution profiles, their rate-limit state and usage
+    Profiles(ProfilesArgs),
+    /// Remove the worktrees of done, cancelled and deleted tasks and the checkpoints of deleted ones
+    Cleanup(CleanupArgs),
+    /// List tool permission requests waiting for an answer (alias: ap)
+    #[command(alias = "ap")]
+    Approvals(ApprovalsArgs),
+    /// Allow a pending tool permission request
+    Approve(ApproveArgs),
+    /// Refuse a pending tool permission request
+    Deny(DenyArgs),
+    /// Run the MCP server that forwards a task's permission prompts to the scheduler
+    #[command(hide = true)]
+    ApprovalServer(ApprovalServerArgs),
 }
 
 #[derive(Parser)]
@@ -53,6 +88,10 @@ pub struct StartArgs {
     #[arg(short, long)]
     pub claude_path: Option<String>,
 
+    /// Maximum number of tasks to run concurrently (default: 1)
+    #[arg(short, long)]
+    pub workers: Option<usize>,
+
     /// Environment file to load (default: ".env")
     #[arg(short, long)]
     pub env: Option<String>,
@@ -70,10 +109,14 @@ pub struct SubmitArgs {
     #[arg(short, long)]
     pub cwd: Option<String>,
 
-    /// Comma-separated list of task IDs this task depends on
+    /// Comma-separated list of task IDs this task depends on, each optionally
+    /// suffixed with the outcome it waits for: `:success` (default), `:failure` or `:any`
     #[arg(short, long)]
     pub depends: Option<String>,
 
+    #[command(flatten)]
+    pub options: TaskOptions,
+
     /// Scheduler host
     #[arg(short = 'H', long)]
     pub host: Option<String>,
@@ -92,10 +135,14 @@ pub struct AddArgs {
     #[arg(short, long)]
     pub cwd: Option<String>,
 
-    /// Comma-separated list of task IDs this task depends on
+    /// Comma-separated list of task IDs this task depends on, each optionally
+    /// suffixed with the outcome it waits for: `:success` (default), `:failure` or `:any`
     #[arg(short, long)]
     pub depends: Option<String>,
 
+    #[command(flatten)]
+    pub options: TaskOptions,
+
     /// Scheduler host
     #[arg(short = 'H', long)]
     pub host: Option<String>,
@@ -105,9 +152,133 @@ pub struct AddArgs {
     pub port: Option<u16>,
 }
 
+/// Execution options shared by every command that creates tasks
+#[derive(Args)]
+pub struct TaskOptions {
+    /// Fail the task if it runs longer than this in total, e.g. "90m" (default: server setting)
+    #[arg(long, value_parser = parse_duration_secs)]
+    pub timeout: Option<u64>,
+
+    /// Fail the task if Claude produces no output for this long, e.g. "10m" (default: server setting)
+    #[arg(long, value_parser = parse_duration_secs)]
+    pub idle_timeout: Option<u64>,
+
+    /// Executor to run the task with: claude, shell or fake (default: claude)
+    #[arg(long)]
+    pub executor: Option<String>,
+
+    /// Accept the first pass without verification rounds
+    #[arg(long)]
+    pub no_verify: bool,
+
+    /// File with the verification prompt template; {{prompt}}, {{success_marker}} and {{failure_marker}} are substituted (default: server setting)
+    #[arg(long)]
+    pub verify_template: Option<String>,
+
+    /// Marker Claude replies with when verification succeeds (default: server setting)
+    #[arg(long)]
+    pub success_marker: Option<String>,
+
+    /// Marker Claude replies with when the task cannot be completed (default: server setting)
+    #[arg(long)]
+    pub failure_marker: Option<String>,
+
+    /// Maximum number of verification rounds (default: server setting)
+    #[arg(long)]
+    pub max_verify_rounds: Option<u32>,
+
+    /// Shell command run in the task's directory after each pass, e.g. "cargo test"; the task is done once it exits with 0 and its output is sent back to Claude otherwise
+    #[arg(long, conflicts_with_all = ["no_verify", "verify_template", "success_marker", "failure_marker"])]
+    pub verify_cmd: Option<String>,
+
+    /// Total number of attempts, so 3 retries a failed task up to twice (default: server setting)
+    #[arg(long)]
+    pub max_attempts: Option<u32>,
+
+    /// Delay before the first retry, doubled for every further one, e.g. "30s" (default: server setting)
+    #[arg(long, value_parser = parse_duration_secs)]
+    pub retry_backoff: Option<u64>,
+
+    /// Comma-separated failure kinds to retry: crash, timeout, verification, budget, policy (default: server setting)
+    #[arg(long, value_parser = FailureKind::parse_list)]
+    pub retry_on: Option<Vec<FailureKind>>,
+
+    /// Fail the task instead of invoking Claude again once it has cost this many USD (default: server setting)
+    #[arg(long)]
+    pub max_cost: Option<f64>,
+
+    /// Claude model to use, e.g. "sonnet" or a full model name
+    #[arg(long)]
+    pub model: Option<String>,
+
+    /// Model to fall back to when the primary model is overloaded
+    #[arg(long)]
+    pub fallback_model: Option<String>,
+
+    /// Maximum number of agentic turns per Claude invocation
+    #[arg(long)]
+    pub max_turns: Option<u32>,
+
+    /// Comma-separated tools Claude may use, e.g. "Read,Edit,Bash(git diff:*)"
+    #[arg(long, value_delimiter = ',')]
+    pub allowed_tools: Vec<String>,
+
+    /// Comma-separated tools Claude must not use
+    #[arg(long, value_delimiter = ',')]
+    pub disallowed_tools: Vec<String>,
+
+    /// Text appended to Claude Code's system prompt
+    #[arg(long)]
+    pub append_system_prompt: Option<String>,
+
+    /// MCP server configuration file
+    #[arg(long)]
+    pub mcp_config: Option<String>,
+
+    /// How Claude handles tool permissions: skip, accept-edits, ask (queue other tools for `ccsched approve`), allow-list (only --allowed-tools) or deny-by-default (default: server setting)
+    #[arg(long)]
+    pub permission_mode: Option<PermissionMode>,
+
+    /// Shell command run in the task's directory before Claude is first invoked; the task fails if it fails
+    #[arg(long)]
+    pub pre_run: Option<String>,
+
+    /// Shell command run after the task succeeds
+    #[arg(long)]
+    pub post_success: Option<String>,
+
+    /// Shell command run after an attempt of the task fails or is cancelled
+    #[arg(long)]
+    pub post_failure: Option<String>,
+
+    /// Shell command run after every attempt of the task
+    #[arg(long)]
+    pub post_always: Option<String>,
+
+    /// Run the task in a git worktree on its own branch, committing the result on success
+    #[arg(long)]
+    pub worktree: bool,
+
+    /// Ref the worktree's branch starts from (default: HEAD)
+    #[arg(long, requires = "worktree")]
+    pub base: Option<String>,
+
+    /// Continue the latest Claude session of this task, once it has succeeded
+    #[arg(long, value_name = "TASK_ID", conflicts_with = "worktree")]
+    pub continue_from: Option<i64>,
+
+    /// Continue in a fork of that session, leaving the parent's history unchanged
+    #[arg(long, requires = "continue_from")]
+    pub fork_session: bool,
+
+    /// Value of a {{vars.<key>}} placeholder in the prompt, as key=value (can be repeated)
+    #[arg(long = "var", value_name = "KEY=VALUE", value_parser = parse_var)]
+    pub vars: Vec<(String, String)>,
+}
+
 #[derive(Parser)]
 pub struct ListArgs {
-    /// Show detailed information including timestamps and session IDs
+    /// Show detailed information including timestamps, session IDs and cost
     #[arg(short, long)]
     pub detail: bool,
 
@@ -198,4 +369,308 @@ pub struct EditArgs {
     /// Scheduler port
     #[arg(short, long)]
     pub port: Option<u16>,
-}
\ No newline at end of file
+}
+
+#[derive(Parser)]
+pub struct CancelArgs {
+    /// Task ID to cancel
+    pub task_id: i64,
+
+    /// Scheduler host
+    #[arg(short = 'H', long)]
+    pub host: Option<String>,
+
+    /// Scheduler port
+    #[arg(short, long)]
+    pub port: Option<u16>,
+}
+
+#[derive(Parser)]
+pub struct AttemptsArgs {
+    /// Task ID to show attempts for
+    pub task_id: i64,
+
+    /// Scheduler host
+    #[arg(short = 'H', long)]
+    pub host: Option<String>,
+
+    /// Scheduler port
+    #[arg(short, long)]
+    pub port: Option<u16>,
+}
+
+#[derive(Parser)]
+pub struct DiffArgs {
+    /// Task ID to show the diff of
+    pub task_id: i64,
+
+    /// Only show the changed files and line counts
+    #[arg(long)]
+    pub stat: bool,
+
+    /// Scheduler host
+    #[arg(short = 'H', long)]
+    pub host: Option<String>,
+
+    /// Scheduler port
+    #[arg(short, long)]
+    pub port: Option<u16>,
+}
+
+#[derive(Parser)]
+pub struct ApplyArgs {
+    /// Workflow file; `.toml` files are read as TOML, anything else as YAML
+    pub file: String,
+
+    /// Scheduler host
+    #[arg(short = 'H', long)]
+    pub host: Option<String>,
+
+    /// Scheduler port
+    #[arg(short, long)]
+    pub port: Option<u16>,
+}
+
+#[derive(Parser)]
+pub struct RunsArgs {
+    /// Scheduler host
+    #[arg(short = 'H', long)]
+    pub host: Option<String>,
+
+    /// Scheduler port
+    #[arg(short, long)]
+    pub port: Option<u16>,
+}
+
+#[derive(Parser)]
+pub struct RunArgs {
+    #[command(subcommand)]
+    pub command: RunCommand,
+}
+
+#[derive(Subcommand)]
+pub enum RunCommand {
+    /// Show a run's status, timing, cost and tasks
+    Show(RunIdArgs),
+    /// Cancel every unfinished task of a run
+    Cancel(RunIdArgs),
+    /// Run the failed, cancelled and skipped tasks of a run again
+    Retry(RunIdArgs),
+}
+
+#[derive(Parser)]
+pub struct RunIdArgs {
+    /// Run ID
+    pub run_id: i64,
+
+    /// Scheduler host
+    #[arg(short = 'H', long)]
+    pub host: Option<String>,
+
+    /// Scheduler port
+    #[arg(short, long)]
+    pub port: Option<u16>,
+}
+
+#[derive(Parser)]
+pub struct DepsArgs {
+    #[command(subcommand)]
+    pub command: DepsCommand,
+}
+
+#[derive(Subcommand)]
+pub enum DepsCommand {
+    /// List the dependencies of a task
+    Show(DepsShowArgs),
+    /// Add dependencies to a task, or change the condition of existing ones
+    Add(DepsChangeArgs),
+    /// Remove dependencies from a task
+    Remove(DepsChangeArgs),
+    /// Replace all dependencies of a task
+    Set(DepsSetArgs),
+}
+
+#[derive(Parser)]
+pub struct DepsShowArgs {
+    /// Task ID
+    pub task_id: i64,
+
+    /// Scheduler host
+    #[arg(short = 'H', long)]
+    pub host: Option<String>,
+
+    /// Scheduler port
+    #[arg(short, long)]
+    pub port: Option<u16>,
+}
+
+#[derive(Parser)]
+pub struct DepsChangeArgs {
+    /// Task ID
+    pub task_id: i64,
+
+    /// Comma-separated list of dependencies, e.g. `3,4:failure,5:any`
+    pub dependencies: String,
+
+    /// Scheduler host
+    #[arg(short = 'H', long)]
+    pub host: Option<String>,
+
+    /// Scheduler port
+    #[arg(short, long)]
+    pub port: Option<u16>,
+}
+
+#[derive(Parser)]
+pub struct DepsSetArgs {
+    /// Task ID
+    pub task_id: i64,
+
+    /// Comma-separated list of dependencies, e.g. `3,4:failure,5:any` (none if omitted)
+    pub dependencies: Option<String>,
+
+    /// Scheduler host
+    #[arg(short = 'H', long)]
+    pub host: Option<String>,
+
+    /// Scheduler port
+    #[arg(short, long)]
+    pub port: Option<u16>,
+}
+
+#[derive(Parser)]
+pub struct RollbackArgs {
+    /// Task ID to roll back
+    pub task_id: i64,
+
+    /// Roll back even if later tasks changed the same files
+    #[arg(long)]
+    pub force: bool,
+
+    /// Scheduler host
+    #[arg(short = 'H', long)]
+    pub host: Option<String>,
+
+    /// Scheduler port
+    #[arg(short, long)]
+    pub port: Option<u16>,
+}
+
+#[derive(Parser)]
+pub struct StatsArgs {
+    /// Scheduler host
+    #[arg(short = 'H', long)]
+    pub host: Option<String>,
+
+    /// Scheduler port
+    #[arg(short, long)]
+    pub port: Option<u16>,
+}
+
+#[derive(Parser)]
+pub struct CleanupArgs {
+    /// Scheduler host
+    #[arg(short = 'H', long)]
+    pub host: Option<String>,
+
+    /// Scheduler port
+    #[arg(short, long)]
+    pub port: Option<u16>,
+}
+
+#[derive(Parser)]
+pub struct ProfilesArgs {
+    /// Scheduler host
+    #[arg(short = 'H', long)]
+    pub host: Option<String>,
+
+    /// Scheduler port
+    #[arg(short, long)]
+    pub port: Option<u16>,
+}
+
+#[derive(Parser)]
+pub struct ApprovalsArgs {
+    /// Scheduler host
+    #[arg(short = 'H', long)]
+    pub host: Option<String>,
+
+    /// Scheduler port
+    #[arg(short, long)]
+    pub port: Option<u16>,
+}
+
+#[derive(Parser)]
+pub struct ApproveArgs {
+    /// Approval ID to allow
+    pub approval_id: i64,
+
+    /// Scheduler host
+    #[arg(short = 'H', long)]
+    pub host: Option<String>,
+
+    /// Scheduler port
+    #[arg(short, long)]
+    pub port: Option<u16>,
+}
+
+#[derive(Parser)]
+pub struct DenyArgs {
+    /// Approval ID to refuse
+    pub approval_id: i64,
+
+    /// Explanation passed back to Claude
+    #[arg(short, long)]
+    pub message: Option<String>,
+
+    /// Scheduler host
+    #[arg(short = 'H', long)]
+    pub host: Option<String>,
+
+    /// Scheduler port
+    #[arg(short, long)]
+    pub port: Option<u16>,
+}
+
+#[derive(Parser)]
+pub struct ApprovalServerArgs {
+    /// Task whose permission prompts are handled
+    pub task_id: i64,
+
+    /// Scheduler host
+    #[arg(short = 'H', long)]
+    pub host: Option<String>,
+
+    /// Scheduler port
+    #[arg(short, long)]
+    pub port: Option<u16>,
+}
+
+/// Parses a duration such as "90", "45s", "30m" or "2h" into seconds.
+pub fn parse_duration_secs(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    let (number, multiplier) = match value.char_indices().last() {
+        Some((i, 's')) => (&value[..i], 1),
+        Some((i, 'm')) => (&value[..i], 60),
+        Some((i, 'h')) => (&value[..i], 3600),
+        _ => (value, 1),
+    };
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("invalid duration '{value}', expected e.g. 90, 45s, 30m or 2h"))?;
+    number
+        .checked_mul(multiplier)
+        .ok_or_else(|| format!("duration '{value}' is too long"))
+}
+
+/// Parses a `--var` value such as "branch=main".
+pub fn parse_var(value: &str) -> Result<(String, String), String> {
+    let Some((key, value)) = value.split_once('=') else {
+        return Err(format!("invalid variable '{value}', expected KEY=VALUE"));
+    };
+    let key = key.trim();
+    if key.is_empty() {
+        return Err("variable name must not be empty".to_string());
+    }
+    Ok((key.to_string(), value.to_string()))
+}
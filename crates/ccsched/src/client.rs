@@ -24,13 +24,9 @@ pub async fn add_task(args: AddArgs) -> Result<()> {
             .to_string()
     });
 
-    let depends_on = if let Some(deps) = &args.depends {
-        deps.split(',')
-            .map(|s| s.trim().parse::<i64>())
-            .collect::<Result<Vec<_>, _>>()
-            .map_err(|e| anyhow!("Invalid dependency ID: {}", e))?
-    } else {
-        Vec::new()
+    let depends_on = match &args.depends {
+        Some(deps) => parse_dependencies(deps)?,
+        None => Vec::new(),
     };
 
     let request = CreateTaskRequest {
@@ -38,6 +34,7 @@ pub async fn add_task(args: AddArgs) -> Result<()> {
         prompt,
         cwd,
         depends_on,
+        ..task_options_request(args.options)?
     };
 
     let client = reqwest::Client::new();
@@ -58,6 +55,67 @@ pub async fn add_task(args: AddArgs) -> Result<()> {
     Ok(())
 }
 
+/// Builds the option fields of a task creation request; the caller fills in the
+/// name, prompt, working directory and dependencies.
+fn task_options_request(options: TaskOptions) -> Result<CreateTaskRequest> {
+    let verification_template = match &options.verify_template {
+        Some(path) => Some(
+            std::fs::read_to_string(path)
+                .map_err(|e| anyhow!("Failed to read verification template '{}': {}", path, e))?,
+        ),
+        None => None,
+    };
+
+    // The server runs Claude from the task's directory, so pass an absolute path
+    let mcp_config = match &options.mcp_config {
+        Some(path) => Some(
+            std::fs::canonicalize(path)
+                .map_err(|e| anyhow!("Failed to find MCP config '{}': {}", path, e))?
+                .to_string_lossy()
+                .to_string(),
+        ),
+        None => None,
+    };
+
+    Ok(CreateTaskRequest {
+        timeout_secs: options.timeout,
+        idle_timeout_secs: options.idle_timeout,
+        executor: options.executor,
+        verify: options.no_verify.then_some(false),
+        verification_template,
+        success_marker: options.success_marker,
+        failure_marker: options.failure_marker,
+        max_verification_rounds: options.max_verify_rounds,
+        verify_cmd: options.verify_cmd,
+        max_attempts: options.max_attempts,
+        retry_backoff_secs: options.retry_backoff,
+        retry_on: options.retry_on,
+        max_cost_usd: options.max_cost,
+        claude_options: ClaudeOptions {
+            model: options.model,
+            fallback_model: options.fallback_model,
+            max_turns: options.max_turns,
+            allowed_tools: options.allowed_tools,
+            disallowed_tools: options.disallowed_tools,
+            append_system_prompt: options.append_system_prompt,
+            mcp_config,
+        },
+        permission_mode: options.permission_mode,
+        hooks: Hooks {
+            pre_run: options.pre_run,
+            post_success: options.post_success,
+            post_failure: options.post_failure,
+            post_always: options.post_always,
+        },
+        worktree: options.worktree,
+        base_ref: options.base,
+        vars: options.vars.into_iter().collect(),
+        continue_from: options.continue_from,
+        fork_session: options.fork_session,
+        ..Default::default()
+    })
+}
+
 pub async fn submit_task(args: SubmitArgs) -> Result<()> {
     let prompt = if let Some(prompt_file) = &args.prompt_file {
         // Prompt file was explicitly provided
@@ -79,13 +137,9 @@ pub async fn submit_task(args: SubmitArgs) -> Result<()> {
             .to_string()
     });
 
-    let depends_on = if let Some(deps) = &args.depends {
-        deps.split(',')
-            .map(|s| s.trim().parse::<i64>())
-            .collect::<Result<Vec<_>, _>>()
-            .map_err(|e| anyhow!("Invalid dependency ID: {}", e))?
-    } else {
-        Vec::new()
+    let depends_on = match &args.depends {
+        Some(deps) => parse_dependencies(deps)?,
+        None => Vec::new(),
     };
 
     let request = CreateTaskRequest {
@@ -93,6 +147,7 @@ pub async fn submit_task(args: SubmitArgs) -> Result<()> {
         prompt,
         cwd,
         depends_on,
+        ..task_options_request(args.options)?
     };
 
     let client = reqwest::Client::new();
@@ -113,6 +168,198 @@ pub async fn submit_task(args: SubmitArgs) -> Result<()> {
     Ok(())
 }
 
+pub async fn apply_workflow(args: ApplyArgs) -> Result<()> {
+    let path = std::path::absolute(&args.file)
+        .map_err(|e| anyhow!("Failed to read workflow '{}': {}", args.file, e))?;
+    let content = std::fs::read_to_string(&path)
+        .map_err(|e| anyhow!("Failed to read workflow '{}': {}", args.file, e))?;
+    let mut workflow: serde_json::Value = if path.extension().is_some_and(|ext| ext == "toml") {
+        toml::from_str(&content).map_err(|e| anyhow!("Invalid workflow '{}': {}", args.file, e))?
+    } else {
+        serde_yaml::from_str(&content).map_err(|e| anyhow!("Invalid workflow '{}': {}", args.file, e))?
+    };
+
+    // Paths in the workflow are relative to the directory it is in
+    let base_dir = path.parent().map(std::path::Path::to_path_buf).unwrap_or_default();
+    // Serde ignores unknown keys, so a misspelled `needs` would silently drop an edge
+    let request_fields = serde_json::to_value(CreateTaskRequest::default())?;
+    let tasks = workflow
+        .get_mut("tasks")
+        .and_then(|tasks| tasks.as_array_mut())
+        .ok_or_else(|| anyhow!("Invalid workflow '{}': expected a list of tasks", args.file))?;
+    for task in tasks {
+        let task = task
+            .as_object_mut()
+            .ok_or_else(|| anyhow!("Invalid workflow '{}': every task must be a mapping", args.file))?;
+        let name = task.get("name").and_then(|name| name.as_str()).unwrap_or_default().to_string();
+
+        if let Some(key) = task
+            .keys()
+            .find(|key| !matches!(key.as_str(), "needs" | "prompt_file") && request_fields.get(key.as_str()).is_none())
+        {
+            return Err(anyhow!("Workflow task '{}' has unknown field '{}'", name, key));
+        }
+
+        if let Some(prompt_file) = task.remove("prompt_file") {
+            if task.contains_key("prompt") {
+                return Err(anyhow!("Workflow task '{}' has both a prompt and a prompt_file", name));
+            }
+            let prompt_file = base_dir.join(prompt_file.as_str().unwrap_or_default());
+            let prompt = std::fs::read_to_string(&prompt_file)
+                .map_err(|e| anyhow!("Failed to read prompt of workflow task '{}' from '{}': {}", name, prompt_file.display(), e))?;
+            task.insert("prompt".to_string(), prompt.into());
+        }
+
+        let cwd = base_dir.join(task.get("cwd").and_then(|cwd| cwd.as_str()).unwrap_or_default());
+        let cwd = std::fs::canonicalize(&cwd)
+            .map_err(|e| anyhow!("Invalid working directory of workflow task '{}': {}: {}", name, cwd.display(), e))?;
+        task.insert("cwd".to_string(), cwd.to_string_lossy().into());
+    }
+
+    let mut request: ApplyWorkflowRequest = serde_json::from_value(workflow)
+        .map_err(|e| anyhow!("Invalid workflow '{}': {}", args.file, e))?;
+    if request.name.is_none() {
+        request.name = path.file_stem().map(|stem| stem.to_string_lossy().to_string());
+    }
+
+    let client = reqwest::Client::new();
+    let url = format!("http://{}:{}/apply",
+                      args.host.as_ref().unwrap_or(&"localhost".to_string()),
+                      args.port.unwrap_or(39512));
+
+    let response = client.post(&url).json(&request).send().await?;
+    if !response.status().is_success() {
+        let message = response.text().await.unwrap_or_default();
+        return Err(anyhow!("Failed to apply workflow '{}': {}", args.file, message));
+    }
+
+    let applied: ApplyWorkflowResponse = response.json().await?;
+    println!("Workflow applied successfully. Run ID: {}", applied.run_id);
+    println!("Task IDs:");
+    for task in &request.tasks {
+        if let Some(id) = applied.task_ids.get(&task.request.name) {
+            println!("  {:<4} {}", id, task.request.name);
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn list_runs(args: RunsArgs) -> Result<()> {
+    let client = reqwest::Client::new();
+    let url = format!("http://{}:{}/runs",
+                      args.host.as_ref().unwrap_or(&"localhost".to_string()),
+                      args.port.unwrap_or(39512));
+
+    let response = client.get(&url).send().await?.error_for_status()?;
+    let run_list: RunsResponse = response.json().await?;
+
+    if run_list.runs.is_empty() {
+        println!("No runs found.");
+        return Ok(());
+    }
+
+    println!("{:<4} {:<25} {:<20} {:<7} {:<20} {:<20} {:>9}",
+             "ID", "Name", "Status", "Done", "Started", "Finished", "Cost");
+    println!("{}", "-".repeat(111));
+    for run in &run_list.runs {
+        println!("{:<4} {:<25} {:<19} {:<7} {:<20} {:<20} {:>9}",
+                 run.id,
+                 truncate(&run.name, 25),
+                 format_run_status(&run.status),
+                 format!("{}/{}", run.done_count, run.task_count),
+                 format_time(run.started_at),
+                 format_time(run.finished_at),
+                 format_cost(run.cost_usd));
+    }
+
+    Ok(())
+}
+
+pub async fn show_run(args: RunIdArgs) -> Result<()> {
+    let client = reqwest::Client::new();
+    let url = format!("http://{}:{}/run/{}",
+                      args.host.as_ref().unwrap_or(&"localhost".to_string()),
+                      args.port.unwrap_or(39512),
+                      args.run_id);
+
+    let response = client.get(&url).send().await?;
+    if !response.status().is_success() {
+        let message = response.text().await.unwrap_or_default();
+        return Err(anyhow!("Failed to get run {}: {}", args.run_id, message));
+    }
+
+    let details: RunDetails = response.json().await?;
+    let run = &details.run;
+    println!("Run Details:");
+    println!("============");
+    println!("ID: {}", run.id);
+    println!("Name: {}", run.name);
+    println!("Status: {}", format_run_status(&run.status));
+    println!("Created: {}", run.created_at.format("%Y-%m-%d %H:%M:%S UTC"));
+    if let Some(started) = run.started_at {
+        println!("Started: {}", started.format("%Y-%m-%d %H:%M:%S UTC"));
+    }
+    if let Some(finished) = run.finished_at {
+        println!("Finished: {}", finished.format("%Y-%m-%d %H:%M:%S UTC"));
+    }
+    println!("Succeeded: {} of {} tasks", run.done_count, run.task_count);
+    println!("Cost: {}", format_cost(run.cost_usd));
+
+    println!("\nTasks:");
+    println!("{:<4} {:<25} {:<11} {:<20} {:>9} {:>10}", "ID", "Name", "Status", "Finished", "Cost", "Tokens");
+    println!("{}", "-".repeat(84));
+    for task in &details.tasks {
+        println!("{:<4} {:<25} {:<10} {:<20} {:>9} {:>10}",
+                 task.id,
+                 truncate(&task.name, 25),
+                 format_status(&task.status),
+                 format_time(task.finished_at),
+                 format_cost(task.usage.cost_usd),
+                 task.usage.total_tokens());
+    }
+
+    Ok(())
+}
+
+pub async fn cancel_run(args: RunIdArgs) -> Result<()> {
+    let task_ids = run_action(&args, "cancel").await?;
+    if task_ids.is_empty() {
+        println!("Run {} has no unfinished tasks.", args.run_id);
+    } else {
+        println!("Cancelled {} task(s) of run {}: {:?}", task_ids.len(), args.run_id, task_ids);
+    }
+    Ok(())
+}
+
+pub async fn retry_run(args: RunIdArgs) -> Result<()> {
+    let task_ids = run_action(&args, "retry").await?;
+    if task_ids.is_empty() {
+        println!("Run {} has no failed, cancelled or skipped tasks.", args.run_id);
+    } else {
+        println!("Retrying {} task(s) of run {}: {:?}", task_ids.len(), args.run_id, task_ids);
+    }
+    Ok(())
+}
+
+async fn run_action(args: &RunIdArgs, action: &str) -> Result<Vec<i64>> {
+    let client = reqwest::Client::new();
+    let url = format!("http://{}:{}/run/{}/{}",
+                      args.host.as_ref().unwrap_or(&"localhost".to_string()),
+                      args.port.unwrap_or(39512),
+                      args.run_id,
+                      action);
+
+    let response = client.post(&url).send().await?;
+    if !response.status().is_success() {
+        let message = response.text().await.unwrap_or_default();
+        return Err(anyhow!("Failed to {} run {}: {}", action, args.run_id, message));
+    }
+
+    let result: RunActionResponse = response.json().await?;
+    Ok(result.task_ids)
+}
+
 pub async fn list_tasks(args: ListArgs) -> Result<()> {
     let client = reqwest::Client::new();
     let url = format!("http://{}:{}/list", 
@@ -129,9 +376,9 @@ pub async fn list_tasks(args: ListArgs) -> Result<()> {
 
     if args.detail {
         // Detailed view with timestamps and session IDs
-        println!("{:<4} {:<25} {:<11} {:<20} {:<20} {:<36}", 
-                 "ID", "Name", "Status", "Submitted", "Finished", "Session ID");
-        println!("{}", "-".repeat(125));
+        println!("{:<4} {:<25} {:<11} {:<20} {:<20} {:<36} {:>9} {:>10}", 
+                 "ID", "Name", "Status", "Submitted", "Finished", "Session ID", "Cost", "Tokens");
+        println!("{}", "-".repeat(146));
 
         for task in &task_list.tasks {
             let finished = task.finished_at
@@ -140,14 +387,19 @@ pub async fn list_tasks(args: ListArgs) -> Result<()> {
 
             let session_id = task.session_id.clone().unwrap_or_else(|| "-".to_string());
 
-            println!("{:<4} {:<25} {:<10} {:<20} {:<20} {:<36}",
+            println!("{:<4} {:<25} {:<10} {:<20} {:<20} {:<36} {:>9} {:>10}",
                      task.id,
                      truncate(&task.name, 25),
                      format_status(&task.status),
                      task.submitted_at.format("%Y-%m-%d %H:%M:%S"),
                      finished,
-                     truncate(&session_id, 36));
+                     truncate(&session_id, 36),
+                     format_cost(task.usage.cost_usd),
+                     task.usage.total_tokens());
         }
+
+        let total_cost: f64 = task_list.tasks.iter().map(|task| task.usage.cost_usd).sum();
+        println!("\nTotal cost: {}", format_cost(total_cost));
     } else {
         // Simple view with just ID, name, and status
         println!("{:<4} {:<40} {:<10}", "ID", "Name", "Status");
@@ -161,6 +413,29 @@ pub async fn list_tasks(args: ListArgs) -> Result<()> {
         }
     }
 
+    if let Some(pause) = &task_list.paused {
+        let remaining = pause.until.signed_duration_since(Utc::now());
+        println!("\n⏸️  Scheduler paused: {}", pause.reason);
+        println!("   No new tasks will start until {} (in {} minutes)",
+                 pause.until.format("%Y-%m-%d %H:%M:%S UTC"),
+                 remaining.num_minutes().max(0));
+    }
+
+    let blocked_tasks: Vec<_> = task_list.tasks.iter()
+        .filter(|task| matches!(task.status, TaskStatus::Blocked))
+        .collect();
+
+    if !blocked_tasks.is_empty() {
+        println!("\n⛔ Blocked Tasks Information:");
+        for task in blocked_tasks {
+            match task.blocked_by.and_then(|id| task_list.tasks.iter().find(|ancestor| ancestor.id == id)) {
+                Some(ancestor) => println!("   Task {} is blocked because task {} ({}) is {}",
+                                           task.id, ancestor.id, ancestor.name, ancestor.status),
+                None => println!("   Task {} is blocked by a failed dependency", task.id),
+            }
+        }
+    }
+
     // Show waiting task information
     let waiting_tasks: Vec<_> = task_list.tasks.iter()
         .filter(|task| matches!(task.status, TaskStatus::Waiting))
@@ -184,6 +459,10 @@ pub async fn list_tasks(args: ListArgs) -> Result<()> {
         }
     }
 
+    if task_list.tasks.iter().any(|task| task.status == TaskStatus::AwaitingApproval) {
+        println!("\n✋ Tasks are waiting for tool permission; answer them with `ccsched approvals`, `ccsched approve <id>` or `ccsched deny <id>`");
+    }
+
     Ok(())
 }
 
@@ -202,6 +481,34 @@ pub async fn show_task(args: ShowArgs) -> Result<()> {
     println!("ID: {}", task.id);
     println!("Name: {}", task.name);
     println!("Status: {}", format_status(&task.status));
+    if let Some(ancestor) = task.blocked_by {
+        println!("Blocked By: task {}", ancestor);
+    }
+    if let Some(run_id) = task.run_id {
+        println!("Run: {}", run_id);
+    }
+    println!("Executor: {}", task.executor);
+    if let Some(profile) = &task.profile {
+        println!("Profile: {}", profile);
+    }
+    if task.executor == "claude" {
+        println!("Permission Mode: {}", task.permission_mode);
+        print_claude_options(&task.claude_options);
+    }
+    if !task.vars.is_empty() {
+        println!("Variables:");
+        for (key, value) in &task.vars {
+            println!("  {}={}", key, value);
+        }
+    }
+    if !task.hooks.is_default() {
+        println!("Hooks:");
+        for kind in HookKind::ALL {
+            if let Some(command) = task.hooks.get(*kind) {
+                println!("  {}: {}", kind, command);
+            }
+        }
+    }
     println!("Submitted: {}", task.submitted_at.format("%Y-%m-%d %H:%M:%S UTC"));
     
     if let Some(finished) = task.finished_at {
@@ -212,14 +519,124 @@ pub async fn show_task(args: ShowArgs) -> Result<()> {
         println!("Session ID: {}", session_id);
     }
     
+    if let Some(parent_id) = task.continue_from {
+        let lineage = if task.fork_session { "Forked From" } else { "Continued From" };
+        match &task.parent_session_id {
+            Some(session_id) => println!("{}: task {} (session {})", lineage, parent_id, session_id),
+            None => println!("{}: task {}", lineage, parent_id),
+        }
+    }
+    
+    if task.worktree {
+        match &task.worktree_path {
+            Some(path) => println!("Worktree: {}", path),
+            None if task.branch.is_some() => println!("Worktree: removed"),
+            None => println!("Worktree: not created yet"),
+        }
+        if let Some(branch) = &task.branch {
+            println!("Branch: {}", branch);
+        }
+        match (&task.base_commit, &task.base_ref) {
+            (Some(commit), Some(base_ref)) => println!("Base Commit: {} ({})", commit, base_ref),
+            (Some(commit), None) => println!("Base Commit: {}", commit),
+            (None, Some(base_ref)) => println!("Base Ref: {}", base_ref),
+            (None, None) => {}
+        }
+        if let Some(commit) = &task.final_commit {
+            println!("Final Commit: {}", commit);
+        }
+    }
+    
     if let Some(resume_at) = task.resume_at {
         println!("Resume At: {}", resume_at.format("%Y-%m-%d %H:%M:%S UTC"));
     }
     
+    if let Some(timeout) = task.timeout_secs {
+        println!("Timeout: {}s", timeout);
+    }
+    
+    if let Some(idle_timeout) = task.idle_timeout_secs {
+        println!("Idle Timeout: {}s", idle_timeout);
+    }
+    
+    if let Some(reason) = &task.failure_reason {
+        match task.failure_kind {
+            Some(kind) => println!("Failure Reason: {} ({})", reason, kind),
+            None => println!("Failure Reason: {}", reason),
+        }
+    }
+    
+    match task.max_attempts {
+        Some(max_attempts) => println!("Attempt: {} of {}", task.attempt, max_attempts),
+        None => println!("Attempt: {}", task.attempt),
+    }
+    
+    if let Some(retry_on) = &task.retry_on
+        && task.max_attempts.is_some_and(|max_attempts| max_attempts > 1)
+    {
+        let backoff = task.retry_backoff_secs.map(|secs| format!(", backoff from {secs}s")).unwrap_or_default();
+        println!("Retry On: {}{}", FailureKind::join(retry_on), backoff);
+    }
+    
+    if task.status == TaskStatus::Pending
+        && let Some(retry_at) = task.retry_at
+    {
+        println!("Next Retry: {}", retry_at.format("%Y-%m-%d %H:%M:%S UTC"));
+    }
+    
+    if task.verify {
+        match task.max_verification_rounds {
+            Some(max_rounds) => println!("Verification Rounds: {} of {}", task.verification_rounds, max_rounds),
+            None => println!("Verification Rounds: {}", task.verification_rounds),
+        }
+        if let Some(command) = &task.verify_cmd {
+            println!("Verification Command: {}", command);
+        } else if let (Some(success), Some(failure)) = (&task.success_marker, &task.failure_marker) {
+            println!("Verification Markers: {} / {}", success, failure);
+        }
+    } else {
+        println!("Verification: disabled");
+    }
+    
+    let usage = &task.usage;
+    if let Some(max_cost) = task.max_cost_usd {
+        println!("Cost: {} of {} max", format_cost(usage.cost_usd), format_cost(max_cost));
+    } else if usage.num_turns > 0 || usage.cost_usd > 0.0 {
+        println!("Cost: {}", format_cost(usage.cost_usd));
+    }
+    if usage.num_turns > 0 || usage.cost_usd > 0.0 {
+        println!(
+            "Tokens: {} input, {} output, {} cache read, {} cache creation",
+            usage.input_tokens, usage.output_tokens, usage.cache_read_tokens, usage.cache_creation_tokens
+        );
+        println!("Turns: {}, Claude Time: {:.1}s", usage.num_turns, usage.duration_ms as f64 / 1000.0);
+    }
+    
     println!("\nPrompt:");
     println!("-------");
     println!("{}", task.prompt);
     
+    if let Some(rendered) = task.rendered_prompt.as_ref().filter(|rendered| **rendered != task.prompt) {
+        println!("\nRendered Prompt:");
+        println!("----------------");
+        println!("{}", rendered);
+    }
+    
+    if task.verify
+        && task.verify_cmd.is_none()
+        && let Some(template) = &task.verification_template
+    {
+        println!("\nVerification Template:");
+        println!("----------------------");
+        println!("{}", template);
+    }
+    
+    if let Some(check_output) = &task.check_output {
+        println!("\nCheck Output:");
+        println!("-------------");
+        println!("{}", check_output.trim_end());
+    }
+    
     if let Some(result) = &task.result {
         println!("\nResult:");
         println!("-------");
@@ -230,7 +647,7 @@ pub async fn show_task(args: ShowArgs) -> Result<()> {
 }
 
 pub async fn resume_task(args: ResumeArgs) -> Result<()> {
-    if !is_local_host(&args.host.as_ref().unwrap_or(&"localhost".to_string())) {
+    if !is_local_host(args.host.as_ref().unwrap_or(&"localhost".to_string())) {
         return Err(anyhow!("Resume command can only be used with local scheduler instances"));
     }
 
@@ -330,7 +747,7 @@ pub async fn edit_task(args: EditArgs) -> Result<()> {
     let task: TaskInfoWithPrompt = response.json().await?;
     
     // Check if task is completed and warn user
-    if matches!(task.status, TaskStatus::Done | TaskStatus::Failed) {
+    if task.status.is_finished() {
         println!("⚠️  Warning: Task {} is already completed (status: {}).", task.id, format_status(&task.status));
         println!("Editing this task will reset it to pending status and start a new execution.");
         println!("The task will continue using the previous session ID: {}", 
@@ -388,10 +805,478 @@ pub async fn edit_task(args: EditArgs) -> Result<()> {
     Ok(())
 }
 
+pub async fn cancel_task(args: CancelArgs) -> Result<()> {
+    let client = reqwest::Client::new();
+    let url = format!("http://{}:{}/task/{}/cancel", 
+                      args.host.as_ref().unwrap_or(&"localhost".to_string()), 
+                      args.port.unwrap_or(39512), 
+                      args.task_id);
+
+    let response = client.post(&url).send().await?;
+
+    if !response.status().is_success() {
+        let message = response.text().await.unwrap_or_default();
+        return Err(anyhow!("Failed to cancel task {}: {}", args.task_id, message));
+    }
+
+    println!("Task {} cancelled.", args.task_id);
+    Ok(())
+}
+
+pub async fn list_approvals(args: ApprovalsArgs) -> Result<()> {
+    let client = reqwest::Client::new();
+    let url = format!("http://{}:{}/approvals", 
+                      args.host.as_ref().unwrap_or(&"localhost".to_string()), 
+                      args.port.unwrap_or(39512));
+
+    let response = client.get(&url).send().await?.error_for_status()?;
+    let approvals: ApprovalsResponse = response.json().await?;
+
+    if approvals.approvals.is_empty() {
+        println!("No pending approvals.");
+        return Ok(());
+    }
+
+    println!("{:<5} {:<6} {:<20} {:<12} Arguments",
+             "ID", "Task", "Requested", "Tool");
+    println!("{}", "-".repeat(105));
+
+    for approval in &approvals.approvals {
+        println!("{:<5} {:<6} {:<20} {:<12} {}",
+                 approval.id,
+                 approval.task_id,
+                 approval.requested_at.format("%Y-%m-%d %H:%M:%S"),
+                 truncate(&approval.tool_name, 12),
+                 truncate(&approval.input.to_string(), 60));
+    }
+
+    Ok(())
+}
+
+pub async fn approve(args: ApproveArgs) -> Result<()> {
+    let client = reqwest::Client::new();
+    let url = format!("http://{}:{}/approval/{}/approve", 
+                      args.host.as_ref().unwrap_or(&"localhost".to_string()), 
+                      args.port.unwrap_or(39512), 
+                      args.approval_id);
+
+    let response = client.post(&url).send().await?;
+
+    if !response.status().is_success() {
+        let message = response.text().await.unwrap_or_default();
+        return Err(anyhow!("Failed to approve {}: {}", args.approval_id, message));
+    }
+
+    let approval: Approval = response.json().await?;
+    println!("Approved {} for task {}.", approval.tool_name, approval.task_id);
+    Ok(())
+}
+
+pub async fn deny(args: DenyArgs) -> Result<()> {
+    let client = reqwest::Client::new();
+    let url = format!("http://{}:{}/approval/{}/deny", 
+                      args.host.as_ref().unwrap_or(&"localhost".to_string()), 
+                      args.port.unwrap_or(39512), 
+                      args.approval_id);
+
+    let decision = ApprovalDecision { message: args.message };
+    let response = client.post(&url).json(&decision).send().await?;
+
+    if !response.status().is_success() {
+        let message = response.text().await.unwrap_or_default();
+        return Err(anyhow!("Failed to deny {}: {}", args.approval_id, message));
+    }
+
+    let approval: Approval = response.json().await?;
+    println!("Denied {} for task {}.", approval.tool_name, approval.task_id);
+    Ok(())
+}
+
+pub async fn show_attempts(args: AttemptsArgs) -> Result<()> {
+    let client = reqwest::Client::new();
+    let url = format!("http://{}:{}/task/{}/attempts", 
+                      args.host.as_ref().unwrap_or(&"localhost".to_string()), 
+                      args.port.unwrap_or(39512), 
+                      args.task_id);
+
+    let response = client.get(&url).send().await?;
+
+    if !response.status().is_success() {
+        let message = response.text().await.unwrap_or_default();
+        return Err(anyhow!("Failed to get attempts of task {}: {}", args.task_id, message));
+    }
+
+    let attempt_list: TaskAttemptsResponse = response.json().await?;
+
+    if attempt_list.attempts.is_empty() {
+        println!("No attempts recorded for task {}.", args.task_id);
+        return Ok(());
+    }
+
+    println!("{:<5} {:<4} {:<13} {:<20} {:<9} {:<5} {:<36} {:<17} {:<16} {:>9}",
+             "ID", "Try", "Kind", "Started", "Duration", "Exit", "Session ID", "Log Bytes", "Result", "Cost");
+    println!("{}", "-".repeat(141));
+
+    for attempt in &attempt_list.attempts {
+        let duration = attempt.finished_at
+            .map(|finished| format!("{}s", finished.signed_duration_since(attempt.started_at).num_seconds()))
+            .unwrap_or_else(|| "-".to_string());
+
+        let exit_code = attempt.exit_code
+            .map(|code| code.to_string())
+            .unwrap_or_else(|| "-".to_string());
+
+        let session_id = attempt.session_id_out.as_ref()
+            .or(attempt.session_id_in.as_ref())
+            .cloned()
+            .unwrap_or_else(|| "-".to_string());
+
+        let log_range = match attempt.log_end {
+            Some(log_end) => format!("{}-{}", attempt.log_start, log_end),
+            None => format!("{}-", attempt.log_start),
+        };
+
+        // Summarize the result event by its subtype, e.g. "success" or "error_max_turns"
+        let result = attempt.result.as_deref()
+            .and_then(|result| serde_json::from_str::<serde_json::Value>(result).ok())
+            .and_then(|result| result.get("subtype").and_then(|v| v.as_str()).map(str::to_string))
+            .unwrap_or_else(|| "-".to_string());
+
+        println!("{:<5} {:<4} {:<13} {:<20} {:<9} {:<5} {:<36} {:<17} {:<16} {:>9}",
+                 attempt.id,
+                 attempt.attempt,
+                 attempt.kind.to_string(),
+                 attempt.started_at.format("%Y-%m-%d %H:%M:%S"),
+                 duration,
+                 exit_code,
+                 truncate(&session_id, 36),
+                 log_range,
+                 truncate(&result, 16),
+                 format_cost(attempt.usage.cost_usd));
+    }
+
+    Ok(())
+}
+
+pub async fn show_diff(args: DiffArgs) -> Result<()> {
+    let client = reqwest::Client::new();
+    let url = format!("http://{}:{}/task/{}/diff", 
+                      args.host.as_ref().unwrap_or(&"localhost".to_string()), 
+                      args.port.unwrap_or(39512), 
+                      args.task_id);
+
+    let response = client.get(&url).send().await?;
+
+    if !response.status().is_success() {
+        let message = response.text().await.unwrap_or_default();
+        return Err(anyhow!("Failed to get diff of task {}: {}", args.task_id, message));
+    }
+
+    let diff: TaskDiff = response.json().await?;
+    let (Some(stat), Some(patch)) = (&diff.stat, &diff.patch) else {
+        println!("Task {} is still running; its diff is recorded once it stops.", args.task_id);
+        return Ok(());
+    };
+
+    if patch.is_empty() {
+        println!("Task {} made no changes.", args.task_id);
+    } else if args.stat {
+        println!("{}", stat);
+    } else {
+        println!("{}", patch);
+    }
+
+    Ok(())
+}
+
+pub async fn show_dependencies(args: DepsShowArgs) -> Result<()> {
+    let client = reqwest::Client::new();
+    let url = format!("http://{}:{}/task/{}/deps",
+                      args.host.as_ref().unwrap_or(&"localhost".to_string()),
+                      args.port.unwrap_or(39512),
+                      args.task_id);
+
+    let response = client.get(&url).send().await?;
+    if !response.status().is_success() {
+        let message = response.text().await.unwrap_or_default();
+        return Err(anyhow!("Failed to get dependencies of task {}: {}", args.task_id, message));
+    }
+
+    let deps: DependenciesResponse = response.json().await?;
+    print_dependencies(args.task_id, &deps.dependencies);
+    Ok(())
+}
+
+pub async fn add_dependencies(args: DepsChangeArgs) -> Result<()> {
+    let dependencies = parse_dependencies(&args.dependencies)?;
+    change_dependencies(args.host, args.port, args.task_id, reqwest::Method::POST, "deps/add", dependencies).await
+}
+
+pub async fn remove_dependencies(args: DepsChangeArgs) -> Result<()> {
+    let dependencies = parse_dependencies(&args.dependencies)?;
+    change_dependencies(args.host, args.port, args.task_id, reqwest::Method::POST, "deps/remove", dependencies).await
+}
+
+pub async fn set_dependencies(args: DepsSetArgs) -> Result<()> {
+    let dependencies = match &args.dependencies {
+        Some(deps) if !deps.trim().is_empty() => parse_dependencies(deps)?,
+        _ => Vec::new(),
+    };
+    change_dependencies(args.host, args.port, args.task_id, reqwest::Method::PUT, "deps", dependencies).await
+}
+
+async fn change_dependencies(
+    host: Option<String>,
+    port: Option<u16>,
+    task_id: i64,
+    method: reqwest::Method,
+    path: &str,
+    dependencies: Vec<Dependency>,
+) -> Result<()> {
+    let client = reqwest::Client::new();
+    let url = format!("http://{}:{}/task/{}/{}",
+                      host.as_deref().unwrap_or("localhost"),
+                      port.unwrap_or(39512),
+                      task_id,
+                      path);
+
+    let response = client
+        .request(method, &url)
+        .json(&DependenciesRequest { dependencies })
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        let message = response.text().await.unwrap_or_default();
+        return Err(anyhow!("Failed to update dependencies of task {}: {}", task_id, message));
+    }
+
+    let deps: DependenciesResponse = response.json().await?;
+    print_dependencies(task_id, &deps.dependencies);
+    Ok(())
+}
+
+fn print_dependencies(task_id: i64, dependencies: &[Dependency]) {
+    if dependencies.is_empty() {
+        println!("Task {} has no dependencies.", task_id);
+    } else {
+        let list: Vec<String> = dependencies.iter().map(Dependency::to_string).collect();
+        println!("Task {} depends on: {}", task_id, list.join(", "));
+    }
+}
+
+pub async fn rollback_task(args: RollbackArgs) -> Result<()> {
+    let client = reqwest::Client::new();
+    let url = format!("http://{}:{}/task/{}/rollback", 
+                      args.host.as_ref().unwrap_or(&"localhost".to_string()), 
+                      args.port.unwrap_or(39512), 
+                      args.task_id);
+
+    let response = client
+        .post(&url)
+        .json(&RollbackRequest { force: args.force })
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        let message = response.text().await.unwrap_or_default();
+        return Err(anyhow!("Failed to roll back task {}: {}", args.task_id, message));
+    }
+
+    let rollback: RollbackResponse = response.json().await?;
+    if rollback.files.is_empty() {
+        println!("Task {} changed no files in {}.", args.task_id, rollback.root);
+    } else {
+        for file in &rollback.files {
+            println!("Restored {}", file);
+        }
+        println!("Rolled back {} file(s) in {}.", rollback.files.len(), rollback.root);
+    }
+
+    Ok(())
+}
+
+pub async fn show_stats(args: StatsArgs) -> Result<()> {
+    let client = reqwest::Client::new();
+    let url = format!("http://{}:{}/stats", 
+                      args.host.as_ref().unwrap_or(&"localhost".to_string()), 
+                      args.port.unwrap_or(39512));
+
+    let response = client.get(&url).send().await?.error_for_status()?;
+    let stats: StatsResponse = response.json().await?;
+
+    print_usage_groups("Day", &stats.by_day);
+    println!();
+    print_usage_groups("Working Directory", &stats.by_cwd);
+    println!();
+    print_usage_groups("Status", &stats.by_status);
+
+    let total = &stats.total;
+    println!("\nTotal: {} for {} tokens ({} input, {} output, {} cache read, {} cache creation) in {} turns",
+             format_cost(total.cost_usd),
+             total.total_tokens(),
+             total.input_tokens,
+             total.output_tokens,
+             total.cache_read_tokens,
+             total.cache_creation_tokens,
+             total.num_turns);
+
+    Ok(())
+}
+
+pub async fn show_profiles(args: ProfilesArgs) -> Result<()> {
+    let client = reqwest::Client::new();
+    let url = format!("http://{}:{}/profiles", 
+                      args.host.as_ref().unwrap_or(&"localhost".to_string()), 
+                      args.port.unwrap_or(39512));
+
+    let response = client.get(&url).send().await?.error_for_status()?;
+    let profiles: ProfilesResponse = response.json().await?;
+
+    println!("{:<16} {:<26} {:>7} {:>11} {:>12} {:>10}  Overrides",
+             "Profile", "Limited Until", "Running", "Invocations", "Tokens", "Cost");
+    println!("{}", "-".repeat(105));
+
+    for profile in &profiles.profiles {
+        let limited_until = profile.limited_until
+            .map(|until| until.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+            .unwrap_or_else(|| "-".to_string());
+
+        let mut overrides = profile.env.clone();
+        if let Some(claude_path) = &profile.claude_path {
+            overrides.insert(0, format!("claude_path={claude_path}"));
+        }
+
+        println!("{:<16} {:<26} {:>7} {:>11} {:>12} {:>10}  {}",
+                 truncate(&profile.name, 16),
+                 limited_until,
+                 profile.running,
+                 profile.invocations,
+                 profile.usage.total_tokens(),
+                 format_cost(profile.usage.cost_usd),
+                 overrides.join(", "));
+    }
+
+    Ok(())
+}
+
+pub async fn cleanup_worktrees(args: CleanupArgs) -> Result<()> {
+    let client = reqwest::Client::new();
+    let url = format!("http://{}:{}/worktrees/cleanup", 
+                      args.host.as_ref().unwrap_or(&"localhost".to_string()), 
+                      args.port.unwrap_or(39512));
+
+    let response = client.post(&url).send().await?;
+
+    if !response.status().is_success() {
+        let message = response.text().await.unwrap_or_default();
+        return Err(anyhow!("Failed to clean up worktrees: {}", message));
+    }
+
+    let cleanup: WorktreeCleanupResponse = response.json().await?;
+    if cleanup.removed.is_empty() {
+        println!("No stale worktrees found.");
+    } else {
+        for path in &cleanup.removed {
+            println!("Removed {}", path);
+        }
+        println!("Removed {} worktree(s); their branches were kept.", cleanup.removed.len());
+    }
+
+    if !cleanup.removed_checkpoints.is_empty() {
+        for checkpoint in &cleanup.removed_checkpoints {
+            println!("Removed {}", checkpoint);
+        }
+        println!("Removed {} checkpoint(s) of deleted tasks.", cleanup.removed_checkpoints.len());
+    }
+
+    Ok(())
+}
+
+fn print_claude_options(options: &ClaudeOptions) {
+    if options.is_default() {
+        println!("Claude Options: defaults");
+        return;
+    }
+
+    println!("Claude Options:");
+    if let Some(model) = &options.model {
+        println!("  Model: {}", model);
+    }
+    if let Some(fallback_model) = &options.fallback_model {
+        println!("  Fallback Model: {}", fallback_model);
+    }
+    if let Some(max_turns) = options.max_turns {
+        println!("  Max Turns: {}", max_turns);
+    }
+    if !options.allowed_tools.is_empty() {
+        println!("  Allowed Tools: {}", options.allowed_tools.join(", "));
+    }
+    if !options.disallowed_tools.is_empty() {
+        println!("  Disallowed Tools: {}", options.disallowed_tools.join(", "));
+    }
+    if let Some(prompt) = &options.append_system_prompt {
+        println!("  Appended System Prompt: {}", prompt);
+    }
+    if let Some(mcp_config) = &options.mcp_config {
+        println!("  MCP Config: {}", mcp_config);
+    }
+}
+
+fn print_usage_groups(title: &str, groups: &[UsageGroup]) {
+    println!("{:<40} {:>6} {:>12} {:>12} {:>12} {:>7} {:>10}",
+             title, "Tasks", "Input", "Output", "Cache Read", "Turns", "Cost");
+    println!("{}", "-".repeat(105));
+
+    if groups.is_empty() {
+        println!("(none)");
+        return;
+    }
+
+    for group in groups {
+        println!("{:<40} {:>6} {:>12} {:>12} {:>12} {:>7} {:>10}",
+                 truncate(&group.key, 40),
+                 group.tasks,
+                 group.usage.input_tokens,
+                 group.usage.output_tokens,
+                 group.usage.cache_read_tokens,
+                 group.usage.num_turns,
+                 format_cost(group.usage.cost_usd));
+    }
+}
+
+fn format_run_status(status: &RunStatus) -> String {
+    match status {
+        RunStatus::Pending => "⏳ pending".to_string(),
+        RunStatus::Running => "🔄 running".to_string(),
+        RunStatus::Succeeded => "✅ succeeded".to_string(),
+        RunStatus::PartiallyFailed => "⚠️ partially_failed".to_string(),
+        RunStatus::Failed => "❌ failed".to_string(),
+        RunStatus::Cancelled => "🚫 cancelled".to_string(),
+    }
+}
+
+fn format_time(time: Option<chrono::NaiveDateTime>) -> String {
+    time.map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_else(|| "-".to_string())
+}
+
+fn format_cost(cost_usd: f64) -> String {
+    format!("${:.4}", cost_usd)
+}
+
 fn is_local_host(host: &str) -> bool {
     matches!(host, "localhost" | "127.0.0.1" | "::1" | "0.0.0.0")
 }
 
+/// Parses a comma-separated list of dependencies such as `3,4:failure,5:any`.
+fn parse_dependencies(deps: &str) -> Result<Vec<Dependency>> {
+    deps.split(',')
+        .map(|dep| dep.parse::<Dependency>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| anyhow!("Invalid dependency: {}", e))
+}
+
 fn truncate(s: &str, max_len: usize) -> String {
     if s.len() <= max_len {
         s.to_string()
@@ -407,6 +1292,10 @@ fn format_status(status: &TaskStatus) -> String {
         TaskStatus::Done => "✅ done".to_string(),
         TaskStatus::Failed => "❌ failed".to_string(),
         TaskStatus::Waiting => "⏸️ waiting".to_string(),
+        TaskStatus::Cancelled => "🚫 cancelled".to_string(),
+        TaskStatus::AwaitingApproval => "✋ awaiting_approval".to_string(),
+        TaskStatus::Blocked => "⛔ blocked".to_string(),
+        TaskStatus::Skipped => "⏭️ skipped".to_string(),
     }
 }
 
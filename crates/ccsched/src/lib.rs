@@ -1,3 +1,4 @@
+pub mod approval_server;
 pub mod cli;
 pub mod client;
 pub mod models;
@@ -1,3 +1,4 @@
+use ccsched::approval_server::run_approval_server;
 use ccsched::cli::*;
 use ccsched::client::*;
 use ccsched::server::start_server;
@@ -20,6 +21,7 @@ async fn main() -> anyhow::Result<()> {
                 args.host,
                 args.port,
                 args.claude_path,
+                args.workers,
                 args.env,
             )?;
 
@@ -35,6 +37,22 @@ async fn main() -> anyhow::Result<()> {
             init_logging(false).await?;
             add_task(args).await?;
         }
+        Commands::Apply(args) => {
+            init_logging(false).await?;
+            apply_workflow(args).await?;
+        }
+        Commands::Runs(args) => {
+            init_logging(false).await?;
+            list_runs(args).await?;
+        }
+        Commands::Run(args) => {
+            init_logging(false).await?;
+            match args.command {
+                RunCommand::Show(args) => show_run(args).await?,
+                RunCommand::Cancel(args) => cancel_run(args).await?,
+                RunCommand::Retry(args) => retry_run(args).await?,
+            }
+        }
         Commands::List(args) => {
             init_logging(false).await?;
             list_tasks(args).await?;
@@ -59,6 +77,59 @@ async fn main() -> anyhow::Result<()> {
             init_logging(false).await?;
             edit_task(args).await?;
         }
+        Commands::Cancel(args) => {
+            init_logging(false).await?;
+            cancel_task(args).await?;
+        }
+        Commands::Deps(args) => {
+            init_logging(false).await?;
+            match args.command {
+                DepsCommand::Show(args) => show_dependencies(args).await?,
+                DepsCommand::Add(args) => add_dependencies(args).await?,
+                DepsCommand::Remove(args) => remove_dependencies(args).await?,
+                DepsCommand::Set(args) => set_dependencies(args).await?,
+            }
+        }
+        Commands::Attempts(args) => {
+            init_logging(false).await?;
+            show_attempts(args).await?;
+        }
+        Commands::Diff(args) => {
+            init_logging(false).await?;
+            show_diff(args).await?;
+        }
+        Commands::Rollback(args) => {
+            init_logging(false).await?;
+            rollback_task(args).await?;
+        }
+        Commands::Stats(args) => {
+            init_logging(false).await?;
+            show_stats(args).await?;
+        }
+        Commands::Profiles(args) => {
+            init_logging(false).await?;
+            show_profiles(args).await?;
+        }
+        Commands::Cleanup(args) => {
+            init_logging(false).await?;
+            cleanup_worktrees(args).await?;
+        }
+        Commands::Approvals(args) => {
+            init_logging(false).await?;
+            list_approvals(args).await?;
+        }
+        Commands::Approve(args) => {
+            init_logging(false).await?;
+            approve(args).await?;
+        }
+        Commands::Deny(args) => {
+            init_logging(false).await?;
+            deny(args).await?;
+        }
+        Commands::ApprovalServer(args) => {
+            init_logging(false).await?;
+            run_approval_server(args).await?;
+        }
     }
 
     Ok(())
@@ -1,3 +1,6 @@
 pub use ccsched_core::models::{
-    ConfigResponse, CreateTaskRequest, CreateTaskResponse, TaskInfo, TaskInfoWithPrompt, TaskListResponse, TaskStatus,
-};
\ No newline at end of file
+    Approval, ApprovalDecision, ApplyWorkflowRequest, ApplyWorkflowResponse, ApprovalStatus, ApprovalsResponse, AttemptKind, CheckpointKind, ClaudeOptions,
+    ConfigResponse, CreateApprovalRequest, CreateTaskRequest, CreateTaskResponse, DependenciesRequest, DependenciesResponse, Dependency, DependencyCondition, FailureKind, HookKind, Hooks, PermissionMode,
+    ProfilesResponse, RollbackRequest, RollbackResponse, Run, RunActionResponse, RunDetails, RunStatus, RunsResponse, StatsResponse, Task, TaskAttempt, TaskAttemptsResponse, TaskDiff, TaskInfo, TaskInfoWithPrompt, TaskListResponse,
+    TaskStatus, Usage, UsageGroup, WorkflowTask, WorktreeCleanupResponse,
+};
@@ -9,17 +9,24 @@ use axum::{
 use ccsched_core::{
     config::Config,
     db::Database,
-    scheduler::Scheduler,
+    error::CcschedError,
+    checkpoint,
+    snapshot,
+    executor::{validate_executor_name, Executors, DEFAULT_EXECUTOR},
+    worktree,
+    scheduler::{Scheduler, SchedulerHandle},
 };
 use serde_json::Value;
+use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::net::TcpListener;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
 #[derive(Clone)]
 pub struct ServerState {
     pub db: Arc<Database>,
     pub config: Config,
+    pub scheduler: SchedulerHandle,
 }
 
 pub async fn start_server(config: Config) -> anyhow::Result<()> {
@@ -29,16 +36,38 @@ pub async fn start_server(config: Config) -> anyhow::Result<()> {
     let state = ServerState {
         db: Arc::new(db),
         config: config.clone(),
+        scheduler: scheduler.handle(),
     };
 
     let app = Router::new()
         .route("/submit", post(submit_task))
+        .route("/apply", post(apply_workflow))
+        .route("/runs", get(list_runs))
+        .route("/run/:id", get(get_run))
+        .route("/run/:id/cancel", post(cancel_run))
+        .route("/run/:id/retry", post(retry_run))
         .route("/list", get(list_tasks))
         .route("/config", get(get_config))
+        .route("/stats", get(get_stats))
+        .route("/profiles", get(get_profiles))
+        .route("/worktrees/cleanup", post(cleanup_worktrees))
         .route("/task/:id", get(get_task_with_prompt))
         .route("/task/:id", delete(delete_task))
         .route("/task/:id/rename", put(rename_task))
         .route("/task/:id/edit", put(edit_task))
+        .route("/task/:id/cancel", post(cancel_task))
+        .route("/task/:id/attempts", get(get_task_attempts))
+        .route("/task/:id/deps", get(get_dependencies))
+        .route("/task/:id/deps", put(set_dependencies))
+        .route("/task/:id/deps/add", post(add_dependencies))
+        .route("/task/:id/deps/remove", post(remove_dependencies))
+        .route("/task/:id/diff", get(get_task_diff))
+        .route("/task/:id/rollback", post(rollback_task))
+        .route("/task/:id/approvals", post(request_approval))
+        .route("/approvals", get(list_approvals))
+        .route("/approval/:id", get(get_approval))
+        .route("/approval/:id/approve", post(approve))
+        .route("/approval/:id/deny", post(deny))
         .route("/task/session/:session_id", get(get_task_by_session))
         .with_state(state);
 
@@ -59,24 +88,14 @@ pub async fn start_server(config: Config) -> anyhow::Result<()> {
 
 async fn submit_task(
     State(state): State<ServerState>,
-    Json(request): Json<CreateTaskRequest>,
+    Json(mut request): Json<CreateTaskRequest>,
 ) -> Result<Json<CreateTaskResponse>, (StatusCode, String)> {
-    let db = state.db;
-
-    if let Err(e) = db.validate_dependencies(&request.depends_on).await {
-        error!("Invalid dependencies: {}", e);
-        return Err((StatusCode::BAD_REQUEST, format!("Invalid dependencies: {e}")));
-    }
+    prepare_task_request(&state, &mut request).await?;
 
-    if let Err(e) = db.check_circular_dependency(0, &request.depends_on).await {
-        error!("Circular dependency detected: {}", e);
-        return Err((StatusCode::BAD_REQUEST, format!("Circular dependency detected: {e}")));
-    }
+    let db = state.db;
+    validate_task_dependencies(&db, None, &request.depends_on).await?;
 
-    match db
-        .create_task(&request.name, &request.prompt, &request.cwd, &request.depends_on)
-        .await
-    {
+    match db.create_task(&request).await {
         Ok(task_id) => {
             info!("Created task {} with ID {}", request.name, task_id);
             Ok(Json(CreateTaskResponse { task_id }))
@@ -96,7 +115,10 @@ async fn list_tasks(
     match db.list_tasks().await {
         Ok(tasks) => {
             let task_infos: Vec<TaskInfo> = tasks.into_iter().map(TaskInfo::from).collect();
-            Ok(Json(TaskListResponse { tasks: task_infos }))
+            Ok(Json(TaskListResponse {
+                tasks: task_infos,
+                paused: state.scheduler.pause(),
+            }))
         }
         Err(e) => {
             error!("Failed to list tasks: {}", e);
@@ -135,9 +157,25 @@ async fn delete_task(
 ) -> Result<StatusCode, (StatusCode, String)> {
     let db = state.db;
 
+    // Stop the Claude process before the row disappears from under its worker
+    if let Ok(task) = db.get_task(id).await
+        && matches!(task.status, TaskStatus::Running | TaskStatus::AwaitingApproval)
+        && let Err(e) = state.scheduler.cancel_task(id).await
+    {
+        error!("Failed to cancel task {} before deleting it: {}", id, e);
+    }
+
+    let task = db.get_task(id).await.ok();
+    let checkpoint = db.get_checkpoint(id).await.ok().flatten();
+
     match db.delete_task(id).await {
         Ok(()) => {
             info!("Deleted task {}", id);
+            if let (Some(task), Some(checkpoint)) = (task, checkpoint)
+                && let Err(e) = checkpoint::remove(&checkpoint, &task.cwd).await
+            {
+                warn!("Failed to remove the checkpoint of deleted task {}: {}", id, e);
+            }
             Ok(StatusCode::NO_CONTENT)
         },
         Err(e) => {
@@ -170,6 +208,831 @@ async fn rename_task(
     }
 }
 
+/// Creates every task of a workflow and the dependencies among them at once.
+async fn apply_workflow(
+    State(state): State<ServerState>,
+    Json(workflow): Json<ApplyWorkflowRequest>,
+) -> Result<Json<ApplyWorkflowResponse>, (StatusCode, String)> {
+    if workflow.tasks.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Workflow has no tasks".to_string()));
+    }
+
+    let mut indices = HashMap::new();
+    for (index, task) in workflow.tasks.iter().enumerate() {
+        let name = &task.request.name;
+        if name.trim().is_empty() || name.contains(':') {
+            return Err((StatusCode::BAD_REQUEST, format!("Invalid workflow task name '{name}'")));
+        }
+        if indices.insert(name.as_str(), index).is_some() {
+            return Err((StatusCode::BAD_REQUEST, format!("Workflow task '{name}' is defined more than once")));
+        }
+    }
+
+    let mut dependencies = Vec::new();
+    let mut needs: Vec<Vec<usize>> = vec![Vec::new(); workflow.tasks.len()];
+    for (index, task) in workflow.tasks.iter().enumerate() {
+        for need in &task.needs {
+            let (name, condition) = match need.split_once(':') {
+                Some((name, condition)) => {
+                    let condition = condition
+                        .parse()
+                        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Workflow task '{}': {e}", task.request.name)))?;
+                    (name, condition)
+                }
+                None => (need.as_str(), DependencyCondition::Success),
+            };
+            let Some(&dependency) = indices.get(name) else {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    format!("Workflow task '{}' needs unknown task '{name}'", task.request.name),
+                ));
+            };
+            if needs[index].contains(&dependency) {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    format!("Workflow task '{}' needs task '{name}' more than once", task.request.name),
+                ));
+            }
+            needs[index].push(dependency);
+            dependencies.push((index, dependency, condition));
+        }
+    }
+
+    // Kahn's algorithm: whatever cannot be ordered is part of a cycle
+    let mut remaining: Vec<usize> = needs.iter().map(Vec::len).collect();
+    let mut ready: Vec<usize> = (0..remaining.len()).filter(|&index| remaining[index] == 0).collect();
+    let mut ordered = 0;
+    while let Some(index) = ready.pop() {
+        ordered += 1;
+        for (dependent, dependent_needs) in needs.iter().enumerate() {
+            if dependent_needs.contains(&index) {
+                remaining[dependent] -= 1;
+                if remaining[dependent] == 0 {
+                    ready.push(dependent);
+                }
+            }
+        }
+    }
+    if ordered < workflow.tasks.len() {
+        let cyclic: Vec<&str> = (0..remaining.len())
+            .filter(|&index| remaining[index] > 0)
+            .map(|index| workflow.tasks[index].request.name.as_str())
+            .collect();
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Circular dependency among workflow tasks: {}", cyclic.join(", ")),
+        ));
+    }
+
+    let mut requests = Vec::with_capacity(workflow.tasks.len());
+    for task in workflow.tasks {
+        let mut request = task.request;
+        let name = request.name.clone();
+        let context = |(status, message): (StatusCode, String)| (status, format!("Workflow task '{name}': {message}"));
+        prepare_task_request(&state, &mut request).await.map_err(context)?;
+        validate_task_dependencies(&state.db, None, &request.depends_on).await.map_err(context)?;
+        requests.push(request);
+    }
+
+    let name = workflow.name.as_deref().unwrap_or("workflow");
+    match state.db.create_run(name, &requests, &dependencies).await {
+        Ok((run_id, task_ids)) => {
+            info!("Created run {} with tasks {:?}", run_id, task_ids);
+            let task_ids = requests.into_iter().map(|request| request.name).zip(task_ids).collect();
+            Ok(Json(ApplyWorkflowResponse { run_id, task_ids }))
+        }
+        Err(e) => {
+            error!("Failed to create workflow tasks: {}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to create workflow tasks: {e}")))
+        }
+    }
+}
+
+async fn list_runs(
+    State(state): State<ServerState>,
+) -> Result<Json<RunsResponse>, (StatusCode, String)> {
+    match state.db.list_runs().await {
+        Ok(runs) => Ok(Json(RunsResponse { runs })),
+        Err(e) => {
+            error!("Failed to list runs: {}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to list runs: {e}")))
+        }
+    }
+}
+
+async fn get_run(
+    State(state): State<ServerState>,
+    Path(id): Path<i64>,
+) -> Result<Json<RunDetails>, (StatusCode, String)> {
+    match state.db.get_run(id).await {
+        Ok((run, tasks)) => Ok(Json(RunDetails {
+            run,
+            tasks: tasks.into_iter().map(TaskInfo::from).collect(),
+        })),
+        Err(e) => Err((StatusCode::NOT_FOUND, format!("Failed to get run: {e}"))),
+    }
+}
+
+/// Cancels every unfinished task of a run.
+async fn cancel_run(
+    State(state): State<ServerState>,
+    Path(id): Path<i64>,
+) -> Result<Json<RunActionResponse>, (StatusCode, String)> {
+    let tasks = match state.db.get_run(id).await {
+        Ok((_, tasks)) => tasks,
+        Err(e) => return Err((StatusCode::NOT_FOUND, format!("Failed to get run: {e}"))),
+    };
+
+    let mut task_ids = Vec::new();
+    for task in tasks.iter().filter(|task| !task.status.is_finished()) {
+        match state.scheduler.cancel_task(task.id).await {
+            Ok(()) => task_ids.push(task.id),
+            // It finished in the meantime
+            Err(CcschedError::InvalidStatusTransition { .. }) => {}
+            Err(e) => {
+                error!("Failed to cancel task {} of run {}: {}", task.id, id, e);
+                return Err((StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to cancel task {}: {e}", task.id)));
+            }
+        }
+    }
+
+    info!("Cancelled tasks {:?} of run {}", task_ids, id);
+    Ok(Json(RunActionResponse { task_ids }))
+}
+
+/// Runs the failed, cancelled and skipped tasks of a run again.
+async fn retry_run(
+    State(state): State<ServerState>,
+    Path(id): Path<i64>,
+) -> Result<Json<RunActionResponse>, (StatusCode, String)> {
+    if let Err(e) = state.db.get_run(id).await {
+        return Err((StatusCode::NOT_FOUND, format!("Failed to get run: {e}")));
+    }
+
+    match state.db.retry_run(id).await {
+        Ok(task_ids) => {
+            info!("Retrying tasks {:?} of run {}", task_ids, id);
+            Ok(Json(RunActionResponse { task_ids }))
+        }
+        Err(e) => {
+            error!("Failed to retry run {}: {}", id, e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to retry run: {e}")))
+        }
+    }
+}
+
+/// Checks that a task can continue the Claude session of `parent_id`, which
+/// has to be resumable by the same executor from the same directory.
+async fn validate_continuation(
+    state: &ServerState,
+    request: &CreateTaskRequest,
+    parent_id: i64,
+) -> Result<(), (StatusCode, String)> {
+    let parent = match state.db.get_task(parent_id).await {
+        Ok(parent) => parent,
+        Err(_) => {
+            return Err((StatusCode::BAD_REQUEST, format!("Task {parent_id} to continue from does not exist")));
+        }
+    };
+
+    let executor = request.executor.as_deref().unwrap_or(DEFAULT_EXECUTOR);
+    let supports_sessions = Executors::new(&state.config)
+        .get(executor)
+        .is_ok_and(|executor| executor.supports_sessions());
+    if !supports_sessions {
+        return Err((StatusCode::BAD_REQUEST, format!("The {executor} executor has no sessions to continue")));
+    }
+    if parent.executor != executor {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Task {parent_id} runs with the {} executor, so its session cannot be continued with {executor}", parent.executor),
+        ));
+    }
+
+    // Claude looks sessions up by the directory it runs in
+    if request.worktree {
+        return Err((StatusCode::BAD_REQUEST, "A task continuing a session cannot run in a worktree".to_string()));
+    }
+    if parent.worktree {
+        return Err((StatusCode::BAD_REQUEST, format!("Task {parent_id} ran in a worktree, so its session cannot be continued")));
+    }
+    if std::path::Path::new(&parent.cwd) != std::path::Path::new(&request.cwd) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Task {parent_id} runs in {}, so its session can only be continued from there", parent.cwd),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Validates a task request and fills in the server defaults the task will run
+/// with. Dependencies are checked separately.
+async fn prepare_task_request(
+    state: &ServerState,
+    request: &mut CreateTaskRequest,
+) -> Result<(), (StatusCode, String)> {
+    // The server-wide timeouts use 0 for no limit; a task either sets one or inherits them
+    if request.timeout_secs == Some(0) {
+        return Err((StatusCode::BAD_REQUEST, "timeout_secs must be at least 1".to_string()));
+    }
+    if request.idle_timeout_secs == Some(0) {
+        return Err((StatusCode::BAD_REQUEST, "idle_timeout_secs must be at least 1".to_string()));
+    }
+
+    if request.max_verification_rounds == Some(0) {
+        return Err((StatusCode::BAD_REQUEST, "max_verification_rounds must be at least 1".to_string()));
+    }
+
+    // Every output contains an empty marker
+    for (field, marker) in [("success_marker", &request.success_marker), ("failure_marker", &request.failure_marker)] {
+        if marker.as_ref().is_some_and(|marker| marker.trim().is_empty()) {
+            return Err((StatusCode::BAD_REQUEST, format!("{field} must not be empty")));
+        }
+    }
+
+    if let Some(command) = &request.verify_cmd {
+        if command.trim().is_empty() {
+            return Err((StatusCode::BAD_REQUEST, "verify_cmd must not be empty".to_string()));
+        }
+        if request.verify == Some(false) {
+            return Err((StatusCode::BAD_REQUEST, "verify_cmd cannot be used with verification disabled".to_string()));
+        }
+    }
+
+    if let Some(max_cost) = request.max_cost_usd
+        && !(max_cost.is_finite() && max_cost > 0.0)
+    {
+        return Err((StatusCode::BAD_REQUEST, "max_cost_usd must be a positive amount".to_string()));
+    }
+    request.max_cost_usd = request.max_cost_usd.or(state.config.budget.task_max_cost_usd);
+
+    if let Err(message) = validate_claude_options(&request.claude_options) {
+        return Err((StatusCode::BAD_REQUEST, message));
+    }
+
+    for kind in HookKind::ALL {
+        if request.hooks.get(*kind).is_some_and(|command| command.trim().is_empty()) {
+            return Err((StatusCode::BAD_REQUEST, format!("The {kind} hook must not be empty")));
+        }
+    }
+
+    if request.vars.keys().any(|key| key.trim().is_empty()) {
+        return Err((StatusCode::BAD_REQUEST, "Variable names must not be empty".to_string()));
+    }
+
+    let permission_mode = *request.permission_mode.get_or_insert(state.config.permissions.default_mode);
+    if let Err(e) = state.config.permissions.check(permission_mode) {
+        return Err((StatusCode::FORBIDDEN, e.to_string()));
+    }
+    if permission_mode == PermissionMode::AllowList && request.claude_options.allowed_tools.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "The allow-list permission mode needs allowed_tools".to_string()));
+    }
+
+    if request.worktree {
+        let base_ref = request.base_ref.as_deref().unwrap_or("HEAD");
+        if let Err(e) = worktree::resolve_commit(&request.cwd, base_ref).await {
+            return Err((StatusCode::BAD_REQUEST, e.to_string()));
+        }
+    } else if request.base_ref.is_some() {
+        return Err((StatusCode::BAD_REQUEST, "base_ref needs a worktree".to_string()));
+    }
+
+    if request.max_attempts == Some(0) {
+        return Err((StatusCode::BAD_REQUEST, "max_attempts must be at least 1".to_string()));
+    }
+
+    // Record the retry policy and verification protocol the task will actually use
+    let retry = &state.config.retry;
+    request.max_attempts.get_or_insert(retry.max_attempts);
+    request.retry_backoff_secs.get_or_insert(retry.backoff_secs);
+    request.retry_on.get_or_insert_with(|| retry.retry_on.clone());
+
+    let verification = &state.config.verification;
+    request.verification_template.get_or_insert_with(|| verification.template.clone());
+    request.success_marker.get_or_insert_with(|| verification.success_marker.clone());
+    request.failure_marker.get_or_insert_with(|| verification.failure_marker.clone());
+    request.max_verification_rounds.get_or_insert(verification.max_rounds);
+
+    if let Some(executor) = &request.executor
+        && let Err(e) = validate_executor_name(executor)
+    {
+        return Err((StatusCode::BAD_REQUEST, e.to_string()));
+    }
+
+    if let Some(parent_id) = request.continue_from {
+        validate_continuation(state, request, parent_id).await?;
+        // The parent's session is only complete once it has finished
+        if !request.depends_on.iter().any(|dependency| dependency.id == parent_id) {
+            request.depends_on.push(Dependency { id: parent_id, condition: DependencyCondition::default() });
+        }
+    } else if request.fork_session {
+        return Err((StatusCode::BAD_REQUEST, "fork_session needs continue_from".to_string()));
+    }
+
+    Ok(())
+}
+
+/// Checks that `task_id` (0 for a task being submitted) can depend on exactly
+/// `dependencies`: each task exists, is listed once and no cycle is formed.
+/// Checks that the dependencies exist and are listed once. For an existing task
+/// (`task_id` is set) they must also not close a cycle; a task that is not
+/// created yet has no dependents, so it cannot.
+async fn validate_task_dependencies(
+    db: &Database,
+    task_id: Option<i64>,
+    dependencies: &[Dependency],
+) -> Result<(), (StatusCode, String)> {
+    let ids: Vec<i64> = dependencies.iter().map(|dependency| dependency.id).collect();
+    if let Some(id) = ids.iter().enumerate().find_map(|(i, id)| ids[..i].contains(id).then_some(id)) {
+        return Err((StatusCode::BAD_REQUEST, format!("Invalid dependencies: task {id} is listed more than once")));
+    }
+
+    if let Err(e) = db.validate_dependencies(&ids).await {
+        error!("Invalid dependencies: {}", e);
+        return Err((StatusCode::BAD_REQUEST, format!("Invalid dependencies: {e}")));
+    }
+
+    if let Some(task_id) = task_id
+        && let Err(e) = db.check_circular_dependency(task_id, &ids).await
+    {
+        error!("Circular dependency detected: {}", e);
+        return Err((StatusCode::BAD_REQUEST, format!("Circular dependency detected: {e}")));
+    }
+
+    Ok(())
+}
+
+async fn current_dependencies(db: &Database, id: i64) -> Result<Vec<Dependency>, (StatusCode, String)> {
+    if let Err(e) = db.get_task(id).await {
+        return Err((StatusCode::NOT_FOUND, format!("Task not found: {e}")));
+    }
+
+    db.get_task_dependencies(id).await.map_err(|e| {
+        error!("Failed to get dependencies of task {}: {}", id, e);
+        (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to get dependencies: {e}"))
+    })
+}
+
+/// Validates and stores the complete new set of dependencies of a task.
+async fn replace_dependencies(
+    db: &Database,
+    id: i64,
+    dependencies: Vec<Dependency>,
+) -> Result<Json<DependenciesResponse>, (StatusCode, String)> {
+    validate_task_dependencies(db, Some(id), &dependencies).await?;
+
+    match db.set_task_dependencies(id, &dependencies).await {
+        Ok(true) => {
+            info!("Updated dependencies of task {}", id);
+            Ok(Json(DependenciesResponse { dependencies }))
+        }
+        Ok(false) => Err((
+            StatusCode::CONFLICT,
+            format!("Task {id} has already started; its dependencies can no longer be changed"),
+        )),
+        Err(e @ CcschedError::TaskNotFound(_)) => Err((StatusCode::NOT_FOUND, e.to_string())),
+        Err(e) => {
+            error!("Failed to update dependencies of task {}: {}", id, e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to update dependencies: {e}")))
+        }
+    }
+}
+
+async fn get_dependencies(
+    State(state): State<ServerState>,
+    Path(id): Path<i64>,
+) -> Result<Json<DependenciesResponse>, (StatusCode, String)> {
+    let dependencies = current_dependencies(&state.db, id).await?;
+    Ok(Json(DependenciesResponse { dependencies }))
+}
+
+async fn set_dependencies(
+    State(state): State<ServerState>,
+    Path(id): Path<i64>,
+    Json(request): Json<DependenciesRequest>,
+) -> Result<Json<DependenciesResponse>, (StatusCode, String)> {
+    replace_dependencies(&state.db, id, request.dependencies).await
+}
+
+/// Adds dependencies to a task; an existing dependency takes the new condition.
+async fn add_dependencies(
+    State(state): State<ServerState>,
+    Path(id): Path<i64>,
+    Json(request): Json<DependenciesRequest>,
+) -> Result<Json<DependenciesResponse>, (StatusCode, String)> {
+    let mut dependencies = current_dependencies(&state.db, id).await?;
+    for added in request.dependencies {
+        match dependencies.iter_mut().find(|dependency| dependency.id == added.id) {
+            Some(dependency) => dependency.condition = added.condition,
+            None => dependencies.push(added),
+        }
+    }
+
+    replace_dependencies(&state.db, id, dependencies).await
+}
+
+async fn remove_dependencies(
+    State(state): State<ServerState>,
+    Path(id): Path<i64>,
+    Json(request): Json<DependenciesRequest>,
+) -> Result<Json<DependenciesResponse>, (StatusCode, String)> {
+    let mut dependencies = current_dependencies(&state.db, id).await?;
+    for removed in request.dependencies {
+        let Some(index) = dependencies.iter().position(|dependency| dependency.id == removed.id) else {
+            return Err((StatusCode::BAD_REQUEST, format!("Task {id} does not depend on task {}", removed.id)));
+        };
+        dependencies.remove(index);
+    }
+
+    replace_dependencies(&state.db, id, dependencies).await
+}
+
+async fn cancel_task(
+    State(state): State<ServerState>,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    match state.scheduler.cancel_task(id).await {
+        Ok(()) => {
+            info!("Cancel requested for task {}", id);
+            Ok(StatusCode::OK)
+        }
+        Err(e @ CcschedError::InvalidStatusTransition { .. }) => {
+            Err((StatusCode::CONFLICT, format!("Failed to cancel task: {e}")))
+        }
+        Err(e @ CcschedError::TaskNotFound(_)) => Err((StatusCode::NOT_FOUND, e.to_string())),
+        Err(e) => {
+            error!("Failed to cancel task {}: {}", id, e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to cancel task: {e}")))
+        }
+    }
+}
+
+/// Called by a task's approval MCP server when Claude asks to use a tool.
+async fn request_approval(
+    State(state): State<ServerState>,
+    Path(id): Path<i64>,
+    Json(request): Json<CreateApprovalRequest>,
+) -> Result<Json<Approval>, (StatusCode, String)> {
+    match state.db.create_approval(id, &request.tool_name, &request.input).await {
+        Ok(approval) => {
+            info!("Task {} requests approval {} to use {}", id, approval.id, approval.tool_name);
+            Ok(Json(approval))
+        }
+        Err(e @ CcschedError::InvalidStatusTransition { .. }) => {
+            Err((StatusCode::CONFLICT, format!("Failed to request approval: {e}")))
+        }
+        Err(e) => {
+            error!("Failed to request approval for task {}: {}", id, e);
+            Err((StatusCode::NOT_FOUND, format!("Failed to request approval: {e}")))
+        }
+    }
+}
+
+async fn list_approvals(
+    State(state): State<ServerState>,
+) -> Result<Json<ApprovalsResponse>, (StatusCode, String)> {
+    match state.db.get_pending_approvals().await {
+        Ok(approvals) => Ok(Json(ApprovalsResponse { approvals })),
+        Err(e) => {
+            error!("Failed to get approvals: {}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to get approvals: {e}")))
+        }
+    }
+}
+
+async fn get_approval(
+    State(state): State<ServerState>,
+    Path(id): Path<i64>,
+) -> Result<Json<Approval>, (StatusCode, String)> {
+    match state.db.get_approval(id).await {
+        Ok(approval) => Ok(Json(approval)),
+        Err(e) => Err((StatusCode::NOT_FOUND, e.to_string())),
+    }
+}
+
+async fn approve(
+    State(state): State<ServerState>,
+    Path(id): Path<i64>,
+) -> Result<Json<Approval>, (StatusCode, String)> {
+    decide_approval(&state, id, ApprovalStatus::Approved, None).await
+}
+
+async fn deny(
+    State(state): State<ServerState>,
+    Path(id): Path<i64>,
+    decision: Option<Json<ApprovalDecision>>,
+) -> Result<Json<Approval>, (StatusCode, String)> {
+    let message = decision.and_then(|Json(decision)| decision.message);
+    decide_approval(&state, id, ApprovalStatus::Denied, message.as_deref()).await
+}
+
+async fn decide_approval(
+    state: &ServerState,
+    id: i64,
+    status: ApprovalStatus,
+    message: Option<&str>,
+) -> Result<Json<Approval>, (StatusCode, String)> {
+    match state.db.decide_approval(id, status, message).await {
+        Ok(approval) => {
+            info!("Approval {} of task {} {}", id, approval.task_id, status);
+            Ok(Json(approval))
+        }
+        Err(e @ CcschedError::InvalidStatusTransition { .. }) => {
+            Err((StatusCode::CONFLICT, format!("Approval {id} was already answered: {e}")))
+        }
+        Err(e) => Err((StatusCode::NOT_FOUND, e.to_string())),
+    }
+}
+
+async fn get_task_attempts(
+    State(state): State<ServerState>,
+    Path(id): Path<i64>,
+) -> Result<Json<TaskAttemptsResponse>, (StatusCode, String)> {
+    let db = state.db;
+
+    if let Err(e) = db.get_task(id).await {
+        return Err((StatusCode::NOT_FOUND, format!("Task not found: {e}")));
+    }
+
+    match db.get_task_attempts(id).await {
+        Ok(attempts) => Ok(Json(TaskAttemptsResponse { attempts })),
+        Err(e) => {
+            error!("Failed to get attempts of task {}: {}", id, e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to get attempts: {e}")))
+        }
+    }
+}
+
+async fn get_task_diff(
+    State(state): State<ServerState>,
+    Path(id): Path<i64>,
+) -> Result<Json<TaskDiff>, (StatusCode, String)> {
+    let db = state.db;
+
+    if let Err(e) = db.get_task(id).await {
+        return Err((StatusCode::NOT_FOUND, format!("Task not found: {e}")));
+    }
+
+    match db.get_task_diff(id).await {
+        Ok(Some(diff)) => Ok(Json(diff)),
+        Ok(None) => Err((StatusCode::NOT_FOUND, format!("No diff recorded for task {id}; it has not run in a git repository"))),
+        Err(e) => {
+            error!("Failed to get diff of task {}: {}", id, e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to get diff: {e}")))
+        }
+    }
+}
+
+/// Restores the files a task changed to their state in its checkpoint.
+async fn rollback_task(
+    State(state): State<ServerState>,
+    Path(id): Path<i64>,
+    Json(request): Json<RollbackRequest>,
+) -> Result<Json<RollbackResponse>, (StatusCode, String)> {
+    let db = state.db;
+
+    let task = match db.get_task(id).await {
+        Ok(task) => task,
+        Err(e) => return Err((StatusCode::NOT_FOUND, format!("Task not found: {e}"))),
+    };
+    let checkpoint = match db.get_checkpoint(id).await {
+        Ok(Some(checkpoint)) => checkpoint,
+        Ok(None) => return Err((StatusCode::NOT_FOUND, format!("Task {id} has no checkpoint"))),
+        Err(e) => {
+            error!("Failed to get checkpoint of task {}: {}", id, e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to get checkpoint: {e}")));
+        }
+    };
+
+    if matches!(task.status, TaskStatus::Running | TaskStatus::AwaitingApproval) {
+        return Err((StatusCode::CONFLICT, format!("Task {id} is still running")));
+    }
+    let Some(files) = checkpoint.changed_files.clone() else {
+        return Err((StatusCode::CONFLICT, format!("Task {id} has no record of the files it changed")));
+    };
+
+    if !request.force {
+        let later = match db.get_later_checkpoints(&checkpoint).await {
+            Ok(later) => later,
+            Err(e) => {
+                error!("Failed to get checkpoints after task {}: {}", id, e);
+                return Err((StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to get checkpoints: {e}")));
+            }
+        };
+
+        let mut conflicts = Vec::new();
+        for other in later.iter().filter(|other| other.rolled_back_at.is_none()) {
+            match &other.changed_files {
+                None => conflicts.push(format!("task {} is still running in {}", other.task_id, checkpoint.root)),
+                Some(other_files) => {
+                    let shared: Vec<&str> = other_files
+                        .iter()
+                        .filter(|file| files.contains(file))
+                        .map(String::as_str)
+                        .collect();
+                    if !shared.is_empty() {
+                        conflicts.push(format!("task {} also changed {}", other.task_id, shared.join(", ")));
+                    }
+                }
+            }
+        }
+        if !conflicts.is_empty() {
+            return Err((
+                StatusCode::CONFLICT,
+                format!("Refusing to roll back task {id}: {}; use --force to roll back anyway", conflicts.join("; ")),
+            ));
+        }
+    }
+
+    let restored = match checkpoint.kind {
+        CheckpointKind::Git => checkpoint::restore_git(&checkpoint.root, &checkpoint.location, &files).await,
+        CheckpointKind::Tarball => match db.get_checkpoint_manifest(id).await {
+            Ok(manifest) => {
+                let before = manifest.and_then(|manifest| serde_json::from_str(&manifest).ok()).unwrap_or_default();
+                checkpoint::restore_tarball(&checkpoint.root, &checkpoint.location, &before, &files).await
+            }
+            Err(e) => Err(e),
+        },
+    };
+    if let Err(e) = restored {
+        error!("Failed to roll back task {}: {}", id, e);
+        return Err((StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to roll back: {e}")));
+    }
+
+    if let Err(e) = db.mark_checkpoint_rolled_back(id).await {
+        error!("Failed to record rollback of task {}: {}", id, e);
+    }
+    info!("Rolled back {} file(s) changed by task {}", files.len(), id);
+
+    Ok(Json(RollbackResponse { root: checkpoint.root, files }))
+}
+
+async fn get_stats(
+    State(state): State<ServerState>,
+) -> Result<Json<StatsResponse>, (StatusCode, String)> {
+    match state.db.get_usage_stats().await {
+        Ok(stats) => Ok(Json(stats)),
+        Err(e) => {
+            error!("Failed to get usage stats: {}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to get usage stats: {e}")))
+        }
+    }
+}
+
+fn validate_claude_options(options: &ClaudeOptions) -> Result<(), String> {
+    let fields = [
+        ("model", &options.model),
+        ("fallback_model", &options.fallback_model),
+        ("append_system_prompt", &options.append_system_prompt),
+        ("mcp_config", &options.mcp_config),
+    ];
+    for (field, value) in fields {
+        if let Some(value) = value
+            && value.trim().is_empty()
+        {
+            return Err(format!("{field} must not be empty"));
+        }
+    }
+
+    if options.max_turns == Some(0) {
+        return Err("max_turns must be at least 1".to_string());
+    }
+
+    if options.allowed_tools.iter().chain(&options.disallowed_tools).any(|tool| tool.trim().is_empty()) {
+        return Err("Tool names must not be empty".to_string());
+    }
+
+    Ok(())
+}
+
+async fn get_profiles(
+    State(state): State<ServerState>,
+) -> Result<Json<ProfilesResponse>, (StatusCode, String)> {
+    let usage = match state.db.get_profile_usage().await {
+        Ok(usage) => usage,
+        Err(e) => {
+            error!("Failed to get profile usage: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to get profile usage: {e}")));
+        }
+    };
+
+    let mut profiles = state.scheduler.profiles();
+    for profile in &mut profiles {
+        if let Some((invocations, profile_usage)) = usage.get(&profile.name) {
+            profile.invocations = *invocations;
+            profile.usage = *profile_usage;
+        }
+    }
+
+    Ok(Json(ProfilesResponse { profiles }))
+}
+
+/// Removes the worktrees of done, cancelled and deleted tasks. Their branches
+/// are kept. Failed tasks keep their worktrees, since their uncommitted work is
+/// where a retry or edit of the task continues.
+async fn cleanup_worktrees(
+    State(state): State<ServerState>,
+) -> Result<Json<WorktreeCleanupResponse>, (StatusCode, String)> {
+    let tasks: HashMap<i64, Task> = match state.db.list_tasks().await {
+        Ok(tasks) => tasks.into_iter().map(|task| (task.id, task)).collect(),
+        Err(e) => {
+            error!("Failed to list tasks: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to list tasks: {e}")));
+        }
+    };
+    let finished = |id: &i64| {
+        tasks
+            .get(id)
+            .is_none_or(|task| matches!(task.status, TaskStatus::Done | TaskStatus::Cancelled))
+    };
+
+    let mut stale: Vec<(i64, String)> = tasks
+        .values()
+        .filter(|task| finished(&task.id))
+        .filter_map(|task| Some((task.id, task.worktree_path.clone()?)))
+        .collect();
+    let worktree_dir = std::path::Path::new(&state.config.worktree_dir);
+    match worktree::task_dirs(worktree_dir) {
+        Ok(dirs) => stale.extend(dirs.into_iter().filter(|(id, _)| finished(id))),
+        Err(e) => {
+            error!("Failed to read worktree directory: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to read worktree directory: {e}")));
+        }
+    }
+    stale.sort();
+    stale.dedup();
+
+    let mut removed = Vec::new();
+    for (id, path) in stale {
+        if let Err(e) = worktree::remove(&path).await {
+            error!("Failed to remove worktree of task {}: {}", id, e);
+            continue;
+        }
+        if tasks.contains_key(&id)
+            && let Err(e) = state.db.clear_task_worktree(id).await
+        {
+            error!("Failed to clear worktree of task {}: {}", id, e);
+        }
+        info!("Removed worktree {} of task {}", path, id);
+        removed.push(path);
+    }
+
+    let removed_checkpoints = remove_orphaned_checkpoints(&state.db, &tasks).await;
+
+    Ok(Json(WorktreeCleanupResponse { removed, removed_checkpoints }))
+}
+
+/// Removes the snapshot refs and tarballs left behind by deleted tasks. Refs are
+/// only found in repositories other tasks still have checkpoints in.
+async fn remove_orphaned_checkpoints(db: &Database, tasks: &HashMap<i64, Task>) -> Vec<String> {
+    let mut removed = Vec::new();
+
+    match checkpoint::tarballs() {
+        Ok(tarballs) => {
+            for (id, path) in tarballs.into_iter().filter(|(id, _)| !tasks.contains_key(id)) {
+                match std::fs::remove_file(&path) {
+                    Ok(()) => {
+                        info!("Removed checkpoint {} of deleted task {}", path, id);
+                        removed.push(path);
+                    }
+                    Err(e) => error!("Failed to remove checkpoint {} of deleted task {}: {}", path, id, e),
+                }
+            }
+        }
+        Err(e) => error!("Failed to read checkpoint directory: {}", e),
+    }
+
+    let repos = match db.get_checkpoint_repos().await {
+        Ok(repos) => repos,
+        Err(e) => {
+            error!("Failed to list checkpoint repositories: {}", e);
+            return removed;
+        }
+    };
+    for repo in repos {
+        // The worktree a checkpoint was taken in may be gone
+        let ids = match snapshot::task_ids(&repo).await {
+            Ok(ids) => ids,
+            Err(e) => {
+                warn!("Failed to list snapshot refs in {}: {}", repo, e);
+                continue;
+            }
+        };
+        for id in ids.into_iter().filter(|id| !tasks.contains_key(id)) {
+            match snapshot::remove_refs(&repo, id).await {
+                Ok(()) => {
+                    info!("Removed snapshot refs of deleted task {} in {}", id, repo);
+                    removed.push(format!("{repo}: refs/ccsched/task-{id}"));
+                }
+                Err(e) => error!("Failed to remove snapshot refs of deleted task {} in {}: {}", id, repo, e),
+            }
+        }
+    }
+
+    removed
+}
+
 async fn get_config(
     State(state): State<ServerState>,
 ) -> Result<Json<ConfigResponse>, (StatusCode, String)> {
@@ -198,7 +1061,7 @@ async fn edit_task(
         }
     };
 
-    let update_result = if matches!(task.status, TaskStatus::Done | TaskStatus::Failed) {
+    let update_result = if task.status.is_finished() {
         // Task is completed, reset status to pending
         db.update_task_prompt_and_reset_status(id, prompt).await
     } else {